@@ -0,0 +1,34 @@
+//! Request/Reply server that shuts down cleanly on Ctrl-C
+//!
+//! Run with: cargo run --example graceful_server --features signals
+//! Then send requests with: cargo run --example reqrep_example client
+
+use oxide_msg::install_ctrlc_handler;
+use oxide_msg::prelude::*;
+use serde_json::json;
+
+fn main() -> Result<()> {
+    let token = install_ctrlc_handler()?;
+
+    println!("Starting server on tcp://127.0.0.1:5556 (Ctrl-C to stop)");
+    let replier = Replier::new("tcp://127.0.0.1:5556")?;
+
+    let mut request_count = 0;
+    replier.serve_until(&token, |request| {
+        request_count += 1;
+        println!(
+            "[Request #{}] {}: {}",
+            request_count, request.topic, request.payload
+        );
+        Ok(Message::new(
+            "echo_reply",
+            json!({
+                "original": request.payload,
+                "request_count": request_count
+            }),
+        ))
+    })?;
+
+    println!("\nShutting down after {} requests", request_count);
+    Ok(())
+}
@@ -3,6 +3,7 @@
 //! Run the publisher with: cargo run --example pubsub_example publisher
 //! Run the subscriber with: cargo run --example pubsub_example subscriber
 
+use oxide_msg::patterns::{wait_until_subscribed, XPublisher};
 use oxide_msg::prelude::*;
 use serde_json::json;
 use std::env;
@@ -27,10 +28,12 @@ fn main() -> Result<()> {
 
 fn run_publisher() -> Result<()> {
     println!("Starting publisher on tcp://127.0.0.1:5555");
-    let publisher = Publisher::new("tcp://127.0.0.1:5555")?;
-
-    // Give subscribers time to connect
-    thread::sleep(Duration::from_millis(500));
+    // An XPUB socket sees subscriptions arrive, so there is no need to
+    // sleep past the slow-joiner window before the first message
+    let publisher = XPublisher::new("tcp://127.0.0.1:5555")?;
+    println!("Waiting for a subscriber...");
+    let subscription = wait_until_subscribed(&publisher, -1)?;
+    println!("Subscriber joined for topic {:?}", subscription.topic);
 
     let mut count = 0;
     loop {
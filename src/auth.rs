@@ -0,0 +1,381 @@
+//! ZMTP security mechanisms and PLAIN username/password authentication
+//!
+//! Both ends of a connection must use the same mechanism, with one side as
+//! the server, or the handshake fails and no messages flow.
+//! [`SecurityMechanism`] names a mechanism and role in one value, and
+//! [`SocketBuilder::security`](crate::SocketBuilder::security) sets every
+//! option it needs:
+//!
+//! - `Null`: no authentication or encryption, ZeroMQ's default. Needs
+//!   nothing on either side.
+//! - `PlainServer` / `PlainClient`: username and password, described below.
+//!   The client needs credentials; the server needs a ZAP handler to check
+//!   them.
+//! - `CurveServer` / `CurveClient`: CurveZMQ public-key encryption. Keys
+//!   are 40-character Z85 strings, as produced by `zmq::CurveKeyPair`. The
+//!   server needs its secret key, the client its own key pair plus the
+//!   server's public key. Requires a libzmq built with CURVE support
+//!   (`zmq::has("curve")`).
+//!
+//! ## PLAIN
+//!
+//! A binding socket built with [`SocketBuilder::plain_server`] asks every
+//! connecting peer for the credentials set with
+//! [`SocketBuilder::plain_auth`]. ZeroMQ hands those credentials to the ZAP
+//! handler of the server's context, if there is one; [`ZapHandler`] is a
+//! minimal handler that checks them against a fixed map.
+//! Every pattern type also has `set_plain_server` and `set_plain_auth`
+//! setters, which apply to endpoints it binds or connects afterwards.
+//!
+//! PLAIN sends the username and password in cleartext and does not encrypt
+//! the messages that follow. Only use it on trusted networks or inside an
+//! encrypted tunnel such as a VPN or SSH forward. Security mechanisms are
+//! not applied to `inproc://` connections.
+//!
+//! ```no_run
+//! use oxide_msg::auth::ZapHandler;
+//! use oxide_msg::{Replier, Requester};
+//! use std::collections::HashMap;
+//!
+//! let context = zmq::Context::new();
+//! let users = HashMap::from([("admin".to_string(), "secret".to_string())]);
+//! let _zap = ZapHandler::start(&context, users)?;
+//!
+//! let replier = Replier::builder()
+//!     .context(&context)
+//!     .plain_server(true)
+//!     .bind("tcp://127.0.0.1:5556")?;
+//! let requester = Requester::builder()
+//!     .plain_auth("admin", "secret")
+//!     .connect("tcp://127.0.0.1:5556")?;
+//! # Ok::<(), oxide_msg::OxideError>(())
+//! ```
+//!
+//! [`SocketBuilder::plain_server`]: crate::SocketBuilder::plain_server
+//! [`SocketBuilder::plain_auth`]: crate::SocketBuilder::plain_auth
+
+use crate::builder::new_socket;
+use crate::error::{OxideError, Result};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use zmq::{Context, Socket};
+
+/// Security mechanism and role of a socket, see the
+/// [module documentation](self)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecurityMechanism {
+    /// No security (`ZMQ_NULL`)
+    Null,
+    /// Accept PLAIN logins (`ZMQ_PLAIN_SERVER`)
+    PlainServer,
+    /// Log in to a PLAIN server
+    PlainClient { username: String, password: String },
+    /// Accept CURVE clients, using the server's Z85 `secret_key`
+    CurveServer { secret_key: String },
+    /// Connect to a CURVE server with Z85 public key `server_key`
+    CurveClient {
+        server_key: String,
+        public_key: String,
+        secret_key: String,
+    },
+}
+
+impl SecurityMechanism {
+    /// Set the options for this mechanism on a socket that has not yet
+    /// bound or connected
+    pub(crate) fn apply(&self, socket: &Socket) -> Result<()> {
+        let option = |result: zmq::Result<()>| result.map_err(OxideError::SocketOption);
+        match self {
+            // Clearing PLAIN_SERVER resets the mechanism to NULL
+            SecurityMechanism::Null => option(socket.set_plain_server(false)),
+            SecurityMechanism::PlainServer => option(socket.set_plain_server(true)),
+            SecurityMechanism::PlainClient { username, password } => {
+                option(socket.set_plain_username(Some(username)))?;
+                option(socket.set_plain_password(Some(password)))
+            }
+            SecurityMechanism::CurveServer { secret_key } => {
+                require_curve()?;
+                option(socket.set_curve_server(true))?;
+                option(socket.set_curve_secretkey(secret_key.as_bytes()))
+            }
+            SecurityMechanism::CurveClient {
+                server_key,
+                public_key,
+                secret_key,
+            } => {
+                require_curve()?;
+                option(socket.set_curve_serverkey(server_key.as_bytes()))?;
+                option(socket.set_curve_publickey(public_key.as_bytes()))?;
+                option(socket.set_curve_secretkey(secret_key.as_bytes()))
+            }
+        }
+    }
+}
+
+fn require_curve() -> Result<()> {
+    if zmq::has("curve") == Some(true) {
+        Ok(())
+    } else {
+        Err(OxideError::Configuration(
+            "libzmq was built without CURVE support".to_string(),
+        ))
+    }
+}
+
+/// Endpoint libzmq sends authentication requests to
+const ZAP_ENDPOINT: &str = "inproc://zeromq.zap.01";
+
+/// How often the handler thread checks whether it should stop
+const ZAP_POLL_MS: i32 = 100;
+
+/// ZAP handler accepting PLAIN logins found in a username/password map
+///
+/// Handles requests for every socket of the context it was started in,
+/// until it is dropped. Only one handler can run per context. Non-PLAIN
+/// requests are rejected.
+pub struct ZapHandler {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ZapHandler {
+    /// Start answering authentication requests in `context`
+    pub fn start(context: &Context, credentials: HashMap<String, String>) -> Result<Self> {
+        let socket = new_socket(context, zmq::REP)?;
+        socket
+            .bind(ZAP_ENDPOINT)
+            .map_err(|e| OxideError::Configuration(format!("cannot start ZAP handler: {}", e)))?;
+        socket
+            .set_rcvtimeo(ZAP_POLL_MS)
+            .map_err(OxideError::SocketOption)?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || Self::serve(socket, &credentials, &stop))
+        };
+        Ok(Self {
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    fn serve(socket: Socket, credentials: &HashMap<String, String>, stop: &AtomicBool) {
+        while !stop.load(Ordering::Relaxed) {
+            let request = match socket.recv_multipart(0) {
+                Ok(request) => request,
+                Err(zmq::Error::EAGAIN) => continue,
+                Err(_) => return,
+            };
+            let reply = Self::reply(&request, credentials);
+            if socket.send_multipart(reply, 0).is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Build the ZAP reply for one request (RFC 27)
+    fn reply(request: &[Vec<u8>], credentials: &HashMap<String, String>) -> Vec<Vec<u8>> {
+        let request_id = request.get(1).cloned().unwrap_or_default();
+        let (status, text, user) = match request {
+            [_, _, _, _, _, mechanism, username, password] if mechanism == b"PLAIN" => {
+                let username = String::from_utf8_lossy(username);
+                match credentials.get(username.as_ref()) {
+                    Some(expected) if expected.as_bytes() == password.as_slice() => {
+                        ("200", "OK", username.into_owned())
+                    }
+                    _ => ("400", "Invalid username or password", String::new()),
+                }
+            }
+            _ => ("400", "Unsupported mechanism", String::new()),
+        };
+        vec![
+            b"1.0".to_vec(),
+            request_id,
+            status.as_bytes().to_vec(),
+            text.as_bytes().to_vec(),
+            user.into_bytes(),
+            Vec::new(),
+        ]
+    }
+}
+
+impl Drop for ZapHandler {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::Message;
+    use crate::monitor::SocketMonitor;
+    use crate::patterns::{Replier, Requester};
+    use serde_json::json;
+    use zmq::SocketEvent;
+
+    /// Wait for the monitored socket to finish a handshake, either way
+    fn handshake_outcome(monitor: &SocketMonitor) -> SocketEvent {
+        loop {
+            let event = monitor
+                .next_event_timeout(10_000)
+                .unwrap()
+                .expect("no handshake within 10 s");
+            match event.event {
+                SocketEvent::HANDSHAKE_SUCCEEDED
+                | SocketEvent::HANDSHAKE_FAILED_AUTH
+                | SocketEvent::HANDSHAKE_FAILED_PROTOCOL
+                | SocketEvent::HANDSHAKE_FAILED_NO_DETAIL => return event.event,
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn test_plain_auth_accepts_only_valid_credentials() {
+        let address = "tcp://127.0.0.1:15594";
+        let context = Context::new();
+        let users = HashMap::from([("admin".to_string(), "secret".to_string())]);
+        let _zap = ZapHandler::start(&context, users).unwrap();
+
+        let replier = Replier::builder()
+            .context(&context)
+            .plain_server(true)
+            .bind(address)
+            .unwrap();
+        // Hand the replier back so its socket is not closed while the reply
+        // is still on its way
+        let server = thread::spawn(move || {
+            let request = replier.receive_timeout(10_000).unwrap().unwrap();
+            replier.reply(&request).unwrap();
+            replier
+        });
+
+        // `immediate` only queues on connections that passed the handshake,
+        // so once the handshake has failed nothing can be sent
+        let request = Message::new("ping", json!(null));
+        let (intruder, monitor) = Requester::builder()
+            .immediate(true)
+            .plain_auth("admin", "wrong")
+            .connect_monitored(address)
+            .unwrap();
+        assert_ne!(
+            handshake_outcome(&monitor),
+            SocketEvent::HANDSHAKE_SUCCEEDED
+        );
+        assert!(matches!(
+            intruder.request_timeout_full(&request, 100, 100),
+            Err(OxideError::Send(zmq::Error::EAGAIN))
+        ));
+        drop(intruder);
+
+        let (requester, monitor) = Requester::builder()
+            .immediate(true)
+            .plain_auth("admin", "secret")
+            .connect_monitored(address)
+            .unwrap();
+        assert_eq!(
+            handshake_outcome(&monitor),
+            SocketEvent::HANDSHAKE_SUCCEEDED
+        );
+        let reply = requester
+            .request_timeout_full(&request, 10_000, 10_000)
+            .unwrap();
+        assert_eq!(reply.topic, "ping");
+        drop(server.join().unwrap());
+    }
+
+    #[test]
+    fn test_plain_setters_apply_to_later_endpoints() {
+        let replier = Replier::new("tcp://127.0.0.1:35594").unwrap();
+        replier.set_plain_server(true).unwrap();
+        replier.bind("tcp://127.0.0.1:45594").unwrap();
+        assert_eq!(
+            replier
+                .with_raw_socket(|socket| socket.get_mechanism())
+                .unwrap(),
+            zmq::Mechanism::ZMQ_PLAIN
+        );
+
+        // A NULL client can use the first endpoint but not the second
+        let (_open, monitor) = Requester::builder()
+            .connect_monitored("tcp://127.0.0.1:35594")
+            .unwrap();
+        assert_eq!(
+            handshake_outcome(&monitor),
+            SocketEvent::HANDSHAKE_SUCCEEDED
+        );
+        let (_refused, monitor) = Requester::builder()
+            .connect_monitored("tcp://127.0.0.1:45594")
+            .unwrap();
+        assert_ne!(
+            handshake_outcome(&monitor),
+            SocketEvent::HANDSHAKE_SUCCEEDED
+        );
+
+        let requester = Requester::new("tcp://127.0.0.1:35594").unwrap();
+        requester.set_plain_auth("admin", "secret").unwrap();
+        let username = requester.with_raw_socket(|socket| socket.get_plain_username());
+        assert_eq!(username.unwrap().unwrap(), "admin");
+    }
+
+    #[test]
+    fn test_null_peers_connect() {
+        let address = "tcp://127.0.0.1:15618";
+        let replier = Replier::builder()
+            .security(SecurityMechanism::Null)
+            .bind(address)
+            .unwrap();
+        // Hand the replier back so its context outlives the reply in flight
+        let server = thread::spawn(move || {
+            let request = replier.receive_timeout(5000).unwrap().unwrap();
+            replier.reply(&request).unwrap();
+            replier
+        });
+
+        let requester = Requester::builder()
+            .immediate(true)
+            .security(SecurityMechanism::Null)
+            .connect(address)
+            .unwrap();
+        let reply = requester
+            .request_timeout_full(&Message::new("ping", json!(null)), 5000, 5000)
+            .unwrap();
+        assert_eq!(reply.topic, "ping");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_mismatched_mechanisms_fail() {
+        let address = "tcp://127.0.0.1:25618";
+        let _replier = Replier::builder()
+            .security(SecurityMechanism::PlainServer)
+            .bind(address)
+            .unwrap();
+
+        let requester = Requester::builder()
+            .immediate(true)
+            .security(SecurityMechanism::Null)
+            .connect(address)
+            .unwrap();
+        assert!(matches!(
+            requester.request_timeout_full(&Message::new("ping", json!(null)), 500, 500),
+            Err(OxideError::Send(zmq::Error::EAGAIN))
+        ));
+
+        if zmq::has("curve") != Some(true) {
+            let curve = Requester::builder()
+                .security(SecurityMechanism::CurveServer {
+                    secret_key: "0".repeat(40),
+                })
+                .connect(address);
+            assert!(matches!(curve, Err(OxideError::Configuration(_))));
+        }
+    }
+}
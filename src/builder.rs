@@ -0,0 +1,1146 @@
+//! Configure pattern sockets before they bind or connect
+//!
+//! Some ZeroMQ options only affect connections made after they are set, so
+//! [`SocketBuilder`] applies its options to the fresh socket before calling
+//! `bind` or `connect`. Every pattern type has a `builder()` constructor:
+//!
+//! ```no_run
+//! use oxide_msg::Subscriber;
+//!
+//! let subscriber = Subscriber::builder()
+//!     .tcp_keepalive(true)
+//!     .tcp_keepalive_idle(60)
+//!     .connect("tcp://127.0.0.1:5555")?;
+//! # Ok::<(), oxide_msg::OxideError>(())
+//! ```
+//!
+//! ## TCP keepalive
+//!
+//! The keepalive options map to `ZMQ_TCP_KEEPALIVE*` and are handed to the
+//! operating system for every TCP connection of the socket; a value of `-1`
+//! keeps the OS default. On Linux the defaults come from the
+//! `net.ipv4.tcp_keepalive_*` sysctls (two hours idle, nine probes, 75
+//! seconds apart), which is usually too slow for NAT gateways. macOS and
+//! FreeBSD support all three per-socket values (macOS names the idle option
+//! `TCP_KEEPALIVE`), while OpenBSD only honours the on/off switch and takes
+//! timings from system-wide sysctls. Values the platform cannot set per
+//! socket are silently ignored by libzmq.
+//!
+//! ## Connect timeout
+//!
+//! A TCP connect to a host that does not answer waits for the operating
+//! system to give up, which on Linux takes about two minutes (governed by
+//! `net.ipv4.tcp_syn_retries`); other platforms differ. `connect_timeout`
+//! aborts each attempt after the given number of milliseconds instead (`0`
+//! keeps the OS behaviour). It only applies to TCP and does not stop
+//! ZeroMQ from trying again: the next attempt starts after the reconnect
+//! interval (`ZMQ_RECONNECT_IVL`, 100 ms by default), so a dead endpoint
+//! is retried every timeout plus interval rather than once every few
+//! minutes. Attempts refused outright already fail without it.
+//!
+//! ## Multicast
+//!
+//! `pgm://` and `epgm://` endpoints (`epgm://eth0;239.192.1.1:5555`, an
+//! interface, a multicast group and a port) carry PUB/SUB traffic over
+//! reliable multicast; PGM sends raw IP packets and needs elevated
+//! privileges, EPGM wraps them in UDP. Both need a libzmq built with
+//! OpenPGM, which most distribution packages are not, and a network that
+//! routes multicast; [`pgm_supported`] checks the first. `multicast_hops`
+//! limits how many routers a packet may cross (1, the default, keeps it on
+//! the local network) and `rate` caps the send rate, since PGM has no
+//! congestion control (100 kbit/s by default). Both take effect when the
+//! socket binds or connects.
+//!
+//! ## I/O thread affinity
+//!
+//! A context runs one I/O thread unless created with
+//! [`context_with_io_threads`]. With several, `ZMQ_AFFINITY` is a bitmask
+//! of the I/O threads that may serve a socket's connections: bit 0 is the
+//! first thread, bit 1 the second, and so on, while the default `0` lets
+//! ZeroMQ spread connections over all of them. Giving a busy publisher
+//! `0b01` and a latency-sensitive requester `0b10` keeps them off each
+//! other's thread. Affinity applies to binds and connects made after it is
+//! set, so prefer [`SocketBuilder::affinity`] to the runtime
+//! `set_affinity`; bits beyond the number of I/O threads are ignored.
+//!
+//! ## Kernel socket buffers
+//!
+//! `sndbuf` and `rcvbuf` set `SO_SNDBUF` and `SO_RCVBUF` on the kernel
+//! sockets behind each connection; `-1`, the default, keeps the OS sizes.
+//! Large buffers help a single fast TCP stream stay busy over a link with a
+//! high bandwidth-delay product. The kernel caps requests at its own limits
+//! (`net.core.wmem_max` and `net.core.rmem_max` on Linux, where it also
+//! doubles the value to make room for bookkeeping), and neither option is
+//! reported back by libzmq, so `options()` shows the size requested rather
+//! than the size granted. The buffers sit below ZeroMQ's own queues: the
+//! high-water marks count whole messages queued inside ZeroMQ, while these
+//! count bytes in flight in the kernel, and a message is only dropped or
+//! blocked once both are full. Like affinity, the sizes apply to
+//! connections made after they are set.
+//!
+//! ## Default context
+//!
+//! Sockets get a fresh ZeroMQ context each unless the builder is given one
+//! with [`SocketBuilder::context`]. [`set_default_context`] changes that
+//! fallback for the calling thread, so the plain constructors (`new`,
+//! `new_bind`, ...) share a context and can talk over `inproc://`:
+//!
+//! ```no_run
+//! use oxide_msg::{Publisher, Subscriber};
+//!
+//! oxide_msg::set_default_context(&zmq::Context::new());
+//! let publisher = Publisher::new("inproc://events")?;
+//! let subscriber = Subscriber::new("inproc://events")?;
+//! # Ok::<(), oxide_msg::OxideError>(())
+//! ```
+//!
+//! The default is thread-local: sockets created on other threads, including
+//! the internal threads of patterns such as `WorkerPool`, are unaffected.
+//! It also applies to everything created on the thread afterwards, so code
+//! far from the call site silently shares the context, its I/O threads and
+//! its ZAP handler. Prefer it in tests and small programs; an explicit
+//! [`SocketBuilder::context`] always takes precedence, and
+//! [`clear_default_context`] restores fresh contexts.
+//!
+//! Every socket, whether made by a builder or internally by a pattern,
+//! is created through its context, and a context that cannot provide one
+//! (because it has been terminated, or the process has run out of file
+//! descriptors or the context out of sockets) makes the constructor fail
+//! with `OxideError::Configuration` naming the socket type. Each fresh
+//! context also starts an I/O thread of its own, so a program that opens
+//! many sockets and runs into such limits should share one context first.
+
+use crate::auth::SecurityMechanism;
+use crate::endpoint::Endpoint;
+use crate::error::{OxideError, Result};
+use crate::monitor::{wait_until_connected, SocketMonitor};
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use zmq::{Context, Socket};
+
+pub(crate) mod sealed {
+    use crate::error::Result;
+    use zmq::{Context, Socket, SocketType};
+
+    /// Pattern types that can be created from a configured socket
+    pub trait Pattern: Sized {
+        /// ZeroMQ socket type used by the pattern
+        const SOCKET_TYPE: SocketType;
+
+        /// Wrap a socket that has been configured but not yet bound or
+        /// connected
+        fn from_socket(context: &Context, socket: Socket) -> Result<Self>;
+
+        /// The underlying socket
+        fn socket(&self) -> &Socket;
+    }
+}
+
+use sealed::Pattern;
+
+/// Options applied to a socket before it binds or connects
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct SocketConfig {
+    sndhwm: Option<i32>,
+    rcvhwm: Option<i32>,
+    immediate: Option<bool>,
+    connect_timeout: Option<i32>,
+    nodrop: Option<bool>,
+    affinity: Option<u64>,
+    sndbuf: Option<i32>,
+    rcvbuf: Option<i32>,
+    multicast_hops: Option<i32>,
+    rate: Option<i32>,
+    identity: Option<Vec<u8>>,
+    probe_router: Option<bool>,
+    tcp_keepalive: Option<i32>,
+    tcp_keepalive_idle: Option<i32>,
+    tcp_keepalive_cnt: Option<i32>,
+    tcp_keepalive_intvl: Option<i32>,
+    security: Option<SecurityMechanism>,
+}
+
+impl SocketConfig {
+    fn apply(&self, socket: &mut Socket) -> Result<()> {
+        if let Some(value) = self.sndhwm {
+            socket.set_sndhwm(value).map_err(config_error)?;
+        }
+        if let Some(value) = self.rcvhwm {
+            socket.set_rcvhwm(value).map_err(config_error)?;
+        }
+        if let Some(value) = self.immediate {
+            socket.set_immediate(value).map_err(config_error)?;
+        }
+        if let Some(value) = self.connect_timeout {
+            socket.set_connect_timeout(value).map_err(config_error)?;
+        }
+        if let Some(value) = self.nodrop {
+            set_xpub_nodrop(socket, value).map_err(config_error)?;
+        }
+        if let Some(value) = self.affinity {
+            socket.set_affinity(value).map_err(config_error)?;
+        }
+        if let Some(value) = self.sndbuf {
+            socket.set_sndbuf(value).map_err(config_error)?;
+        }
+        if let Some(value) = self.rcvbuf {
+            socket.set_rcvbuf(value).map_err(config_error)?;
+        }
+        if let Some(value) = self.multicast_hops {
+            socket.set_multicast_hops(value).map_err(config_error)?;
+        }
+        if let Some(value) = self.rate {
+            socket.set_rate(value).map_err(config_error)?;
+        }
+        if let Some(identity) = &self.identity {
+            socket.set_identity(identity).map_err(config_error)?;
+        }
+        if let Some(value) = self.probe_router {
+            socket.set_probe_router(value).map_err(config_error)?;
+        }
+        if let Some(value) = self.tcp_keepalive {
+            socket.set_tcp_keepalive(value).map_err(config_error)?;
+        }
+        if let Some(value) = self.tcp_keepalive_idle {
+            socket.set_tcp_keepalive_idle(value).map_err(config_error)?;
+        }
+        if let Some(value) = self.tcp_keepalive_cnt {
+            socket.set_tcp_keepalive_cnt(value).map_err(config_error)?;
+        }
+        if let Some(value) = self.tcp_keepalive_intvl {
+            socket
+                .set_tcp_keepalive_intvl(value)
+                .map_err(config_error)?;
+        }
+        if let Some(mechanism) = &self.security {
+            mechanism.apply(socket)?;
+        }
+        Ok(())
+    }
+}
+
+thread_local! {
+    static DEFAULT_CONTEXT: RefCell<Option<Context>> = const { RefCell::new(None) };
+}
+
+/// Use `context` for sockets created on this thread without an explicit one
+///
+/// See the [module documentation](self#default-context) for the caveats.
+pub fn set_default_context(context: &Context) {
+    DEFAULT_CONTEXT.with(|default| *default.borrow_mut() = Some(context.clone()));
+}
+
+/// Go back to a fresh context per socket on this thread
+pub fn clear_default_context() {
+    DEFAULT_CONTEXT.with(|default| *default.borrow_mut() = None);
+}
+
+/// New context with `threads` I/O threads (`ZMQ_IO_THREADS`)
+///
+/// Pass it to [`SocketBuilder::context`] or [`set_default_context`], and
+/// pin sockets to threads with [`SocketBuilder::affinity`].
+pub fn context_with_io_threads(threads: i32) -> Result<Context> {
+    if threads < 1 {
+        return Err(OxideError::Configuration(format!(
+            "a context needs at least one I/O thread, got {}",
+            threads
+        )));
+    }
+    let context = Context::new();
+    context.set_io_threads(threads).map_err(|err| {
+        OxideError::Configuration(format!("cannot use {} I/O threads: {}", threads, err))
+    })?;
+    Ok(context)
+}
+
+/// Create a socket of `socket_type` on `context`, reporting failure as a
+/// configuration error
+pub(crate) fn new_socket(context: &Context, socket_type: zmq::SocketType) -> Result<Socket> {
+    context.socket(socket_type).map_err(|err| {
+        OxideError::Configuration(format!("cannot create {:?} socket: {}", socket_type, err))
+    })
+}
+
+/// Whether libzmq was built with PGM support, required for `pgm://` and
+/// `epgm://` endpoints
+pub fn pgm_supported() -> bool {
+    zmq::has("pgm").unwrap_or(false)
+}
+
+fn default_context() -> Context {
+    DEFAULT_CONTEXT.with(|default| default.borrow().clone().unwrap_or_default())
+}
+
+/// Set `ZMQ_XPUB_NODROP`, which the `zmq` crate does not wrap
+fn set_xpub_nodrop(socket: &mut Socket, enabled: bool) -> zmq::Result<()> {
+    ffi::set_int_option(socket, zmq_sys::ZMQ_XPUB_NODROP, i32::from(enabled))
+}
+
+/// The only calls into libzmq that bypass the `zmq` crate
+///
+/// `zmq-sys` is pinned to the version `zmq` itself depends on, so these
+/// calls go through the same libzmq bindings as the rest of the socket.
+mod ffi {
+    use std::os::raw::{c_int, c_void};
+    use zmq::Socket;
+
+    /// Set an integer option that `zmq::Socket` has no setter for
+    pub(super) fn set_int_option(socket: &mut Socket, option: u32, value: i32) -> zmq::Result<()> {
+        let value: c_int = value;
+        // SAFETY: `as_mut_ptr` returns the live socket handle owned by
+        // `socket`, which the `&mut` borrow keeps open and unshared for the
+        // call. libzmq only reads `size_of_val(&value)` bytes from the
+        // pointer, which point at the local `value`, and does not keep it.
+        let rc = unsafe {
+            zmq_sys::zmq_setsockopt(
+                socket.as_mut_ptr(),
+                option as c_int,
+                &value as *const c_int as *const c_void,
+                std::mem::size_of_val(&value),
+            )
+        };
+        if rc == 0 {
+            Ok(())
+        } else {
+            // SAFETY: `zmq_errno` only reads the calling thread's errno,
+            // which `zmq_setsockopt` has just set on failure
+            Err(zmq::Error::from_raw(unsafe { zmq_sys::zmq_errno() }))
+        }
+    }
+}
+
+fn config_error(err: zmq::Error) -> OxideError {
+    OxideError::SocketOption(err)
+}
+
+/// Builder for pattern types, see the [module documentation](self)
+#[derive(Clone)]
+pub struct SocketBuilder<P> {
+    context: Option<Context>,
+    config: SocketConfig,
+    pattern: PhantomData<fn() -> P>,
+}
+
+impl<P: Pattern> SocketBuilder<P> {
+    pub(crate) fn new() -> Self {
+        Self {
+            context: None,
+            config: SocketConfig::default(),
+            pattern: PhantomData,
+        }
+    }
+
+    /// Create the socket in `context` instead of the default one
+    ///
+    /// `inproc://` endpoints only connect sockets of the same context.
+    pub fn context(mut self, context: &Context) -> Self {
+        self.context = Some(context.clone());
+        self
+    }
+
+    /// Maximum number of outbound messages queued per peer (`ZMQ_SNDHWM`)
+    pub fn sndhwm(mut self, messages: i32) -> Self {
+        self.config.sndhwm = Some(messages);
+        self
+    }
+
+    /// Maximum number of inbound messages queued per peer (`ZMQ_RCVHWM`)
+    pub fn rcvhwm(mut self, messages: i32) -> Self {
+        self.config.rcvhwm = Some(messages);
+        self
+    }
+
+    /// Give up on a TCP connection attempt after `ms` milliseconds
+    /// (`ZMQ_CONNECT_TIMEOUT`), see the
+    /// [module documentation](self#connect-timeout)
+    pub fn connect_timeout(mut self, ms: i32) -> Self {
+        self.config.connect_timeout = Some(ms);
+        self
+    }
+
+    /// Refuse sends at the high-water mark instead of silently dropping
+    /// them (`ZMQ_XPUB_NODROP`, PUB and XPUB sockets only)
+    ///
+    /// Blocking publishes then wait for room, and non-blocking ones such as
+    /// [`Publisher::try_publish`](crate::Publisher::try_publish) fail with
+    /// `EAGAIN`, so a slow subscriber becomes visible to the publisher.
+    pub fn nodrop(mut self, enabled: bool) -> Self {
+        self.config.nodrop = Some(enabled);
+        self
+    }
+
+    /// Serve the socket's connections only from the I/O threads in the
+    /// `mask` bitmask (`ZMQ_AFFINITY`), see the
+    /// [module documentation](self#io-thread-affinity)
+    pub fn affinity(mut self, mask: u64) -> Self {
+        self.config.affinity = Some(mask);
+        self
+    }
+
+    /// Kernel send buffer size in bytes for each connection
+    /// (`ZMQ_SNDBUF`), see the [module documentation](self#kernel-socket-buffers)
+    pub fn sndbuf(mut self, bytes: i32) -> Self {
+        self.config.sndbuf = Some(bytes);
+        self
+    }
+
+    /// Kernel receive buffer size in bytes for each connection
+    /// (`ZMQ_RCVBUF`), see the [module documentation](self#kernel-socket-buffers)
+    pub fn rcvbuf(mut self, bytes: i32) -> Self {
+        self.config.rcvbuf = Some(bytes);
+        self
+    }
+
+    /// Queue outbound messages only on completed connections
+    /// (`ZMQ_IMMEDIATE`)
+    ///
+    /// With this set, sends block (or time out) while no peer is connected
+    /// instead of queueing for a peer that may never arrive.
+    pub fn immediate(mut self, enabled: bool) -> Self {
+        self.config.immediate = Some(enabled);
+        self
+    }
+
+    /// Routing identity presented to ROUTER peers (`ZMQ_ROUTING_ID`)
+    ///
+    /// Between 1 and 255 bytes, not starting with a zero byte. Without it
+    /// a ROUTER assigns a random identity to each connection.
+    pub fn identity(mut self, identity: &[u8]) -> Self {
+        self.config.identity = Some(identity.to_vec());
+        self
+    }
+
+    /// Announce each new connection to ROUTER peers with an empty message
+    /// (`ZMQ_PROBE_ROUTER`, DEALER and ROUTER sockets only)
+    ///
+    /// See [`Dealer::set_probe_router`](crate::patterns::Dealer::set_probe_router).
+    pub fn probe_router(mut self, enabled: bool) -> Self {
+        self.config.probe_router = Some(enabled);
+        self
+    }
+
+    /// Routers a multicast packet may cross (`ZMQ_MULTICAST_HOPS`), see
+    /// the [module documentation](self#multicast)
+    pub fn multicast_hops(mut self, hops: i32) -> Self {
+        self.config.multicast_hops = Some(hops);
+        self
+    }
+
+    /// Maximum multicast send rate in kilobits per second (`ZMQ_RATE`)
+    pub fn rate(mut self, kbps: i32) -> Self {
+        self.config.rate = Some(kbps);
+        self
+    }
+
+    /// Enable or disable TCP keepalive probes (`ZMQ_TCP_KEEPALIVE`)
+    pub fn tcp_keepalive(mut self, enabled: bool) -> Self {
+        self.config.tcp_keepalive = Some(enabled as i32);
+        self
+    }
+
+    /// Seconds a connection must be idle before the first probe
+    /// (`ZMQ_TCP_KEEPALIVE_IDLE`)
+    pub fn tcp_keepalive_idle(mut self, secs: i32) -> Self {
+        self.config.tcp_keepalive_idle = Some(secs);
+        self
+    }
+
+    /// Unanswered probes before the connection is dropped
+    /// (`ZMQ_TCP_KEEPALIVE_CNT`)
+    pub fn tcp_keepalive_cnt(mut self, count: i32) -> Self {
+        self.config.tcp_keepalive_cnt = Some(count);
+        self
+    }
+
+    /// Seconds between probes (`ZMQ_TCP_KEEPALIVE_INTVL`)
+    pub fn tcp_keepalive_intvl(mut self, secs: i32) -> Self {
+        self.config.tcp_keepalive_intvl = Some(secs);
+        self
+    }
+
+    /// Use `mechanism` for the ZMTP handshake, in the role it names
+    ///
+    /// Replaces any mechanism set earlier, including by
+    /// [`SocketBuilder::plain_server`] and [`SocketBuilder::plain_auth`].
+    /// See the [`auth`](crate::auth) module docs for what each mechanism
+    /// needs.
+    pub fn security(mut self, mechanism: SecurityMechanism) -> Self {
+        self.config.security = Some(mechanism);
+        self
+    }
+
+    /// Act as a PLAIN authentication server (`ZMQ_PLAIN_SERVER`)
+    ///
+    /// Peers must then log in with [`SocketBuilder::plain_auth`]. Without a
+    /// ZAP handler in the socket's context any credentials are accepted;
+    /// see [`ZapHandler`](crate::auth::ZapHandler). `false` selects
+    /// [`SecurityMechanism::Null`].
+    pub fn plain_server(self, enabled: bool) -> Self {
+        self.security(if enabled {
+            SecurityMechanism::PlainServer
+        } else {
+            SecurityMechanism::Null
+        })
+    }
+
+    /// Log in to a PLAIN server (`ZMQ_PLAIN_USERNAME`/`ZMQ_PLAIN_PASSWORD`)
+    ///
+    /// PLAIN sends the password in cleartext; see the [`auth`](crate::auth)
+    /// module docs.
+    pub fn plain_auth(self, username: &str, password: &str) -> Self {
+        self.security(SecurityMechanism::PlainClient {
+            username: username.to_string(),
+            password: password.to_string(),
+        })
+    }
+
+    /// Create the socket and bind it to `address`
+    pub fn bind(self, address: impl Into<Endpoint>) -> Result<P> {
+        let endpoint = address.into();
+        endpoint.validate()?;
+        let pattern = self.open()?;
+        pattern.socket().bind(endpoint.as_str())?;
+        Ok(pattern)
+    }
+
+    /// Create the socket and connect it to `address`
+    pub fn connect(self, address: impl Into<Endpoint>) -> Result<P> {
+        let endpoint = address.into();
+        endpoint.validate()?;
+        let pattern = self.open()?;
+        pattern.socket().connect(endpoint.as_str())?;
+        Ok(pattern)
+    }
+
+    /// Create the socket, connect it to `address` and wait for the
+    /// connection to be established
+    ///
+    /// Fails with `OxideError::Timeout` if no TCP or IPC connection is made
+    /// within `timeout_ms`; ZeroMQ keeps retrying in the background, but the
+    /// socket is dropped. A connection does not mean a PUB peer has seen
+    /// a SUB socket's subscriptions yet, so the first messages may still be
+    /// missed. `inproc://` endpoints emit no connect event and always time
+    /// out.
+    pub fn connect_and_wait(self, address: impl Into<Endpoint>, timeout_ms: i32) -> Result<P> {
+        let endpoint = address.into();
+        let (pattern, monitor) = self.connect_monitored(endpoint.clone())?;
+        wait_until_connected(&monitor, timeout_ms).map_err(|err| match err {
+            OxideError::Timeout(_) => OxideError::Timeout(format!(
+                "no connection to {} after {} ms",
+                endpoint, timeout_ms
+            )),
+            err => err,
+        })?;
+        Ok(pattern)
+    }
+
+    /// Create the socket with a [`SocketMonitor`] attached, then connect it
+    /// to `address`
+    ///
+    /// Attaching the monitor before connecting means the connect event
+    /// cannot be missed; wait for it with [`wait_until_connected`].
+    pub fn connect_monitored(self, address: impl Into<Endpoint>) -> Result<(P, SocketMonitor)> {
+        let endpoint = address.into();
+        endpoint.validate()?;
+        let (pattern, monitor) = self.open_monitored()?;
+        pattern.socket().connect(endpoint.as_str())?;
+        Ok((pattern, monitor))
+    }
+
+    /// Create the socket with a [`SocketMonitor`] attached, then bind it to
+    /// `address`
+    ///
+    /// The monitor sees every peer that connects from then on.
+    pub fn bind_monitored(self, address: impl Into<Endpoint>) -> Result<(P, SocketMonitor)> {
+        let endpoint = address.into();
+        endpoint.validate()?;
+        let (pattern, monitor) = self.open_monitored()?;
+        pattern.socket().bind(endpoint.as_str())?;
+        Ok((pattern, monitor))
+    }
+
+    /// Create and configure the socket with a [`SocketMonitor`] attached,
+    /// without binding or connecting it
+    pub(crate) fn open_monitored(&self) -> Result<(P, SocketMonitor)> {
+        let context = self.context.clone().unwrap_or_else(default_context);
+        let mut socket = new_socket(&context, P::SOCKET_TYPE)?;
+        self.config.apply(&mut socket)?;
+        let monitor = SocketMonitor::new(&context, &socket)?;
+        let pattern = P::from_socket(&context, socket)?;
+        Ok((pattern, monitor))
+    }
+
+    /// Create and configure the socket without binding or connecting it
+    /// The context sockets of this builder are created in
+    pub(crate) fn context_or_default(&self) -> Context {
+        self.context.clone().unwrap_or_else(default_context)
+    }
+
+    pub(crate) fn open(&self) -> Result<P> {
+        let context = self.context_or_default();
+        let mut socket = new_socket(&context, P::SOCKET_TYPE)?;
+        self.config.apply(&mut socket)?;
+        P::from_socket(&context, socket)
+    }
+}
+
+/// Implement [`sealed::Pattern`], `builder()`, `options()`, `with_raw_socket()`,
+/// `message_counts()` and drop tracing for pattern types made of a `socket`
+/// and `counters`
+///
+/// Further fields listed in braces, as in `Subscriber { subscriptions }`,
+/// start out as their `Default`; a field written `field = init` is set to
+/// `init(context)` instead, for types that keep something of the context
+/// their socket was created in.
+macro_rules! impl_pattern {
+    (@init $context:ident) => {
+        Default::default()
+    };
+    (@init $context:ident $init:expr) => {
+        ($init)($context)
+    };
+    ($($ty:ident $({ $($field:ident $(= $init:expr)?),* })? => $socket_type:expr),* $(,)?) => {
+        $(
+            impl $crate::builder::sealed::Pattern for $ty {
+                const SOCKET_TYPE: zmq::SocketType = $socket_type;
+
+                fn from_socket(_context: &zmq::Context, socket: zmq::Socket) -> $crate::Result<Self> {
+                    Ok(Self {
+                        socket,
+                        counters: Default::default(),
+                        $($($field: impl_pattern!(@init _context $($init)?),)*)?
+                    })
+                }
+
+                fn socket(&self) -> &zmq::Socket {
+                    &self.socket
+                }
+            }
+
+            impl $ty {
+                /// Configure a new socket before binding or connecting it
+                pub fn builder() -> $crate::builder::SocketBuilder<Self> {
+                    $crate::builder::SocketBuilder::new()
+                }
+
+                /// Read back the socket's current options
+                pub fn options(&self) -> $crate::Result<$crate::options::SocketOptions> {
+                    $crate::options::SocketOptions::read(&self.socket)
+                }
+
+                /// Run `f` with the underlying ZeroMQ socket, as an escape hatch for
+                /// options and calls this type does not wrap
+                ///
+                /// The socket is only lent for the duration of `f`. Setting options is
+                /// usually harmless, but sending, receiving, binding or disconnecting on
+                /// the raw socket bypasses the framing, counters and state this type
+                /// keeps, and can leave its own methods misbehaving.
+                pub fn with_raw_socket<R>(&self, f: impl FnOnce(&zmq::Socket) -> R) -> R {
+                    f(&self.socket)
+                }
+
+                /// Messages sent and received so far (requires the `metrics` feature)
+                #[cfg(feature = "metrics")]
+                pub fn message_counts(&self) -> $crate::metrics::MessageCounts {
+                    self.counters
+                        .snapshot()
+                        .unwrap_or_default()
+                }
+            }
+
+            #[cfg(feature = "tracing")]
+            impl Drop for $ty {
+                fn drop(&mut self) {
+                    $crate::metrics::trace_drop(stringify!($ty), &self.socket, &self.counters);
+                }
+            }
+        )*
+    };
+}
+
+/// Implement `bind` for pattern types that can listen on further endpoints
+macro_rules! impl_bind {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl $ty {
+                /// Also listen on `address`, in addition to existing endpoints
+                ///
+                /// A socket can be bound to any number of endpoints, even on
+                /// different transports; messages from all of them arrive on
+                /// this one socket. `options().last_endpoint` only reports
+                /// the most recent bind or connect.
+                pub fn bind(
+                    &self,
+                    address: impl Into<$crate::endpoint::Endpoint>,
+                ) -> $crate::Result<()> {
+                    let endpoint = address.into();
+                    endpoint.validate()?;
+                    self.socket.bind(endpoint.as_str())?;
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+/// Implement frame-level `recv_frame` and `has_more` for receiving types
+macro_rules! impl_frames {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl $ty {
+                /// Receive a single frame (blocking)
+                ///
+                /// For reading multipart messages by hand: call
+                /// [`has_more`](Self::has_more) after each frame and keep
+                /// reading until it returns `false`. Leaving frames unread
+                /// makes the next receive start in the middle of a message.
+                pub fn recv_frame(&self) -> $crate::Result<Vec<u8>> {
+                    let frame = self
+                        .socket
+                        .recv_bytes(0)
+                        .map_err($crate::OxideError::Receive)?;
+                    if !self.has_more()? {
+                        self.counters.record_received();
+                    }
+                    Ok(frame)
+                }
+
+                /// Whether more frames of the current message follow
+                /// (`ZMQ_RCVMORE`)
+                ///
+                /// Only meaningful after a receive: it describes the frame
+                /// read last, and is `false` before anything was received.
+                pub fn has_more(&self) -> $crate::Result<bool> {
+                    self.socket
+                        .get_rcvmore()
+                        .map_err($crate::OxideError::Receive)
+                }
+            }
+        )*
+    };
+}
+
+/// Implement runtime `ZMQ_AFFINITY` accessors for types with a `socket` field
+macro_rules! impl_affinity {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl $ty {
+                /// Restrict binds and connects made from now on to the I/O
+                /// threads in the `mask` bitmask
+                pub fn set_affinity(&self, mask: u64) -> $crate::Result<()> {
+                    self.socket
+                        .set_affinity(mask)
+                        .map_err($crate::OxideError::SocketOption)
+                }
+
+                /// Current `ZMQ_AFFINITY` bitmask (`0` for any I/O thread)
+                pub fn affinity(&self) -> $crate::Result<u64> {
+                    self.socket
+                        .get_affinity()
+                        .map_err($crate::OxideError::SocketOption)
+                }
+            }
+        )*
+    };
+}
+
+/// Implement runtime `ZMQ_SNDBUF` and `ZMQ_RCVBUF` setters for types with a
+/// `socket` field
+macro_rules! impl_buffers {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl $ty {
+                /// Kernel send buffer size in bytes for connections made from
+                /// now on, see the
+                /// [builder documentation](crate::builder#kernel-socket-buffers)
+                pub fn set_sndbuf(&self, bytes: i32) -> $crate::Result<()> {
+                    self.socket
+                        .set_sndbuf(bytes)
+                        .map_err($crate::OxideError::SocketOption)
+                }
+
+                /// Kernel receive buffer size in bytes for connections made
+                /// from now on
+                pub fn set_rcvbuf(&self, bytes: i32) -> $crate::Result<()> {
+                    self.socket
+                        .set_rcvbuf(bytes)
+                        .map_err($crate::OxideError::SocketOption)
+                }
+            }
+        )*
+    };
+}
+
+/// Implement runtime `ZMQ_MULTICAST_HOPS` and `ZMQ_RATE` accessors for
+/// types with a `socket` field
+macro_rules! impl_multicast {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl $ty {
+                /// Routers multicast packets may cross, for endpoints bound
+                /// or connected from now on
+                pub fn set_multicast_hops(&self, hops: i32) -> $crate::Result<()> {
+                    self.socket
+                        .set_multicast_hops(hops)
+                        .map_err($crate::OxideError::SocketOption)
+                }
+
+                /// Current `ZMQ_MULTICAST_HOPS`
+                pub fn multicast_hops(&self) -> $crate::Result<i32> {
+                    self.socket
+                        .get_multicast_hops()
+                        .map_err($crate::OxideError::SocketOption)
+                }
+
+                /// Maximum multicast send rate in kilobits per second, for
+                /// endpoints bound or connected from now on
+                pub fn set_rate(&self, kbps: i32) -> $crate::Result<()> {
+                    self.socket
+                        .set_rate(kbps)
+                        .map_err($crate::OxideError::SocketOption)
+                }
+
+                /// Current `ZMQ_RATE` in kilobits per second
+                pub fn rate(&self) -> $crate::Result<i32> {
+                    self.socket
+                        .get_rate()
+                        .map_err($crate::OxideError::SocketOption)
+                }
+            }
+        )*
+    };
+}
+
+/// Implement runtime `ZMQ_CONNECT_TIMEOUT` accessors for connecting types
+/// with a `socket` field
+macro_rules! impl_connect_timeout {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl $ty {
+                /// Give up on TCP connection attempts started from now on
+                /// after `ms` milliseconds (`0` for the OS default)
+                pub fn set_connect_timeout(&self, ms: i32) -> $crate::Result<()> {
+                    self.socket
+                        .set_connect_timeout(ms)
+                        .map_err($crate::OxideError::SocketOption)
+                }
+
+                /// Current `ZMQ_CONNECT_TIMEOUT` in milliseconds
+                pub fn connect_timeout(&self) -> $crate::Result<i32> {
+                    self.socket
+                        .get_connect_timeout()
+                        .map_err($crate::OxideError::SocketOption)
+                }
+            }
+        )*
+    };
+}
+
+/// Implement runtime TCP keepalive accessors for types with a `socket` field
+///
+/// Changes only affect connections established afterwards; use the builder
+/// to configure the first connection.
+macro_rules! impl_tcp_keepalive {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl $ty {
+                /// Enable or disable TCP keepalive probes for new connections
+                pub fn set_tcp_keepalive(&self, enabled: bool) -> $crate::Result<()> {
+                    self.socket
+                        .set_tcp_keepalive(enabled as i32)
+                        .map_err($crate::OxideError::SocketOption)
+                }
+
+                /// Set the idle time in seconds before the first keepalive probe
+                pub fn set_tcp_keepalive_idle(&self, secs: i32) -> $crate::Result<()> {
+                    self.socket
+                        .set_tcp_keepalive_idle(secs)
+                        .map_err($crate::OxideError::SocketOption)
+                }
+
+                /// Set the number of unanswered probes before dropping a connection
+                pub fn set_tcp_keepalive_cnt(&self, count: i32) -> $crate::Result<()> {
+                    self.socket
+                        .set_tcp_keepalive_cnt(count)
+                        .map_err($crate::OxideError::SocketOption)
+                }
+
+                /// Set the interval in seconds between keepalive probes
+                pub fn set_tcp_keepalive_intvl(&self, secs: i32) -> $crate::Result<()> {
+                    self.socket
+                        .set_tcp_keepalive_intvl(secs)
+                        .map_err($crate::OxideError::SocketOption)
+                }
+
+                /// Current `ZMQ_TCP_KEEPALIVE`, or `None` when the OS default applies
+                pub fn tcp_keepalive(&self) -> $crate::Result<Option<bool>> {
+                    let value = self
+                        .socket
+                        .get_tcp_keepalive()
+                        .map_err($crate::OxideError::SocketOption)?;
+                    Ok(if value < 0 { None } else { Some(value != 0) })
+                }
+
+                /// Current keepalive idle time in seconds (`-1` for the OS default)
+                pub fn tcp_keepalive_idle(&self) -> $crate::Result<i32> {
+                    self.socket
+                        .get_tcp_keepalive_idle()
+                        .map_err($crate::OxideError::SocketOption)
+                }
+
+                /// Current keepalive probe count (`-1` for the OS default)
+                pub fn tcp_keepalive_cnt(&self) -> $crate::Result<i32> {
+                    self.socket
+                        .get_tcp_keepalive_cnt()
+                        .map_err($crate::OxideError::SocketOption)
+                }
+
+                /// Current keepalive probe interval in seconds (`-1` for the OS default)
+                pub fn tcp_keepalive_intvl(&self) -> $crate::Result<i32> {
+                    self.socket
+                        .get_tcp_keepalive_intvl()
+                        .map_err($crate::OxideError::SocketOption)
+                }
+            }
+        )*
+    };
+}
+
+/// Implement runtime PLAIN authentication setters for types with a `socket`
+/// field
+macro_rules! impl_plain {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl $ty {
+                /// Act as a PLAIN authentication server for connections made
+                /// from now on, see [`SocketBuilder::plain_server`]
+                ///
+                /// [`SocketBuilder::plain_server`]: crate::SocketBuilder::plain_server
+                pub fn set_plain_server(&self, enabled: bool) -> $crate::Result<()> {
+                    let mechanism = if enabled {
+                        $crate::auth::SecurityMechanism::PlainServer
+                    } else {
+                        $crate::auth::SecurityMechanism::Null
+                    };
+                    mechanism.apply(&self.socket)
+                }
+
+                /// Log in to PLAIN servers on connections made from now on,
+                /// see [`SocketBuilder::plain_auth`]
+                ///
+                /// [`SocketBuilder::plain_auth`]: crate::SocketBuilder::plain_auth
+                pub fn set_plain_auth(&self, username: &str, password: &str) -> $crate::Result<()> {
+                    $crate::auth::SecurityMechanism::PlainClient {
+                        username: username.to_string(),
+                        password: password.to_string(),
+                    }
+                    .apply(&self.socket)
+                }
+            }
+        )*
+    };
+}
+
+pub(crate) use impl_affinity;
+pub(crate) use impl_bind;
+pub(crate) use impl_buffers;
+pub(crate) use impl_connect_timeout;
+pub(crate) use impl_frames;
+pub(crate) use impl_multicast;
+pub(crate) use impl_pattern;
+pub(crate) use impl_plain;
+pub(crate) use impl_tcp_keepalive;
+
+#[cfg(test)]
+mod tests {
+    use crate::patterns::{Publisher, Requester, Subscriber};
+
+    #[test]
+    fn test_tcp_keepalive_applied_before_connect() {
+        let address = "tcp://127.0.0.1:15578";
+        let _publisher = Publisher::new(address).unwrap();
+
+        let subscriber = Subscriber::builder()
+            .tcp_keepalive(true)
+            .tcp_keepalive_idle(30)
+            .tcp_keepalive_cnt(4)
+            .tcp_keepalive_intvl(5)
+            .connect(address)
+            .unwrap();
+
+        assert_eq!(subscriber.tcp_keepalive().unwrap(), Some(true));
+        assert_eq!(subscriber.tcp_keepalive_idle().unwrap(), 30);
+        assert_eq!(subscriber.tcp_keepalive_cnt().unwrap(), 4);
+        assert_eq!(subscriber.tcp_keepalive_intvl().unwrap(), 5);
+    }
+
+    #[test]
+    fn test_tcp_keepalive_runtime_setters() {
+        let requester = Requester::new("tcp://127.0.0.1:25578").unwrap();
+        assert_eq!(requester.tcp_keepalive().unwrap(), None);
+
+        requester.set_tcp_keepalive(false).unwrap();
+        requester.set_tcp_keepalive_idle(120).unwrap();
+        requester.set_tcp_keepalive_cnt(3).unwrap();
+        requester.set_tcp_keepalive_intvl(10).unwrap();
+
+        assert_eq!(requester.tcp_keepalive().unwrap(), Some(false));
+        assert_eq!(requester.tcp_keepalive_idle().unwrap(), 120);
+        assert_eq!(requester.tcp_keepalive_cnt().unwrap(), 3);
+        assert_eq!(requester.tcp_keepalive_intvl().unwrap(), 10);
+    }
+
+    #[test]
+    fn test_connect_timeout_abandons_unanswered_attempt() {
+        use super::sealed::Pattern;
+        use crate::monitor::SocketMonitor;
+        use crate::patterns::Dealer;
+        use std::net::{TcpListener, TcpStream};
+        use std::time::{Duration, Instant};
+
+        // A listener that never accepts stops answering SYNs once its
+        // backlog is full, which stands in for an unreachable host
+        let address = "127.0.0.1:15621";
+        let _listener = TcpListener::bind(address).unwrap();
+        let mut backlog = Vec::new();
+        while let Ok(stream) =
+            TcpStream::connect_timeout(&address.parse().unwrap(), Duration::from_millis(100))
+        {
+            backlog.push(stream);
+            assert!(backlog.len() < 5000, "listener backlog never filled");
+        }
+
+        let context = zmq::Context::new();
+        let dealer = Dealer::builder()
+            .context(&context)
+            .connect_timeout(200)
+            .open()
+            .unwrap();
+        assert_eq!(dealer.connect_timeout().unwrap(), 200);
+        let monitor = SocketMonitor::new(&context, dealer.socket()).unwrap();
+        let started = Instant::now();
+        dealer
+            .socket()
+            .connect(&format!("tcp://{}", address))
+            .unwrap();
+
+        // Without the timeout the attempt would hang for the OS SYN retries
+        let retried = std::iter::from_fn(|| monitor.next_event_timeout(2000).unwrap())
+            .find(|event| event.event == zmq::SocketEvent::CONNECT_RETRIED);
+        assert!(retried.is_some());
+        assert!(started.elapsed() < Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn test_connect_monitored_sees_connection() {
+        use crate::monitor::wait_until_connected;
+        use crate::patterns::{Dealer, Router};
+
+        let address = "tcp://127.0.0.1:25642";
+        let _router = Router::new(address).unwrap();
+        let (_dealer, monitor) = Dealer::builder().connect_monitored(address).unwrap();
+        wait_until_connected(&monitor, 2000).unwrap();
+        assert_eq!(monitor.peer_count().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_multicast_options_applied() {
+        let publisher = Publisher::builder()
+            .multicast_hops(4)
+            .rate(1000)
+            .bind("tcp://127.0.0.1:15629")
+            .unwrap();
+        assert_eq!(publisher.multicast_hops().unwrap(), 4);
+        assert_eq!(publisher.rate().unwrap(), 1000);
+
+        publisher.set_multicast_hops(2).unwrap();
+        publisher.set_rate(500).unwrap();
+        assert_eq!(publisher.multicast_hops().unwrap(), 2);
+        assert_eq!(publisher.rate().unwrap(), 500);
+    }
+
+    /// Needs a libzmq built with OpenPGM and a multicast-capable loopback;
+    /// run with `cargo test -- --ignored`
+    #[test]
+    #[ignore]
+    fn test_epgm_publisher_binds_with_options() {
+        assert!(super::pgm_supported(), "libzmq was built without PGM");
+        let publisher = Publisher::builder()
+            .multicast_hops(1)
+            .rate(10_000)
+            .bind("epgm://127.0.0.1;239.192.1.1:15629")
+            .unwrap();
+        assert_eq!(publisher.multicast_hops().unwrap(), 1);
+        assert_eq!(publisher.rate().unwrap(), 10_000);
+    }
+
+    #[test]
+    fn test_default_context_shared_by_plain_constructors() {
+        use crate::message::Message;
+        use serde_json::json;
+
+        super::set_default_context(&zmq::Context::new());
+        let publisher = Publisher::new("inproc://default-context").unwrap();
+        let subscriber = Subscriber::new("inproc://default-context").unwrap();
+        super::clear_default_context();
+        subscriber.subscribe("").unwrap();
+
+        // Subscriptions reach the publisher asynchronously, so keep
+        // publishing until one message gets through
+        let message = Message::new("shared", json!(1));
+        let received = (0..200).find_map(|_| {
+            publisher.publish(&message).unwrap();
+            subscriber.receive_timeout(10).unwrap()
+        });
+        assert_eq!(received.unwrap().topic, "shared");
+    }
+
+    #[test]
+    fn test_unusable_context_is_a_configuration_error() {
+        use crate::OxideError;
+
+        assert!(matches!(
+            super::context_with_io_threads(0),
+            Err(OxideError::Configuration(_))
+        ));
+
+        let mut context = zmq::Context::new();
+        context.destroy().unwrap();
+        match Subscriber::builder()
+            .context(&context)
+            .connect("tcp://127.0.0.1:15654")
+        {
+            Err(OxideError::Configuration(message)) => assert!(message.contains("SUB")),
+            other => panic!("expected a configuration error, got {:?}", other.err()),
+        }
+
+        // The plain constructors fail the same way through a shared context
+        super::set_default_context(&context);
+        let result = Requester::new("tcp://127.0.0.1:15654");
+        super::clear_default_context();
+        assert!(matches!(result, Err(OxideError::Configuration(_))));
+    }
+
+    #[test]
+    fn test_affinity_applied_and_read_back() {
+        let context = super::context_with_io_threads(2).unwrap();
+        assert_eq!(context.get_io_threads().unwrap(), 2);
+
+        let publisher = Publisher::builder()
+            .context(&context)
+            .affinity(0b10)
+            .bind("tcp://127.0.0.1:15611")
+            .unwrap();
+        assert_eq!(publisher.affinity().unwrap(), 0b10);
+
+        let requester = Requester::builder().context(&context).open().unwrap();
+        assert_eq!(requester.affinity().unwrap(), 0);
+        requester.set_affinity(0b01).unwrap();
+        assert_eq!(requester.affinity().unwrap(), 0b01);
+
+        assert!(matches!(
+            super::context_with_io_threads(0),
+            Err(crate::OxideError::Configuration(_))
+        ));
+    }
+}
@@ -0,0 +1,81 @@
+//! Time sources for message tracking
+//!
+//! [`Message::track`](crate::Message::track) and
+//! [`Message::age`](crate::Message::age) read the [`SystemClock`]. Their
+//! `_with` variants take any [`Clock`] instead, so tests can pin
+//! timestamps with a [`MockClock`] and assert exact ages:
+//!
+//! ```
+//! use oxide_msg::{Message, MockClock};
+//! use serde_json::json;
+//! use std::time::Duration;
+//!
+//! let clock = MockClock::at_millis(1_000);
+//! let message = Message::new("t", json!(null)).track_with(&clock);
+//! clock.advance(Duration::from_millis(250));
+//! assert_eq!(message.age_with(&clock), Some(Duration::from_millis(250)));
+//! ```
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A source of wall-clock time
+pub trait Clock: Send + Sync {
+    /// The current time
+    fn now(&self) -> SystemTime;
+}
+
+/// The operating system clock, used unless another one is passed in
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that only moves when told to
+///
+/// Clones share the same time, so a test can keep one and hand another to
+/// the code under test.
+#[derive(Debug, Clone, Default)]
+pub struct MockClock {
+    millis: Arc<AtomicU64>,
+}
+
+impl MockClock {
+    /// A clock reading `millis` milliseconds after the Unix epoch
+    pub fn at_millis(millis: u64) -> Self {
+        Self {
+            millis: Arc::new(AtomicU64::new(millis)),
+        }
+    }
+
+    /// Move the clock forward by `by`
+    pub fn advance(&self, by: Duration) {
+        self.millis
+            .fetch_add(by.as_millis() as u64, Ordering::SeqCst);
+    }
+
+    /// Set the clock to `millis` milliseconds after the Unix epoch
+    pub fn set_millis(&self, millis: u64) {
+        self.millis.store(millis, Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.millis.load(Ordering::SeqCst))
+    }
+}
+
+/// Milliseconds since the Unix epoch on `clock`, 0 for times before it
+pub(crate) fn unix_millis(clock: &impl Clock) -> u64 {
+    clock
+        .now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
@@ -0,0 +1,244 @@
+//! Typed ZeroMQ endpoints
+//!
+//! Pattern constructors and [`SocketBuilder`](crate::SocketBuilder) take
+//! `impl Into<Endpoint>`, so they accept both plain strings and a
+//! [`Transport`]:
+//!
+//! ```no_run
+//! use oxide_msg::endpoint::Transport;
+//! use oxide_msg::{Publisher, Subscriber};
+//!
+//! let publisher = Publisher::new(Transport::tcp("127.0.0.1", 5555))?;
+//! let subscriber = Subscriber::new("tcp://127.0.0.1:5555")?;
+//! # Ok::<(), oxide_msg::OxideError>(())
+//! ```
+//!
+//! A `Transport` always renders a well-formed endpoint. Strings are checked
+//! when the socket binds or connects (or up front with [`Endpoint::parse`])
+//! and malformed ones, such as `tpc://host:5555` or a TCP address without a
+//! port, fail with [`OxideError::Configuration`] instead of a bare `EINVAL`
+//! from ZeroMQ.
+
+use crate::error::{OxideError, Result};
+use std::fmt;
+use std::path::PathBuf;
+
+/// Transports whose endpoints are checked by [`Endpoint::validate`]
+const SCHEMES: &[&str] = &[
+    "tcp", "ipc", "inproc", "pgm", "epgm", "tipc", "vmci", "udp", "ws", "wss",
+];
+
+/// A transport and its address, rendered with [`Transport::to_endpoint`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Transport {
+    /// TCP to or on `host` (a name, an IP address or `*`) and `port`
+    Tcp { host: String, port: u16 },
+    /// Unix domain socket at `path`
+    Ipc { path: PathBuf },
+    /// In-process transport named `name`; both ends need the same context
+    Inproc { name: String },
+}
+
+impl Transport {
+    /// TCP endpoint on `host` and `port`
+    pub fn tcp(host: impl Into<String>, port: u16) -> Self {
+        Transport::Tcp {
+            host: host.into(),
+            port,
+        }
+    }
+
+    /// IPC endpoint at `path`
+    pub fn ipc(path: impl Into<PathBuf>) -> Self {
+        Transport::Ipc { path: path.into() }
+    }
+
+    /// In-process endpoint called `name`
+    pub fn inproc(name: impl Into<String>) -> Self {
+        Transport::Inproc { name: name.into() }
+    }
+
+    /// The endpoint string ZeroMQ expects
+    ///
+    /// IPv6 hosts are wrapped in brackets.
+    pub fn to_endpoint(&self) -> String {
+        match self {
+            Transport::Tcp { host, port } if host.contains(':') && !host.starts_with('[') => {
+                format!("tcp://[{}]:{}", host, port)
+            }
+            Transport::Tcp { host, port } => format!("tcp://{}:{}", host, port),
+            Transport::Ipc { path } => format!("ipc://{}", path.display()),
+            Transport::Inproc { name } => format!("inproc://{}", name),
+        }
+    }
+}
+
+/// An endpoint to bind or connect to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Endpoint {
+    address: String,
+}
+
+impl Endpoint {
+    /// Check `address` and wrap it
+    pub fn parse(address: &str) -> Result<Self> {
+        let endpoint = Self::from(address);
+        endpoint.validate()?;
+        Ok(endpoint)
+    }
+
+    /// The endpoint string
+    pub fn as_str(&self) -> &str {
+        &self.address
+    }
+
+    /// Check that the endpoint names a known transport and, for TCP-like
+    /// and multicast transports, has a port
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: &str| {
+            Err(OxideError::Configuration(format!(
+                "invalid endpoint {:?}: {}",
+                self.address, reason
+            )))
+        };
+
+        let Some((scheme, rest)) = self.address.split_once("://") else {
+            return invalid("expected transport://address");
+        };
+        if !SCHEMES.contains(&scheme) {
+            return invalid("unknown transport");
+        }
+        if rest.is_empty() {
+            return invalid("missing address");
+        }
+        if matches!(scheme, "pgm" | "epgm") {
+            // interface;multicast-group:port
+            let group = rest.split_once(';').map(|(_, group)| group);
+            if !group.is_some_and(has_port) {
+                return invalid("expected interface;group:port");
+            }
+        }
+        if matches!(scheme, "tcp" | "udp") && !has_port(rest) {
+            return invalid("expected host:port");
+        }
+        Ok(())
+    }
+}
+
+/// Whether `address` ends in `:port` or `:*`
+fn has_port(address: &str) -> bool {
+    let port = address.rsplit_once(':').map(|(_, port)| port);
+    port.is_some_and(|port| {
+        port == "*" || (!port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()))
+    })
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.address)
+    }
+}
+
+impl From<Transport> for Endpoint {
+    fn from(transport: Transport) -> Self {
+        Self {
+            address: transport.to_endpoint(),
+        }
+    }
+}
+
+impl From<&Transport> for Endpoint {
+    fn from(transport: &Transport) -> Self {
+        Self {
+            address: transport.to_endpoint(),
+        }
+    }
+}
+
+impl From<&str> for Endpoint {
+    fn from(address: &str) -> Self {
+        Self {
+            address: address.to_string(),
+        }
+    }
+}
+
+impl From<String> for Endpoint {
+    fn from(address: String) -> Self {
+        Self { address }
+    }
+}
+
+impl From<&String> for Endpoint {
+    fn from(address: &String) -> Self {
+        Self::from(address.as_str())
+    }
+}
+
+impl From<&Endpoint> for Endpoint {
+    fn from(endpoint: &Endpoint) -> Self {
+        endpoint.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transport_endpoints() {
+        assert_eq!(
+            Transport::tcp("127.0.0.1", 5555).to_endpoint(),
+            "tcp://127.0.0.1:5555"
+        );
+        assert_eq!(Transport::tcp("*", 80).to_endpoint(), "tcp://*:80");
+        assert_eq!(
+            Transport::tcp("::1", 5555).to_endpoint(),
+            "tcp://[::1]:5555"
+        );
+        assert_eq!(
+            Transport::ipc("/tmp/oxide.sock").to_endpoint(),
+            "ipc:///tmp/oxide.sock"
+        );
+        assert_eq!(
+            Transport::inproc("workers").to_endpoint(),
+            "inproc://workers"
+        );
+        for transport in [
+            Transport::tcp("localhost", 1),
+            Transport::ipc("/tmp/x"),
+            Transport::inproc("x"),
+        ] {
+            Endpoint::from(transport).validate().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_malformed_strings_are_rejected() {
+        for address in [
+            "tpc://127.0.0.1:5555",
+            "127.0.0.1:5555",
+            "tcp://127.0.0.1",
+            "tcp://127.0.0.1:port",
+            "inproc://",
+            "epgm://239.192.1.1:5555",
+            "pgm://eth0;239.192.1.1",
+        ] {
+            assert!(
+                matches!(Endpoint::parse(address), Err(OxideError::Configuration(_))),
+                "{address}"
+            );
+        }
+        assert_eq!(
+            Endpoint::parse("tcp://*:5555").unwrap().as_str(),
+            "tcp://*:5555"
+        );
+        Endpoint::parse("epgm://eth0;239.192.1.1:5555").unwrap();
+    }
+
+    #[test]
+    fn test_constructors_reject_malformed_endpoint() {
+        let result = crate::Publisher::new("tpc://127.0.0.1:15600");
+        assert!(matches!(result, Err(OxideError::Configuration(_))));
+    }
+}
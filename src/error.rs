@@ -1,4 +1,8 @@
 //! Error types for the Oxide messaging framework
+//!
+//! Failures reported by ZeroMQ keep the original `zmq::Error`, so callers
+//! can match on the errno (for example `OxideError::Send(zmq::Error::EAGAIN)`
+//! when a non-blocking send would block) instead of parsing strings.
 
 use std::fmt;
 
@@ -9,34 +13,113 @@ pub type Result<T> = std::result::Result<T, OxideError>;
 #[derive(Debug)]
 pub enum OxideError {
     /// ZeroMQ error
+    #[cfg(feature = "transport")]
     Zmq(zmq::Error),
     /// Serialization/deserialization error
     Serialization(String),
     /// Invalid configuration
     Configuration(String),
+    /// ZeroMQ rejected a socket option (displayed as a configuration error)
+    #[cfg(feature = "transport")]
+    SocketOption(zmq::Error),
     /// Connection error
     Connection(String),
-    /// Send error
-    Send(String),
-    /// Receive error
-    Receive(String),
+    /// An operation did not complete in time
+    Timeout(String),
+    /// A wait was ended early through a `StopHandle`
+    Cancelled,
+    /// Sending on the socket failed
+    #[cfg(feature = "transport")]
+    Send(zmq::Error),
+    /// Receiving from the socket failed
+    #[cfg(feature = "transport")]
+    Receive(zmq::Error),
+    /// A peer sent frames that do not follow the expected protocol
+    Protocol(String),
+    /// A payload does not have the expected shape
+    Validation(String),
+    /// Sequenced messages `first..=last` never arrived and were skipped
+    SequenceGap { first: u64, last: u64 },
+    /// Reading or writing a stream failed
+    Io(std::io::Error),
+    /// Error object returned by a JSON-RPC peer
+    Rpc { code: i64, message: String },
+}
+
+#[cfg(feature = "transport")]
+impl OxideError {
+    /// The underlying ZeroMQ error, if this error came from ZeroMQ
+    pub fn zmq_error(&self) -> Option<zmq::Error> {
+        match self {
+            OxideError::Zmq(e)
+            | OxideError::SocketOption(e)
+            | OxideError::Send(e)
+            | OxideError::Receive(e) => Some(*e),
+            _ => None,
+        }
+    }
+
+    /// Whether retrying the operation could succeed
+    ///
+    /// True for send failures with `EAGAIN` (the high-water mark was reached
+    /// or the send timed out) or `EINTR` (a signal interrupted the call).
+    /// Everything else, including receive timeouts, is treated as fatal:
+    /// repeating a receive on a REQ socket, for instance, cannot help.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            OxideError::Zmq(zmq::Error::EAGAIN | zmq::Error::EINTR)
+                | OxideError::Send(zmq::Error::EAGAIN | zmq::Error::EINTR)
+        )
+    }
 }
 
 impl fmt::Display for OxideError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            #[cfg(feature = "transport")]
             OxideError::Zmq(e) => write!(f, "ZeroMQ error: {}", e),
             OxideError::Serialization(msg) => write!(f, "Serialization error: {}", msg),
             OxideError::Configuration(msg) => write!(f, "Configuration error: {}", msg),
+            #[cfg(feature = "transport")]
+            OxideError::SocketOption(e) => write!(f, "Configuration error: {}", e),
             OxideError::Connection(msg) => write!(f, "Connection error: {}", msg),
-            OxideError::Send(msg) => write!(f, "Send error: {}", msg),
-            OxideError::Receive(msg) => write!(f, "Receive error: {}", msg),
+            OxideError::Timeout(msg) => write!(f, "Timeout: {}", msg),
+            OxideError::Cancelled => f.write_str("Cancelled"),
+            #[cfg(feature = "transport")]
+            OxideError::Send(e) => write!(f, "Send error: {}", e),
+            #[cfg(feature = "transport")]
+            OxideError::Receive(e) => write!(f, "Receive error: {}", e),
+            OxideError::Protocol(msg) => write!(f, "Protocol error: {}", msg),
+            OxideError::Validation(msg) => write!(f, "Validation error: {}", msg),
+            OxideError::SequenceGap { first, last } => {
+                write!(
+                    f,
+                    "Sequence gap: messages {} to {} never arrived",
+                    first, last
+                )
+            }
+            OxideError::Io(e) => write!(f, "I/O error: {}", e),
+            OxideError::Rpc { code, message } => write!(f, "RPC error {}: {}", code, message),
         }
     }
 }
 
-impl std::error::Error for OxideError {}
+impl std::error::Error for OxideError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "transport")]
+            OxideError::Zmq(e)
+            | OxideError::SocketOption(e)
+            | OxideError::Send(e)
+            | OxideError::Receive(e) => Some(e),
+            OxideError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
+#[cfg(feature = "transport")]
 impl From<zmq::Error> for OxideError {
     fn from(err: zmq::Error) -> Self {
         OxideError::Zmq(err)
@@ -48,3 +131,29 @@ impl From<serde_json::Error> for OxideError {
         OxideError::Serialization(err.to_string())
     }
 }
+
+impl From<std::io::Error> for OxideError {
+    fn from(err: std::io::Error) -> Self {
+        OxideError::Io(err)
+    }
+}
+
+#[cfg(all(test, feature = "transport"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_matches_string_form() {
+        let err = OxideError::Send(zmq::Error::EAGAIN);
+        assert_eq!(
+            err.to_string(),
+            format!("Send error: {}", zmq::Error::EAGAIN)
+        );
+        let err = OxideError::SocketOption(zmq::Error::EINVAL);
+        assert_eq!(
+            err.to_string(),
+            format!("Configuration error: {}", zmq::Error::EINVAL)
+        );
+        assert_eq!(err.zmq_error(), Some(zmq::Error::EINVAL));
+    }
+}
@@ -0,0 +1,455 @@
+//! JSON-RPC 2.0 over the request/reply pattern
+//!
+//! Requests and responses travel as [`Message`]s on the [`JSONRPC_TOPIC`]
+//! topic with the JSON-RPC object as the payload. Because REP sockets must
+//! answer every request, the server also replies to notifications (requests
+//! without an `id`), using a `null` id.
+//!
+//! [`service!`](crate::service) declares a set of methods once and generates
+//! the typed server and client for them on top of [`RpcServer`] and
+//! [`RpcClient`].
+
+use crate::endpoint::Endpoint;
+use crate::error::{OxideError, Result};
+use crate::message::Message;
+use crate::patterns::{Replier, Requester};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::cell::Cell;
+use std::collections::HashMap;
+
+/// Topic used for JSON-RPC requests and responses
+pub const JSONRPC_TOPIC: &str = "jsonrpc";
+
+/// Invalid JSON was received by the server
+pub const PARSE_ERROR: i64 = -32700;
+/// The JSON sent is not a valid request object
+pub const INVALID_REQUEST: i64 = -32600;
+/// The method does not exist or is not available
+pub const METHOD_NOT_FOUND: i64 = -32601;
+/// Invalid method parameters
+pub const INVALID_PARAMS: i64 = -32602;
+/// Internal JSON-RPC error
+pub const INTERNAL_ERROR: i64 = -32603;
+
+/// A JSON-RPC error object
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcError {
+    /// Error code, see the constants in this module
+    pub code: i64,
+    /// Short description of the error
+    pub message: String,
+    /// Optional additional information
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    /// Create a new error with the given code and message
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+impl From<RpcError> for OxideError {
+    fn from(err: RpcError) -> Self {
+        OxideError::Rpc {
+            code: err.code,
+            message: err.message,
+        }
+    }
+}
+
+/// JSON-RPC client over a [`Requester`]
+pub struct RpcClient {
+    requester: Requester,
+    next_id: Cell<u64>,
+}
+
+impl RpcClient {
+    /// Create a new client that connects to the specified address
+    pub fn new(address: impl Into<Endpoint>) -> Result<Self> {
+        Ok(Self::from_requester(Requester::new(address)?))
+    }
+
+    /// Create a client from an existing requester
+    pub fn from_requester(requester: Requester) -> Self {
+        Self {
+            requester,
+            next_id: Cell::new(1),
+        }
+    }
+
+    /// Call `method` with `params` and wait for the result
+    ///
+    /// An error object in the response is returned as [`OxideError::Rpc`].
+    pub fn call<P: Serialize, R: DeserializeOwned>(&self, method: &str, params: P) -> Result<R> {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+
+        let params = serde_json::to_value(params)?;
+        let request = Message::new(
+            JSONRPC_TOPIC,
+            json!({"jsonrpc": "2.0", "method": method, "params": params, "id": id}),
+        );
+        let mut response = self.requester.request(&request)?.payload;
+
+        if response["id"] != json!(id) {
+            return Err(OxideError::Protocol(format!(
+                "JSON-RPC response id {} does not match request id {}",
+                response["id"], id
+            )));
+        }
+        if let Some(error) = response.get_mut("error") {
+            let error: RpcError = serde_json::from_value(error.take())?;
+            return Err(error.into());
+        }
+        match response.get_mut("result") {
+            Some(result) => Ok(serde_json::from_value(result.take())?),
+            None => Err(OxideError::Serialization(
+                "JSON-RPC response has neither result nor error".to_string(),
+            )),
+        }
+    }
+}
+
+type Handler = Box<dyn Fn(Value) -> std::result::Result<Value, RpcError>>;
+
+/// JSON-RPC server over a [`Replier`]
+pub struct RpcServer {
+    replier: Replier,
+    methods: HashMap<String, Handler>,
+}
+
+impl RpcServer {
+    /// Create a new server that binds to the specified address
+    pub fn new(address: impl Into<Endpoint>) -> Result<Self> {
+        Ok(Self::from_replier(Replier::new(address)?))
+    }
+
+    /// Create a server from an existing replier
+    pub fn from_replier(replier: Replier) -> Self {
+        Self {
+            replier,
+            methods: HashMap::new(),
+        }
+    }
+
+    /// Register a handler for `method`
+    ///
+    /// Params that fail to deserialize into `P` are answered with
+    /// [`INVALID_PARAMS`] without calling the handler.
+    pub fn register<P, R, F>(&mut self, method: &str, handler: F)
+    where
+        P: DeserializeOwned,
+        R: Serialize,
+        F: Fn(P) -> std::result::Result<R, RpcError> + 'static,
+    {
+        let handler = move |params: Value| {
+            let params = serde_json::from_value(params)
+                .map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))?;
+            let result = handler(params)?;
+            serde_json::to_value(result).map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))
+        };
+        self.methods.insert(method.to_string(), Box::new(handler));
+    }
+
+    /// Receive and answer a single request (blocking)
+    pub fn handle_next(&self) -> Result<()> {
+        let response = match self.replier.receive() {
+            Ok(request) => self.dispatch(request.payload),
+            Err(OxideError::Serialization(e)) => {
+                error_response(Value::Null, RpcError::new(PARSE_ERROR, e))
+            }
+            Err(e) => return Err(e),
+        };
+        self.replier.reply(&Message::new(JSONRPC_TOPIC, response))
+    }
+
+    /// Answer requests forever
+    pub fn run(&self) -> Result<()> {
+        loop {
+            self.handle_next()?;
+        }
+    }
+
+    fn dispatch(&self, mut request: Value) -> Value {
+        let id = request
+            .get_mut("id")
+            .map(Value::take)
+            .unwrap_or(Value::Null);
+
+        if request["jsonrpc"] != "2.0" {
+            return error_response(
+                id,
+                RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\""),
+            );
+        }
+        let method = match request["method"].as_str() {
+            Some(method) => method,
+            None => {
+                return error_response(
+                    id,
+                    RpcError::new(INVALID_REQUEST, "method must be a string"),
+                )
+            }
+        };
+        let handler = match self.methods.get(method) {
+            Some(handler) => handler,
+            None => {
+                let message = format!("method not found: {}", method);
+                return error_response(id, RpcError::new(METHOD_NOT_FOUND, message));
+            }
+        };
+
+        let params = request
+            .get_mut("params")
+            .map(Value::take)
+            .unwrap_or(Value::Null);
+        match handler(params) {
+            Ok(result) => json!({"jsonrpc": "2.0", "result": result, "id": id}),
+            Err(error) => error_response(id, error),
+        }
+    }
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({"jsonrpc": "2.0", "error": error, "id": id})
+}
+
+/// Declare a JSON-RPC service: a trait to implement, a server that dispatches
+/// to it and a client with one typed method per entry
+///
+/// Each entry `method => fn(Params) -> Reply` becomes a trait method
+/// `fn method(&self, params: Params) -> Result<Reply, RpcError>`, a handler
+/// registered under the name `"method"`, and a client method
+/// `fn method(&self, params: Params) -> oxide_msg::Result<Reply>`. `Params`
+/// must implement `Serialize` and `DeserializeOwned`, and so must `Reply`.
+///
+/// ```no_run
+/// use oxide_msg::jsonrpc::RpcError;
+///
+/// oxide_msg::service! {
+///     /// Integer arithmetic
+///     pub service Calculator {
+///         server = CalculatorServer;
+///         client = CalculatorClient;
+///         add => fn((i64, i64)) -> i64;
+///         negate => fn(i64) -> i64;
+///     }
+/// }
+///
+/// struct Arithmetic;
+///
+/// impl Calculator for Arithmetic {
+///     fn add(&self, (a, b): (i64, i64)) -> Result<i64, RpcError> {
+///         Ok(a + b)
+///     }
+///
+///     fn negate(&self, n: i64) -> Result<i64, RpcError> {
+///         Ok(-n)
+///     }
+/// }
+///
+/// # fn main() -> oxide_msg::Result<()> {
+/// std::thread::spawn(|| {
+///     CalculatorServer::new("tcp://*:5555", Arithmetic)?.run()
+/// });
+/// let client = CalculatorClient::new("tcp://localhost:5555")?;
+/// assert_eq!(client.add((2, 3))?, 5);
+/// # Ok(())
+/// # }
+/// ```
+#[macro_export]
+macro_rules! service {
+    (
+        $(#[$meta:meta])*
+        $vis:vis service $service:ident {
+            server = $server:ident;
+            client = $client:ident;
+            $(
+                $(#[$method_meta:meta])*
+                $method:ident => fn($params:ty) -> $reply:ty;
+            )*
+        }
+    ) => {
+        $(#[$meta])*
+        $vis trait $service {
+            $(
+                $(#[$method_meta])*
+                fn $method(
+                    &self,
+                    params: $params,
+                ) -> ::std::result::Result<$reply, $crate::jsonrpc::RpcError>;
+            )*
+        }
+
+        #[doc = concat!("Serves a [`", stringify!($service), "`] implementation over JSON-RPC")]
+        $vis struct $server {
+            rpc: $crate::jsonrpc::RpcServer,
+        }
+
+        // A program need not use every generated method
+        #[allow(dead_code)]
+        impl $server {
+            /// Create a new server that binds to the specified address
+            $vis fn new(
+                address: impl Into<$crate::Endpoint>,
+                service: impl $service + 'static,
+            ) -> $crate::Result<Self> {
+                Ok(Self::from_server(
+                    $crate::jsonrpc::RpcServer::new(address)?,
+                    service,
+                ))
+            }
+
+            /// Register the service's methods on an existing server
+            $vis fn from_server(
+                mut rpc: $crate::jsonrpc::RpcServer,
+                service: impl $service + 'static,
+            ) -> Self {
+                let service = ::std::rc::Rc::new(service);
+                $(
+                    let handler = ::std::rc::Rc::clone(&service);
+                    rpc.register(stringify!($method), move |params: $params| {
+                        handler.$method(params)
+                    });
+                )*
+                let _ = service;
+                Self { rpc }
+            }
+
+            /// Receive and answer a single request (blocking)
+            $vis fn handle_next(&self) -> $crate::Result<()> {
+                self.rpc.handle_next()
+            }
+
+            /// Answer requests forever
+            $vis fn run(&self) -> $crate::Result<()> {
+                loop {
+                    self.handle_next()?;
+                }
+            }
+        }
+
+        #[doc = concat!("Typed JSON-RPC client for [`", stringify!($service), "`]")]
+        $vis struct $client {
+            rpc: $crate::jsonrpc::RpcClient,
+        }
+
+        // A program need not use every generated method
+        #[allow(dead_code)]
+        impl $client {
+            /// Create a new client that connects to the specified address
+            $vis fn new(address: impl Into<$crate::Endpoint>) -> $crate::Result<Self> {
+                Ok(Self::from_client($crate::jsonrpc::RpcClient::new(address)?))
+            }
+
+            /// Wrap an existing JSON-RPC client
+            $vis fn from_client(rpc: $crate::jsonrpc::RpcClient) -> Self {
+                Self { rpc }
+            }
+
+            $(
+                $(#[$method_meta])*
+                $vis fn $method(&self, params: $params) -> $crate::Result<$reply> {
+                    self.rpc.call(stringify!($method), params)
+                }
+            )*
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[derive(Serialize, Deserialize)]
+    struct AddParams {
+        a: i64,
+        b: i64,
+    }
+
+    crate::service! {
+        /// Test service with a struct and a scalar parameter
+        service Calculator {
+            server = CalculatorServer;
+            client = CalculatorClient;
+            add => fn(AddParams) -> i64;
+            negate => fn(i64) -> i64;
+        }
+    }
+
+    struct Arithmetic;
+
+    impl Calculator for Arithmetic {
+        fn add(&self, params: AddParams) -> std::result::Result<i64, RpcError> {
+            Ok(params.a + params.b)
+        }
+
+        fn negate(&self, n: i64) -> std::result::Result<i64, RpcError> {
+            n.checked_neg()
+                .ok_or_else(|| RpcError::new(INVALID_PARAMS, "cannot negate i64::MIN"))
+        }
+    }
+
+    #[test]
+    fn test_service_macro_client_calls_server() {
+        let address = "tcp://127.0.0.1:15651";
+        let (done_tx, done_rx) = std::sync::mpsc::channel::<()>();
+        let server_handle = thread::spawn(move || {
+            let server = CalculatorServer::new(address, Arithmetic).unwrap();
+            for _ in 0..3 {
+                server.handle_next().unwrap();
+            }
+            // Keep the socket open until the last reply has arrived
+            let _ = done_rx.recv();
+        });
+
+        let client = CalculatorClient::new(address).unwrap();
+        assert_eq!(client.add(AddParams { a: 2, b: 3 }).unwrap(), 5);
+        assert_eq!(client.negate(7).unwrap(), -7);
+        match client.negate(i64::MIN) {
+            Err(OxideError::Rpc { code, .. }) => assert_eq!(code, INVALID_PARAMS),
+            other => panic!("expected invalid-params error, got {:?}", other),
+        }
+        drop(done_tx);
+        server_handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_jsonrpc_calls() {
+        let address = "tcp://127.0.0.1:15570";
+
+        let server_handle = thread::spawn(move || {
+            let mut server = RpcServer::new(address).unwrap();
+            server.register("add", |p: AddParams| Ok::<_, RpcError>(p.a + p.b));
+            for _ in 0..3 {
+                server.handle_next().unwrap();
+            }
+        });
+
+        let client = RpcClient::new(address).unwrap();
+
+        let sum: i64 = client.call("add", json!({"a": 2, "b": 3})).unwrap();
+        assert_eq!(sum, 5);
+
+        match client.call::<_, Value>("subtract", json!({"a": 2, "b": 3})) {
+            Err(OxideError::Rpc { code, .. }) => assert_eq!(code, METHOD_NOT_FOUND),
+            other => panic!("expected method-not-found error, got {:?}", other),
+        }
+
+        match client.call::<_, i64>("add", json!({"a": "two"})) {
+            Err(OxideError::Rpc { code, .. }) => assert_eq!(code, INVALID_PARAMS),
+            other => panic!("expected invalid-params error, got {:?}", other),
+        }
+
+        server_handle.join().unwrap();
+    }
+}
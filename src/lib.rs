@@ -10,18 +10,87 @@
 //! - Push/Pull pattern
 //! - Easy-to-use API with error handling
 //! - Support for serialization with JSON
+//! - JSON-RPC 2.0 client and server over request/reply, with typed services
+//!   declared by [`service!`]
+//! - Graceful Ctrl-C shutdown of receive loops (`signals` feature)
+//!
+//! ## Thread safety
+//!
+//! Pattern types own a ZeroMQ socket: they are `Send`, so they can be
+//! moved to another thread, but not `Sync`. Give each thread its own
+//! socket, hand requesters out with
+//! [`RequesterPool`](patterns::RequesterPool), or run a replier behind a
+//! [`ServerHandle`](patterns::ServerHandle), which is `Send + Sync`.
+//! [`Message`] is plain data and both.
+//!
+//! ## Message-only builds
+//!
+//! Everything that touches ZeroMQ sits behind the default `transport`
+//! feature. With `default-features = false` the crate only provides
+//! [`Message`], [`Topic`], [`Serializer`] and the error types, so message
+//! definitions can be shared with components that never open a socket.
 
+#[cfg(feature = "transport")]
+pub mod auth;
+#[cfg(feature = "transport")]
+pub mod builder;
+pub mod clock;
+#[cfg(feature = "transport")]
+pub mod endpoint;
 pub mod error;
+#[cfg(feature = "transport")]
+pub mod jsonrpc;
 pub mod message;
+#[cfg(feature = "transport")]
+pub mod metrics;
+#[cfg(feature = "transport")]
+pub mod monitor;
+#[cfg(feature = "transport")]
+pub mod options;
+#[cfg(feature = "transport")]
 pub mod patterns;
+#[cfg(feature = "transport")]
+pub mod retry;
+#[cfg(feature = "transport")]
+pub mod selftest;
+pub mod serializer;
+#[cfg(feature = "signals")]
+pub mod signals;
+#[cfg(feature = "transport")]
+pub mod stop;
+#[cfg(all(feature = "transport", any(test, feature = "testing")))]
+pub mod test_support;
+pub mod topic;
+pub mod wire;
 
+#[cfg(feature = "transport")]
+pub use builder::{clear_default_context, set_default_context, SocketBuilder};
+pub use clock::{Clock, MockClock, SystemClock};
+#[cfg(feature = "transport")]
+pub use endpoint::{Endpoint, Transport};
 pub use error::{OxideError, Result};
-pub use message::Message;
+pub use message::{FieldKind, Message, Topical};
+#[cfg(feature = "transport")]
+pub use metrics::MessageCounts;
+#[cfg(feature = "transport")]
+pub use options::SocketOptions;
+#[cfg(feature = "transport")]
 pub use patterns::{Publisher, Puller, Pusher, Replier, Requester, Subscriber};
+#[cfg(feature = "cbor")]
+pub use serializer::CborSerializer;
+pub use serializer::{CompatSerializer, JsonSerializer, Serializer};
+#[cfg(feature = "signals")]
+pub use signals::{install_ctrlc_handler, ShutdownToken};
+#[cfg(feature = "transport")]
+pub use stop::StopHandle;
+pub use topic::Topic;
+pub use wire::{WireFormat, MAX_DECOMPRESSED_LEN, WIRE_FORMAT_VERSION};
 
 /// Re-export commonly used types
 pub mod prelude {
-    pub use crate::{
-        Message, OxideError, Publisher, Puller, Pusher, Replier, Requester, Result, Subscriber,
-    };
+    #[cfg(feature = "transport")]
+    pub use crate::patterns::{MessageReceiver, MessageSender};
+    pub use crate::{Message, OxideError, Result};
+    #[cfg(feature = "transport")]
+    pub use crate::{Publisher, Puller, Pusher, Replier, Requester, Subscriber};
 }
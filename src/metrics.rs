@@ -0,0 +1,159 @@
+//! Per-socket message counters
+//!
+//! With the `metrics` feature every pattern type counts the messages it has
+//! sent and received and reports them through `message_counts()`. A
+//! multipart message (such as a topic-framed publish or a stream) counts
+//! once. Without the feature the counters compile away entirely.
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "metrics")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Lifetime message counts of a socket
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageCounts {
+    /// Messages sent successfully
+    pub sent: u64,
+    /// Messages received successfully
+    pub received: u64,
+}
+
+/// Counters embedded in each pattern type
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    #[cfg(feature = "metrics")]
+    sent: AtomicU64,
+    #[cfg(feature = "metrics")]
+    received: AtomicU64,
+    #[cfg(feature = "metrics")]
+    dropped: AtomicU64,
+}
+
+impl Counters {
+    #[inline]
+    pub(crate) fn record_sent(&self) {
+        #[cfg(feature = "metrics")]
+        self.sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn record_received(&self) {
+        #[cfg(feature = "metrics")]
+        self.received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn record_dropped(&self) {
+        #[cfg(feature = "metrics")]
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Messages refused at the high-water mark
+    #[cfg(feature = "metrics")]
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Current counts, or `None` when the `metrics` feature is off
+    #[allow(dead_code)]
+    pub(crate) fn snapshot(&self) -> Option<MessageCounts> {
+        #[cfg(feature = "metrics")]
+        return Some(MessageCounts {
+            sent: self.sent.load(Ordering::Relaxed),
+            received: self.received.load(Ordering::Relaxed),
+        });
+        #[cfg(not(feature = "metrics"))]
+        None
+    }
+}
+
+/// Log a pattern socket being dropped (`tracing` feature)
+#[cfg(feature = "tracing")]
+pub(crate) fn trace_drop(pattern: &str, socket: &zmq::Socket, counters: &Counters) {
+    let endpoint = match socket.get_last_endpoint() {
+        Ok(Ok(endpoint)) => endpoint,
+        _ => String::new(),
+    };
+    match counters.snapshot() {
+        Some(counts) => tracing::trace!(
+            pattern,
+            endpoint = %endpoint,
+            sent = counts.sent,
+            received = counts.received,
+            "socket dropped"
+        ),
+        None => tracing::trace!(pattern, endpoint = %endpoint, "socket dropped"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(any(feature = "metrics", feature = "tracing"))]
+    use crate::message::Message;
+    #[cfg(feature = "tracing")]
+    use crate::patterns::Publisher;
+    #[cfg(any(feature = "metrics", feature = "tracing"))]
+    use serde_json::json;
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_counts_track_sends_and_receives() {
+        use crate::test_support::TestPair;
+
+        let pair = TestPair::pipeline().unwrap();
+        for n in 0..3 {
+            pair.sender.push(&Message::new("task", json!(n))).unwrap();
+        }
+        for _ in 0..3 {
+            pair.receiver.pull_timeout(2000).unwrap().unwrap();
+        }
+        assert_eq!(pair.sender.message_counts().sent, 3);
+        assert_eq!(pair.receiver.message_counts().received, 3);
+        assert_eq!(pair.receiver.message_counts().sent, 0);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_drop_is_logged_with_endpoint() {
+        use std::io::Write;
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Clone, Default)]
+        struct Capture(Arc<Mutex<Vec<u8>>>);
+
+        impl Write for Capture {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let capture = Capture::default();
+        let writer = capture.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+
+        let address = "tcp://127.0.0.1:15590";
+        tracing::subscriber::with_default(subscriber, || {
+            let publisher = Publisher::new(address).unwrap();
+            publisher
+                .publish(&Message::new("leak", json!(null)))
+                .unwrap();
+            drop(publisher);
+        });
+
+        let output = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("socket dropped"), "{output}");
+        assert!(output.contains("pattern=\"Publisher\""), "{output}");
+        assert!(output.contains(address), "{output}");
+        #[cfg(feature = "metrics")]
+        assert!(output.contains("sent=1"), "{output}");
+    }
+}
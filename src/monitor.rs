@@ -0,0 +1,137 @@
+//! Socket event monitoring
+//!
+//! A [`SocketMonitor`] reads the `zmq_socket_monitor` event stream of a
+//! pattern socket over an inproc PAIR socket and keeps a best-effort count of
+//! connected peers. Events are queued by ZeroMQ until they are read, and
+//! dropped once that queue fills, so counts can drift if the monitor is
+//! left unread for a long time.
+//!
+//! [`wait_until_connected`] replaces a sleep after connecting. Get a
+//! monitor that sees the connect event from
+//! [`SocketBuilder::connect_monitored`](crate::SocketBuilder::connect_monitored).
+
+use crate::builder::new_socket;
+use crate::error::{OxideError, Result};
+use crate::patterns::{deadline_after, remaining_ms};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use zmq::{Context, Socket, SocketEvent};
+
+static NEXT_MONITOR_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A single event reported by a socket monitor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MonitorEvent {
+    /// Kind of event
+    pub event: SocketEvent,
+    /// Event-specific value (a file descriptor, errno or interval)
+    pub value: u32,
+    /// Endpoint the event relates to
+    pub endpoint: String,
+}
+
+/// Reads connection events for a socket
+pub struct SocketMonitor {
+    socket: Socket,
+    peers: Cell<usize>,
+}
+
+impl SocketMonitor {
+    /// Start monitoring all events of `socket`, which must belong to `context`
+    pub(crate) fn new(context: &Context, socket: &Socket) -> Result<Self> {
+        let id = NEXT_MONITOR_ID.fetch_add(1, Ordering::Relaxed);
+        let endpoint = format!("inproc://oxide-monitor-{}", id);
+        socket
+            .monitor(&endpoint, SocketEvent::ALL as i32)
+            .map_err(OxideError::SocketOption)?;
+
+        let monitor = new_socket(context, zmq::PAIR)?;
+        monitor.connect(&endpoint)?;
+        Ok(Self {
+            socket: monitor,
+            peers: Cell::new(0),
+        })
+    }
+
+    /// Wait up to `timeout_ms` for the next event
+    ///
+    /// A negative timeout waits forever.
+    pub fn next_event_timeout(&self, timeout_ms: i32) -> Result<Option<MonitorEvent>> {
+        let ready = self
+            .socket
+            .poll(zmq::POLLIN, timeout_ms as i64)
+            .map_err(OxideError::Receive)?;
+        if ready == 0 {
+            return Ok(None);
+        }
+
+        let frames = self.socket.recv_multipart(0).map_err(OxideError::Receive)?;
+        let event = Self::decode(&frames)?;
+        self.track(&event);
+        Ok(Some(event))
+    }
+
+    /// Return the next event if one is already queued
+    pub fn try_next_event(&self) -> Result<Option<MonitorEvent>> {
+        self.next_event_timeout(0)
+    }
+
+    /// Best-effort number of currently connected peers
+    ///
+    /// Drains queued events before answering.
+    pub fn peer_count(&self) -> Result<usize> {
+        while self.try_next_event()?.is_some() {}
+        Ok(self.peers.get())
+    }
+
+    fn track(&self, event: &MonitorEvent) {
+        match event.event {
+            SocketEvent::CONNECTED | SocketEvent::ACCEPTED => {
+                self.peers.set(self.peers.get() + 1);
+            }
+            SocketEvent::DISCONNECTED => {
+                self.peers.set(self.peers.get().saturating_sub(1));
+            }
+            _ => {}
+        }
+    }
+
+    fn decode(frames: &[Vec<u8>]) -> Result<MonitorEvent> {
+        match frames {
+            [header, endpoint] if header.len() == 6 => Ok(MonitorEvent {
+                event: SocketEvent::from_raw(u16::from_le_bytes([header[0], header[1]])),
+                value: u32::from_le_bytes([header[2], header[3], header[4], header[5]]),
+                endpoint: String::from_utf8_lossy(endpoint).into_owned(),
+            }),
+            _ => Err(OxideError::Protocol(
+                "malformed socket monitor event".to_string(),
+            )),
+        }
+    }
+}
+
+/// Wait up to `timeout_ms` (-1 waits forever) until the monitored socket
+/// has connected to a peer
+///
+/// Fails with `OxideError::Timeout` if no TCP or IPC connection is made in
+/// time; `inproc://` connections emit no event and always time out. A
+/// connection does not mean a PUB peer has seen a SUB socket's
+/// subscriptions yet: when the publisher is an `XPublisher`, use
+/// [`wait_until_subscribed`](crate::patterns::xpub::wait_until_subscribed)
+/// on its side instead.
+pub fn wait_until_connected(monitor: &SocketMonitor, timeout_ms: i32) -> Result<()> {
+    let deadline = deadline_after(timeout_ms);
+    loop {
+        let Some(wait_ms) = remaining_ms(deadline) else {
+            return Err(OxideError::Timeout(format!(
+                "no connection after {} ms",
+                timeout_ms
+            )));
+        };
+        if let Some(event) = monitor.next_event_timeout(wait_ms)? {
+            if event.event == SocketEvent::CONNECTED {
+                return Ok(());
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! Read back the effective options of a live socket
+//!
+//! Every pattern type has an `options()` method returning a
+//! [`SocketOptions`] snapshot, which is handy for checking that builder
+//! settings took effect.
+
+use crate::error::{OxideError, Result};
+use zmq::{Socket, SocketType};
+
+/// Snapshot of a socket's options, read with `zmq_getsockopt`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SocketOptions {
+    /// Send high-water mark in messages (`ZMQ_SNDHWM`, 0 means no limit)
+    pub sndhwm: i32,
+    /// Receive high-water mark in messages (`ZMQ_RCVHWM`, 0 means no limit)
+    pub rcvhwm: i32,
+    /// Requested kernel send buffer size in bytes (`ZMQ_SNDBUF`, -1 for the
+    /// OS default)
+    pub sndbuf: i32,
+    /// Requested kernel receive buffer size in bytes (`ZMQ_RCVBUF`, -1 for
+    /// the OS default)
+    pub rcvbuf: i32,
+    /// Milliseconds pending messages are kept after close (`ZMQ_LINGER`,
+    /// -1 waits forever)
+    pub linger: i32,
+    /// Receive timeout in milliseconds (`ZMQ_RCVTIMEO`, -1 blocks forever)
+    pub rcvtimeo: i32,
+    /// ZeroMQ socket type
+    pub type_: SocketType,
+    /// Endpoint of the most recent bind or connect, empty if there was none
+    pub last_endpoint: String,
+}
+
+impl SocketOptions {
+    pub(crate) fn read(socket: &Socket) -> Result<Self> {
+        let last_endpoint = match socket
+            .get_last_endpoint()
+            .map_err(OxideError::SocketOption)?
+        {
+            Ok(endpoint) => endpoint,
+            Err(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        };
+        Ok(Self {
+            sndhwm: socket.get_sndhwm().map_err(OxideError::SocketOption)?,
+            rcvhwm: socket.get_rcvhwm().map_err(OxideError::SocketOption)?,
+            sndbuf: socket.get_sndbuf().map_err(OxideError::SocketOption)?,
+            rcvbuf: socket.get_rcvbuf().map_err(OxideError::SocketOption)?,
+            linger: socket.get_linger().map_err(OxideError::SocketOption)?,
+            rcvtimeo: socket.get_rcvtimeo().map_err(OxideError::SocketOption)?,
+            type_: socket.get_socket_type().map_err(OxideError::SocketOption)?,
+            last_endpoint,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::patterns::{Publisher, Subscriber};
+
+    #[test]
+    fn test_options_reflect_builder_settings() {
+        let address = "tcp://127.0.0.1:15587";
+
+        let publisher = Publisher::builder().sndhwm(250).bind(address).unwrap();
+        let options = publisher.options().unwrap();
+        assert_eq!(options.sndhwm, 250);
+        assert_eq!(options.type_, zmq::PUB);
+        assert_eq!(options.last_endpoint, address);
+
+        let subscriber = Subscriber::builder().rcvhwm(42).connect(address).unwrap();
+        let options = subscriber.options().unwrap();
+        assert_eq!(options.rcvhwm, 42);
+        assert_eq!(options.rcvtimeo, -1);
+        assert_eq!(options.type_, zmq::SUB);
+    }
+
+    #[test]
+    fn test_buffer_sizes_set_and_read_back() {
+        let address = "tcp://127.0.0.1:15652";
+        let publisher = Publisher::builder()
+            .sndbuf(256 * 1024)
+            .bind(address)
+            .unwrap();
+        let subscriber = Subscriber::builder()
+            .rcvbuf(512 * 1024)
+            .connect(address)
+            .unwrap();
+        // libzmq reports the requested size, not the kernel's doubled one
+        assert_eq!(publisher.options().unwrap().sndbuf, 256 * 1024);
+        assert_eq!(publisher.options().unwrap().rcvbuf, -1);
+        assert_eq!(subscriber.options().unwrap().rcvbuf, 512 * 1024);
+
+        subscriber.set_sndbuf(64 * 1024).unwrap();
+        subscriber.set_rcvbuf(-1).unwrap();
+        let options = subscriber.options().unwrap();
+        assert_eq!((options.sndbuf, options.rcvbuf), (64 * 1024, -1));
+    }
+}
@@ -1,9 +1,132 @@
 //! Messaging patterns built on ZeroMQ
 
+pub mod aggregator;
+pub mod async_client;
+#[cfg(feature = "async")]
+pub mod async_publisher;
+pub mod batch;
+pub mod bounded;
+pub mod combinators;
+pub mod confirm;
+pub mod dedup;
+pub mod dispatcher;
+pub mod fire_and_forget;
+pub mod forwarder;
+pub mod health;
+pub mod heartbeat;
+pub mod idempotent;
+pub mod identified;
+pub mod peer;
 pub mod pipeline;
+pub mod pool;
+pub mod priority;
 pub mod pubsub;
+pub mod reliable;
+pub mod replay;
 pub mod reqrep;
+pub mod router;
+pub mod select;
+pub mod server;
+pub mod stream;
+pub mod subscription_set;
+pub mod subscription_tree;
+pub mod traits;
+pub mod workers;
+pub mod xpub;
 
+pub use aggregator::Aggregator;
+pub use async_client::{AsyncClient, ReplyHandle};
+#[cfg(feature = "async")]
+pub use async_publisher::{AsyncPublisher, Publish};
+pub use batch::BatchRequester;
+pub use bounded::BoundedPuller;
+pub use confirm::{ConfirmedPublisher, ConfirmedSubscriber};
+pub use dedup::DedupSubscriber;
+pub use dispatcher::Dispatcher;
+pub use fire_and_forget::FireAndForget;
+pub use forwarder::Forwarder;
+pub use health::{HealthClient, HealthServer, HealthStatus, HEALTH_TOPIC};
+pub use idempotent::{IdempotentReplier, IdempotentRequester, IDEMPOTENCY_KEY_HEADER};
+pub use identified::{IdentifiedReplier, PeerId};
+pub use peer::Peer;
 pub use pipeline::{Puller, Pusher};
-pub use pubsub::{Publisher, Subscriber};
-pub use reqrep::{Replier, Requester};
+pub use pool::{PooledRequester, RequesterPool};
+pub use priority::PriorityPublisher;
+pub use pubsub::{Publisher, ScopedSubscription, Subscriber, TopicStat};
+pub use reliable::ReliableQueue;
+pub use replay::ReplayPublisher;
+pub use reqrep::{Replier, RequestOutcome, Requester};
+pub use router::{Dealer, RoutedMessage, Router};
+pub use select::{select_receive, Receivable};
+pub use server::{ServerHandle, ServerStats};
+pub use stream::Stream;
+pub use subscription_set::SubscriptionSet;
+pub use subscription_tree::SubscriptionTree;
+pub use traits::{drain, MessageReceiver, MessageSender};
+pub use workers::WorkerPool;
+pub use xpub::{wait_until_subscribed, Subscription, XPublisher};
+
+use crate::error::{OxideError, Result};
+use std::time::{Duration, Instant};
+use zmq::Socket;
+
+/// When a wait of `timeout_ms` starting now ends, or `None` for a negative
+/// timeout that waits forever
+pub(crate) fn deadline_after(timeout_ms: i32) -> Option<Instant> {
+    u64::try_from(timeout_ms)
+        .ok()
+        .map(|ms| Instant::now() + Duration::from_millis(ms))
+}
+
+/// Milliseconds left until `deadline` as a ZeroMQ timeout, `-1` without a
+/// deadline, or `None` once the deadline has passed
+pub(crate) fn remaining_ms(deadline: Option<Instant>) -> Option<i32> {
+    match deadline {
+        Some(deadline) => {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            (!remaining.is_zero()).then_some(remaining.as_millis() as i32)
+        }
+        None => Some(-1),
+    }
+}
+
+/// Receive one frame, waiting at most `timeout_ms` milliseconds
+///
+/// The socket's previous `ZMQ_RCVTIMEO` is restored afterwards, so a later
+/// blocking receive still blocks.
+pub(crate) fn recv_bytes_timeout(socket: &Socket, timeout_ms: i32) -> Result<Option<Vec<u8>>> {
+    let previous = socket.get_rcvtimeo().map_err(OxideError::SocketOption)?;
+    socket
+        .set_rcvtimeo(timeout_ms)
+        .map_err(OxideError::SocketOption)?;
+
+    let received = socket.recv_bytes(0);
+
+    socket
+        .set_rcvtimeo(previous)
+        .map_err(OxideError::SocketOption)?;
+
+    match received {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(zmq::Error::EAGAIN) => Ok(None),
+        Err(e) => Err(OxideError::Receive(e)),
+    }
+}
+
+/// Send one frame, waiting at most `timeout_ms` milliseconds
+///
+/// Fails with `OxideError::Send(zmq::Error::EAGAIN)` on timeout. The
+/// socket's previous `ZMQ_SNDTIMEO` is restored afterwards.
+pub(crate) fn send_bytes_timeout(socket: &Socket, bytes: &[u8], timeout_ms: i32) -> Result<()> {
+    let previous = socket.get_sndtimeo().map_err(OxideError::SocketOption)?;
+    socket
+        .set_sndtimeo(timeout_ms)
+        .map_err(OxideError::SocketOption)?;
+
+    let sent = socket.send(bytes, 0);
+
+    socket
+        .set_sndtimeo(previous)
+        .map_err(OxideError::SocketOption)?;
+    sent.map_err(OxideError::Send)
+}
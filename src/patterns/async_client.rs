@@ -0,0 +1,241 @@
+//! Request/reply client with many requests in flight
+//!
+//! A `Requester` waits for each reply before the next request can go out.
+//! [`AsyncClient::call`] returns a [`ReplyHandle`] straight away instead,
+//! and a background thread matches replies to requests by the message
+//! [`id`](Message::id): untracked requests are tracked first, and the
+//! server must copy the request's `id` into its reply. Any ROUTER peer
+//! works, for example a [`Router`](crate::patterns::Router) answering with
+//! `send_routed`; replies may arrive in any order.
+//!
+//! A request stays pending until its reply arrives or its deadline passes,
+//! after which the background thread drops it and resolves the handle with
+//! [`OxideError::Timeout`]. Without a deadline, from
+//! [`AsyncClient::with_default_timeout`] or [`AsyncClient::call_timeout`],
+//! a request whose reply is lost stays pending for the life of the client.
+//! Replies arriving after their request timed out are discarded.
+
+use crate::error::{OxideError, Result};
+use crate::message::Message;
+use crate::patterns::Dealer;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How long the client thread waits for replies before checking for new
+/// requests, expired ones and whether it should stop
+const CLIENT_POLL_MS: i32 = 10;
+
+struct Pending {
+    deadline: Option<Instant>,
+    reply: Sender<Result<Message>>,
+}
+
+#[derive(Default)]
+struct SharedState {
+    stop: AtomicBool,
+    pending: Mutex<HashMap<String, Pending>>,
+}
+
+impl SharedState {
+    fn resolve(&self, id: &str, result: Result<Message>) {
+        let pending = self.pending.lock().unwrap().remove(id);
+        if let Some(pending) = pending {
+            // The caller may have dropped the handle already
+            let _ = pending.reply.send(result);
+        }
+    }
+
+    fn expire(&self, now: Instant) {
+        let mut pending = self.pending.lock().unwrap();
+        let expired: Vec<String> = pending
+            .iter()
+            .filter(|(_, request)| request.deadline.is_some_and(|deadline| deadline <= now))
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in expired {
+            if let Some(request) = pending.remove(&id) {
+                let _ = request.reply.send(Err(OxideError::Timeout(format!(
+                    "no reply to request {}",
+                    id
+                ))));
+            }
+        }
+    }
+}
+
+/// Reply to a request made with [`AsyncClient::call`]
+pub struct ReplyHandle {
+    id: String,
+    reply: Receiver<Result<Message>>,
+}
+
+impl ReplyHandle {
+    /// Id of the request, which the reply carries as well
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Wait for the reply, or the timeout or send error ending the request
+    pub fn wait(self) -> Result<Message> {
+        self.reply.recv().unwrap_or_else(|_| Err(client_stopped()))
+    }
+
+    /// The reply if the request has finished, without blocking
+    pub fn try_wait(&self) -> Option<Result<Message>> {
+        match self.reply.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(client_stopped())),
+        }
+    }
+}
+
+fn client_stopped() -> OxideError {
+    OxideError::Connection("async client stopped before the reply arrived".to_string())
+}
+
+/// Client sending requests over a DEALER socket without waiting for replies
+///
+/// Dropping the client stops its thread; handles still pending then
+/// resolve with a connection error.
+pub struct AsyncClient {
+    state: Arc<SharedState>,
+    requests: Sender<Message>,
+    default_timeout: Option<Duration>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl AsyncClient {
+    /// Connect to the server at `address`
+    pub fn new(address: &str) -> Result<Self> {
+        let dealer = Dealer::new(address)?;
+        let state = Arc::new(SharedState::default());
+        let (requests, outgoing) = mpsc::channel();
+        let thread = {
+            let state = Arc::clone(&state);
+            thread::spawn(move || Self::run(&dealer, &state, &outgoing))
+        };
+        Ok(Self {
+            state,
+            requests,
+            default_timeout: None,
+            thread: Some(thread),
+        })
+    }
+
+    /// Time out requests made with [`AsyncClient::call`] after `timeout_ms`
+    pub fn with_default_timeout(mut self, timeout_ms: u64) -> Self {
+        self.default_timeout = Some(Duration::from_millis(timeout_ms));
+        self
+    }
+
+    fn run(dealer: &Dealer, state: &SharedState, outgoing: &Receiver<Message>) {
+        while !state.stop.load(Ordering::Relaxed) {
+            for request in outgoing.try_iter() {
+                if let Err(e) = dealer.send(&request) {
+                    state.resolve(request.id.as_deref().unwrap_or_default(), Err(e));
+                }
+            }
+            let mut timeout_ms = CLIENT_POLL_MS;
+            loop {
+                match dealer.receive_timeout(timeout_ms) {
+                    Ok(Some(reply)) => {
+                        if let Some(id) = reply.id.clone() {
+                            state.resolve(&id, Ok(reply));
+                        }
+                    }
+                    // A malformed reply cannot be matched to its request,
+                    // which then times out
+                    Err(OxideError::Serialization(_) | OxideError::Protocol(_)) => {}
+                    Ok(None) | Err(_) => break,
+                }
+                timeout_ms = 0;
+            }
+            state.expire(Instant::now());
+        }
+    }
+
+    /// Send `request` and return a handle to its reply
+    ///
+    /// The request times out after the default timeout, if one is set.
+    pub fn call(&self, request: &Message) -> Result<ReplyHandle> {
+        self.send(request, self.default_timeout)
+    }
+
+    /// Send `request` and time it out after `timeout_ms`
+    pub fn call_timeout(&self, request: &Message, timeout_ms: u64) -> Result<ReplyHandle> {
+        self.send(request, Some(Duration::from_millis(timeout_ms)))
+    }
+
+    fn send(&self, request: &Message, timeout: Option<Duration>) -> Result<ReplyHandle> {
+        let request = match request.id {
+            Some(_) => request.clone(),
+            None => request.clone().track(),
+        };
+        let id = request.id.clone().unwrap_or_default();
+        let (reply, handle) = mpsc::channel();
+        self.state.pending.lock().unwrap().insert(
+            id.clone(),
+            Pending {
+                deadline: timeout.map(|timeout| Instant::now() + timeout),
+                reply,
+            },
+        );
+        if self.requests.send(request).is_err() {
+            self.state.pending.lock().unwrap().remove(&id);
+            return Err(client_stopped());
+        }
+        Ok(ReplyHandle { id, reply: handle })
+    }
+
+    /// Number of requests still waiting for a reply
+    pub fn pending(&self) -> usize {
+        self.state.pending.lock().unwrap().len()
+    }
+}
+
+impl Drop for AsyncClient {
+    fn drop(&mut self) {
+        self.state.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        self.state.pending.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patterns::{RoutedMessage, Router};
+    use serde_json::json;
+
+    #[test]
+    fn test_unanswered_call_times_out() {
+        let address = "tcp://127.0.0.1:15612";
+        let router = Router::new(address).unwrap();
+        let client = AsyncClient::new(address).unwrap().with_default_timeout(300);
+
+        let answered = client.call(&Message::new("ping", json!(1))).unwrap();
+        let request = router.recv_routed_timeout(2000).unwrap().unwrap();
+        let mut reply = Message::new("pong", json!(1));
+        reply.id = request.message.id;
+        router
+            .send_routed(&RoutedMessage {
+                identity: request.identity,
+                message: reply,
+            })
+            .unwrap();
+        assert_eq!(answered.wait().unwrap().topic, "pong");
+
+        // The router never answers this one
+        let silent = client.call(&Message::new("ping", json!(2))).unwrap();
+        assert_eq!(client.pending(), 1);
+        assert!(matches!(silent.wait(), Err(OxideError::Timeout(_))));
+        assert_eq!(client.pending(), 0);
+    }
+}
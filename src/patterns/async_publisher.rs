@@ -0,0 +1,349 @@
+//! Publishing from async code with backpressure (`async` feature)
+//!
+//! An [`AsyncPublisher`] puts messages on a bounded queue that a dedicated
+//! thread drains into a [`Publisher`]. [`AsyncPublisher::publish`] returns
+//! a future that completes once its message is on the queue; while the
+//! queue is full the future stays pending, so a congested socket suspends
+//! the publishing task instead of blocking its executor thread or
+//! buffering without limit.
+//!
+//! The future only needs a waker, so it runs on any executor. Backpressure
+//! reaches the queue only if the publisher itself pushes back, which a
+//! plain PUB socket never does: it drops messages for subscribers at their
+//! high-water mark. Build the publisher with
+//! [`SocketBuilder::nodrop`](crate::SocketBuilder::nodrop) to hold them
+//! instead, as in [`AsyncPublisher::from_publisher`].
+//!
+//! Messages still queued when the `AsyncPublisher` is dropped are
+//! discarded; a send error stops the thread, after which `publish` fails.
+
+use crate::endpoint::Endpoint;
+use crate::error::{OxideError, Result};
+use crate::message::Message;
+use crate::patterns::Publisher;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How long the sender thread waits before retrying a send refused at the
+/// high-water mark
+const SEND_RETRY_MS: u64 = 1;
+
+#[derive(Default)]
+struct Queue {
+    messages: VecDeque<Message>,
+    /// Wakers of `publish` futures waiting for room, one per task
+    waiting: Vec<Waker>,
+}
+
+/// State shared with the sender thread
+struct Shared {
+    queue: Mutex<Queue>,
+    arrived: Condvar,
+    stop: AtomicBool,
+    stopped: AtomicBool,
+}
+
+/// Publisher fed from async code through a bounded queue
+pub struct AsyncPublisher {
+    shared: Arc<Shared>,
+    capacity: usize,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl AsyncPublisher {
+    /// Create an async publisher that binds to the specified address with
+    /// room for `capacity` queued messages
+    ///
+    /// The socket is built with `nodrop(true)`, so slow subscribers push
+    /// back on the queue.
+    pub fn new(address: impl Into<Endpoint>, capacity: usize) -> Result<Self> {
+        Self::from_publisher(Publisher::builder().nodrop(true).bind(address)?, capacity)
+    }
+
+    /// Send queued messages through an existing publisher on a background
+    /// thread
+    pub fn from_publisher(publisher: Publisher, capacity: usize) -> Result<Self> {
+        if capacity == 0 {
+            return Err(OxideError::Configuration(
+                "async publisher capacity must be at least one message".to_string(),
+            ));
+        }
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue::default()),
+            arrived: Condvar::new(),
+            stop: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+        });
+        let thread = {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || run(&publisher, &shared))
+        };
+        Ok(Self {
+            shared,
+            capacity,
+            thread: Some(thread),
+        })
+    }
+
+    /// Most messages queued at once
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Messages queued and not yet handed to the socket
+    pub fn len(&self) -> usize {
+        self.shared.queue.lock().unwrap().messages.len()
+    }
+
+    /// Whether no messages are queued
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Queue `message` for publishing, waiting for room while the queue is
+    /// full
+    ///
+    /// Completes once the message is queued, not once it is sent. Fails
+    /// with `OxideError::Connection` if the sender thread has stopped.
+    pub fn publish(&self, message: &Message) -> Publish<'_> {
+        Publish {
+            publisher: self,
+            message: Some(message.clone()),
+        }
+    }
+}
+
+/// Future returned by [`AsyncPublisher::publish`]
+pub struct Publish<'a> {
+    publisher: &'a AsyncPublisher,
+    message: Option<Message>,
+}
+
+impl Future for Publish<'_> {
+    type Output = Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let shared = &self.publisher.shared;
+        let mut queue = shared.queue.lock().unwrap();
+        if shared.stopped.load(Ordering::Relaxed) {
+            return Poll::Ready(Err(OxideError::Connection(
+                "async publisher thread has stopped".to_string(),
+            )));
+        }
+        if queue.messages.len() >= self.publisher.capacity {
+            if !queue
+                .waiting
+                .iter()
+                .any(|waker| waker.will_wake(cx.waker()))
+            {
+                queue.waiting.push(cx.waker().clone());
+            }
+            return Poll::Pending;
+        }
+        let message = self
+            .message
+            .take()
+            .expect("Publish future polled after completion");
+        queue.messages.push_back(message);
+        shared.arrived.notify_one();
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn run(publisher: &Publisher, shared: &Shared) {
+    'messages: loop {
+        let message = {
+            let mut queue = shared.queue.lock().unwrap();
+            // Checked under the lock, which `Drop` holds while setting it,
+            // so the wake-up cannot be missed
+            if shared.stop.load(Ordering::Relaxed) {
+                break;
+            }
+            let Some(message) = queue.messages.pop_front() else {
+                drop(shared.arrived.wait(queue).unwrap());
+                continue;
+            };
+            for waker in queue.waiting.drain(..) {
+                waker.wake();
+            }
+            message
+        };
+        loop {
+            match publisher.try_publish(&message) {
+                Ok(()) => break,
+                Err(OxideError::Send(zmq::Error::EAGAIN)) => {
+                    if shared.stop.load(Ordering::Relaxed) {
+                        break 'messages;
+                    }
+                    thread::sleep(Duration::from_millis(SEND_RETRY_MS));
+                }
+                Err(_) => break 'messages,
+            }
+        }
+    }
+    // Taking the lock first keeps the flag from landing between a
+    // future's check and its waker registration
+    let mut queue = shared.queue.lock().unwrap();
+    shared.stopped.store(true, Ordering::Relaxed);
+    for waker in queue.waiting.drain(..) {
+        waker.wake();
+    }
+}
+
+impl Drop for AsyncPublisher {
+    fn drop(&mut self) {
+        {
+            let _queue = self.shared.queue.lock().unwrap();
+            self.shared.stop.store(true, Ordering::Relaxed);
+            self.shared.arrived.notify_all();
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patterns::Subscriber;
+    use serde_json::json;
+    use std::sync::atomic::AtomicUsize;
+    use std::task::Wake;
+    use std::time::Instant;
+
+    /// Counts how often the test future is woken
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Nodrop publisher with a connected subscriber, both with a
+    /// high-water mark of one so they fill up quickly
+    fn congestible_pair(context: &zmq::Context, endpoint: &str) -> (Publisher, Subscriber) {
+        let publisher = Publisher::builder()
+            .context(context)
+            .nodrop(true)
+            .sndhwm(1)
+            .bind(endpoint)
+            .unwrap();
+        let subscriber = Subscriber::builder()
+            .context(context)
+            .rcvhwm(1)
+            .connect(endpoint)
+            .unwrap();
+        subscriber.subscribe("").unwrap();
+        let probe = Message::new("probe", json!(null));
+        let ready = (0..200).any(|_| {
+            let _ = publisher.try_publish(&probe);
+            subscriber.receive_timeout(10).unwrap().is_some()
+        });
+        assert!(ready);
+        (publisher, subscriber)
+    }
+
+    #[test]
+    fn test_full_queue_suspends_publish() {
+        let context = zmq::Context::new();
+        let (publisher, subscriber) = congestible_pair(&context, "inproc://async-publisher");
+
+        let publisher = AsyncPublisher::from_publisher(publisher, 2).unwrap();
+        assert_eq!(publisher.capacity(), 2);
+        let waker = Arc::new(CountingWaker::default());
+        let wakes = Arc::clone(&waker);
+        let waker = Waker::from(waker);
+        let mut cx = Context::from_waker(&waker);
+
+        // The subscriber has stopped reading: publish until the socket and
+        // then the queue fill up and a publish has to wait
+        let mut sent = 0;
+        let mut stalled = None;
+        while sent < 100 {
+            let mut publish = Box::pin(publisher.publish(&Message::new("flood", json!(sent))));
+            // A pending publish may only be waiting for the thread to catch
+            // up; it is stuck once it stays pending
+            let mut result = publish.as_mut().poll(&mut cx);
+            if result.is_pending() {
+                thread::sleep(Duration::from_millis(50));
+                result = publish.as_mut().poll(&mut cx);
+            }
+            match result {
+                Poll::Ready(result) => result.unwrap(),
+                Poll::Pending => {
+                    stalled = Some(publish);
+                    break;
+                }
+            }
+            sent += 1;
+        }
+        let mut stalled = stalled.expect("publish never had to wait");
+        assert_eq!(publisher.len(), 2);
+        // Polled twice by the same task, registered once
+        assert_eq!(publisher.shared.queue.lock().unwrap().waiting.len(), 1);
+        wakes.0.store(0, Ordering::SeqCst);
+
+        // Reading again makes room, wakes the publish and loses nothing
+        let mut received = Vec::new();
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while wakes.0.load(Ordering::SeqCst) == 0 && Instant::now() < deadline {
+            if let Some(message) = subscriber.receive_timeout(10).unwrap() {
+                received.push(message.payload);
+            }
+        }
+        assert!(wakes.0.load(Ordering::SeqCst) > 0);
+        assert!(matches!(
+            stalled.as_mut().poll(&mut cx),
+            Poll::Ready(Ok(()))
+        ));
+        while let Some(message) = subscriber.receive_timeout(200).unwrap() {
+            received.push(message.payload);
+        }
+        let expected: Vec<_> = (0..=sent).map(|n| json!(n)).collect();
+        assert_eq!(received, expected);
+    }
+
+    #[tokio::test]
+    async fn test_publish_await_suspends_task_until_room() {
+        let context = zmq::Context::new();
+        let (publisher, subscriber) = congestible_pair(&context, "inproc://async-publisher-tokio");
+        let publisher = AsyncPublisher::from_publisher(publisher, 2).unwrap();
+
+        // The runtime keeps going while the publish waits, so the timeout
+        // fires
+        let mut suspended = false;
+        for n in 0..100 {
+            let publish = publisher.publish(&Message::new("flood", json!(n)));
+            if tokio::time::timeout(Duration::from_millis(50), publish)
+                .await
+                .is_err()
+            {
+                suspended = true;
+                break;
+            }
+        }
+        assert!(suspended, "publish never had to wait");
+
+        let reader = thread::spawn(
+            move || {
+                while subscriber.receive_timeout(500).unwrap().is_some() {}
+            },
+        );
+        let publish = publisher.publish(&Message::new("after", json!(null)));
+        tokio::time::timeout(Duration::from_secs(2), publish)
+            .await
+            .expect("publish still waiting after the subscriber caught up")
+            .unwrap();
+        drop(publisher);
+        reader.join().unwrap();
+    }
+}
@@ -0,0 +1,124 @@
+//! Many requests in flight from one call
+//!
+//! A `Requester` waits for each reply before sending the next request, so a
+//! batch of queries costs one round trip each. [`BatchRequester::request_all`]
+//! sends the whole batch over a DEALER socket first and then collects the
+//! replies, costing roughly one round trip for the batch.
+//!
+//! Replies are matched to requests by the message [`id`](Message::id), as
+//! with [`AsyncClient`](crate::patterns::AsyncClient): untracked requests
+//! are tracked first, and the server must copy each request's `id` into its
+//! reply. The result lists replies in request order whatever order they
+//! arrive in, with `None` for requests that got no reply before the
+//! timeout. Replies to earlier batches that arrive late are discarded.
+
+use crate::endpoint::Endpoint;
+use crate::error::{OxideError, Result};
+use crate::message::Message;
+use crate::patterns::{deadline_after, remaining_ms, Dealer};
+use std::collections::HashMap;
+
+/// Client sending batches of requests over a DEALER socket
+pub struct BatchRequester {
+    dealer: Dealer,
+}
+
+impl BatchRequester {
+    /// Create a new batch requester that connects to the specified address
+    pub fn new(address: impl Into<Endpoint>) -> Result<Self> {
+        Ok(Self {
+            dealer: Dealer::new(address)?,
+        })
+    }
+
+    /// Send every request in `requests`, then wait up to `timeout_ms` in
+    /// total (-1 waits forever) for their replies
+    ///
+    /// Fails with `OxideError::Configuration` if two requests share an id.
+    /// Replies that cannot be decoded are skipped.
+    pub fn request_all(
+        &self,
+        requests: Vec<Message>,
+        timeout_ms: i32,
+    ) -> Result<Vec<Option<Message>>> {
+        let mut positions = HashMap::with_capacity(requests.len());
+        let requests: Vec<Message> = requests
+            .into_iter()
+            .map(|request| {
+                if request.is_tracked() {
+                    request
+                } else {
+                    request.track()
+                }
+            })
+            .collect();
+        for (position, request) in requests.iter().enumerate() {
+            let id = request.id.as_deref().expect("requests are tracked");
+            if positions.insert(id.to_string(), position).is_some() {
+                return Err(OxideError::Configuration(format!(
+                    "request id {} appears twice in the batch",
+                    id
+                )));
+            }
+        }
+        for request in &requests {
+            self.dealer.send(request)?;
+        }
+
+        let deadline = deadline_after(timeout_ms);
+        let mut replies: Vec<Option<Message>> = vec![None; requests.len()];
+        while !positions.is_empty() {
+            let wait_ms = remaining_ms(deadline).unwrap_or(0);
+            let reply = match self.dealer.receive_timeout(wait_ms) {
+                Ok(Some(reply)) => reply,
+                Ok(None) => break,
+                Err(OxideError::Serialization(_) | OxideError::Protocol(_)) => continue,
+                Err(e) => return Err(e),
+            };
+            let position = reply.id.as_ref().and_then(|id| positions.remove(id));
+            if let Some(position) = position {
+                replies[position] = Some(reply);
+            }
+        }
+        Ok(replies)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patterns::{RoutedMessage, Router};
+    use serde_json::json;
+    use std::thread;
+
+    #[test]
+    fn test_replies_align_with_requests() {
+        let address = "tcp://127.0.0.1:15632";
+        let router = Router::new(address).unwrap();
+        let server = thread::spawn(move || {
+            let requests: Vec<RoutedMessage> = (0..3)
+                .map(|_| router.recv_routed_timeout(2000).unwrap().unwrap())
+                .collect();
+            // Answer in reverse order so arrival order differs from request order
+            for request in requests.into_iter().rev() {
+                let n = request.message.payload.as_i64().unwrap();
+                let mut reply = Message::new("doubled", json!(n * 2));
+                reply.id = request.message.id;
+                router
+                    .send_routed(&RoutedMessage {
+                        identity: request.identity,
+                        message: reply,
+                    })
+                    .unwrap();
+            }
+            router
+        });
+
+        let client = BatchRequester::new(address).unwrap();
+        let requests = (1..=3).map(|n| Message::new("double", json!(n))).collect();
+        let replies = client.request_all(requests, 2000).unwrap();
+        let payloads: Vec<_> = replies.into_iter().map(|r| r.unwrap().payload).collect();
+        assert_eq!(payloads, [json!(2), json!(4), json!(6)]);
+        drop(server.join().unwrap());
+    }
+}
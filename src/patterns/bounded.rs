@@ -0,0 +1,198 @@
+//! Puller that keeps the newest messages when the consumer falls behind
+//!
+//! ZeroMQ queues up to the high-water mark and then pushes back on the
+//! sender, so a slow consumer ends up working through old messages.
+//! `ZMQ_CONFLATE` goes to the other extreme and keeps only the latest one.
+//! A [`BoundedPuller`] keeps the newest `capacity` messages: a background
+//! thread pulls continuously into a bounded queue, dropping the oldest
+//! message when the queue is full, and [`BoundedPuller::recv`] serves from
+//! that queue.
+//!
+//! The thread owns the socket, so the ZeroMQ queue stays nearly empty and
+//! senders are never pushed back on. Memory is bounded by `capacity`
+//! decoded messages. Messages that fail to decode are skipped; any other
+//! receive error stops the thread, and `recv` fails once the queue has
+//! been emptied.
+
+use crate::endpoint::Endpoint;
+use crate::error::{OxideError, Result};
+use crate::message::Message;
+use crate::patterns::{deadline_after, remaining_ms, Puller};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How long the pull thread waits for a message before checking whether
+/// it should stop
+const PULL_POLL_MS: i32 = 10;
+
+/// State shared with the pull thread
+struct Shared {
+    queue: Mutex<VecDeque<Message>>,
+    arrived: Condvar,
+    dropped: AtomicU64,
+    stop: AtomicBool,
+    stopped: AtomicBool,
+}
+
+/// Puller holding at most `capacity` messages, dropping the oldest
+pub struct BoundedPuller {
+    shared: Arc<Shared>,
+    capacity: usize,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl BoundedPuller {
+    /// Create a bounded puller that binds to the specified address
+    pub fn new_bind(address: impl Into<Endpoint>, capacity: usize) -> Result<Self> {
+        Self::from_puller(Puller::new_bind(address)?, capacity)
+    }
+
+    /// Create a bounded puller that connects to the specified address
+    pub fn new_connect(address: impl Into<Endpoint>, capacity: usize) -> Result<Self> {
+        Self::from_puller(Puller::new_connect(address)?, capacity)
+    }
+
+    /// Pull from an existing puller on a background thread
+    pub fn from_puller(puller: Puller, capacity: usize) -> Result<Self> {
+        if capacity == 0 {
+            return Err(OxideError::Configuration(
+                "bounded puller capacity must be at least one message".to_string(),
+            ));
+        }
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(VecDeque::with_capacity(capacity)),
+            arrived: Condvar::new(),
+            dropped: AtomicU64::new(0),
+            stop: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+        });
+        let thread = {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || run(&puller, &shared, capacity))
+        };
+        Ok(Self {
+            shared,
+            capacity,
+            thread: Some(thread),
+        })
+    }
+
+    /// Most messages held at once
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Messages currently held
+    pub fn len(&self) -> usize {
+        self.shared.queue.lock().unwrap().len()
+    }
+
+    /// Whether no messages are held
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Messages dropped to make room for newer ones
+    pub fn dropped_count(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Take the oldest held message (blocking)
+    pub fn recv(&self) -> Result<Message> {
+        loop {
+            if let Some(message) = self.recv_timeout(-1)? {
+                return Ok(message);
+            }
+        }
+    }
+
+    /// Take the oldest held message, waiting at most `timeout_ms` (-1 waits
+    /// forever)
+    pub fn recv_timeout(&self, timeout_ms: i32) -> Result<Option<Message>> {
+        let deadline = deadline_after(timeout_ms);
+        let mut queue = self.shared.queue.lock().unwrap();
+        loop {
+            if let Some(message) = queue.pop_front() {
+                return Ok(Some(message));
+            }
+            if self.shared.stopped.load(Ordering::Relaxed) {
+                return Err(OxideError::Connection(
+                    "bounded puller thread has stopped".to_string(),
+                ));
+            }
+            queue = match remaining_ms(deadline) {
+                Some(-1) => self.shared.arrived.wait(queue).unwrap(),
+                Some(ms) => {
+                    self.shared
+                        .arrived
+                        .wait_timeout(queue, Duration::from_millis(ms as u64))
+                        .unwrap()
+                        .0
+                }
+                None => return Ok(None),
+            };
+        }
+    }
+}
+
+fn run(puller: &Puller, shared: &Shared, capacity: usize) {
+    while !shared.stop.load(Ordering::Relaxed) {
+        let message = match puller.pull_timeout(PULL_POLL_MS) {
+            Ok(Some(message)) => message,
+            Ok(None) | Err(OxideError::Serialization(_)) => continue,
+            Err(_) => break,
+        };
+        let mut queue = shared.queue.lock().unwrap();
+        if queue.len() == capacity {
+            queue.pop_front();
+            shared.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        queue.push_back(message);
+        shared.arrived.notify_one();
+    }
+    shared.stopped.store(true, Ordering::Relaxed);
+    // Wake a receiver blocked on the empty queue; taking the lock first
+    // keeps the flag from landing between its check and its wait
+    let _queue = shared.queue.lock().unwrap();
+    shared.arrived.notify_all();
+}
+
+impl Drop for BoundedPuller {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestPair;
+    use serde_json::json;
+    use std::time::Instant;
+
+    #[test]
+    fn test_flood_keeps_newest_messages() {
+        let pair = TestPair::pipeline().unwrap();
+        let puller = BoundedPuller::from_puller(pair.receiver, 3).unwrap();
+        for i in 0..10 {
+            pair.sender.push(&Message::new("n", json!(i))).unwrap();
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while puller.dropped_count() < 7 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(puller.dropped_count(), 7);
+        assert_eq!(puller.len(), 3);
+
+        let payloads: Vec<_> = (0..3).map(|_| puller.recv().unwrap().payload).collect();
+        assert_eq!(payloads, [json!(7), json!(8), json!(9)]);
+        assert!(puller.recv_timeout(50).unwrap().is_none());
+    }
+}
@@ -0,0 +1,164 @@
+//! Iterator adapters over a subscriber's incoming messages
+//!
+//! [`Subscriber::messages`], [`Subscriber::map`] and [`Subscriber::filter`]
+//! turn a subscriber into a blocking, never-ending iterator, so a receive
+//! loop can be written with combinators:
+//!
+//! ```no_run
+//! use oxide_msg::Subscriber;
+//!
+//! let subscriber = Subscriber::new("tcp://127.0.0.1:5555")?;
+//! subscriber.subscribe("")?;
+//! for topic in subscriber.filter(|msg| msg.topic != "heartbeat").map(|msg| msg.topic) {
+//!     println!("{}", topic?);
+//! }
+//! # Ok::<(), oxide_msg::OxideError>(())
+//! ```
+//!
+//! Every item is a `Result`: receive and decode errors are yielded as they
+//! happen, bypassing the map function and the predicate, and iteration
+//! continues afterwards. [`Filter`] has its own `map` and `filter`, which
+//! work on the messages inside the results like the `Subscriber` methods;
+//! beyond that, use the standard iterator methods.
+
+use crate::error::Result;
+use crate::message::Message;
+use crate::patterns::Subscriber;
+
+/// Blocking iterator over the messages of a [`Subscriber`]
+pub struct Messages {
+    subscriber: Subscriber,
+}
+
+impl Messages {
+    /// The subscriber being read from
+    pub fn subscriber(&self) -> &Subscriber {
+        &self.subscriber
+    }
+
+    /// Give back the subscriber
+    pub fn into_inner(self) -> Subscriber {
+        self.subscriber
+    }
+}
+
+impl Iterator for Messages {
+    type Item = Result<Message>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.subscriber.receive())
+    }
+}
+
+/// Messages transformed by a function, see [`Subscriber::map`]
+pub struct Map<I, F> {
+    inner: I,
+    f: F,
+}
+
+impl<I, F, T> Iterator for Map<I, F>
+where
+    I: Iterator<Item = Result<Message>>,
+    F: FnMut(Message) -> T,
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|received| received.map(&mut self.f))
+    }
+}
+
+/// Messages matching a predicate, see [`Subscriber::filter`]
+pub struct Filter<I, P> {
+    inner: I,
+    predicate: P,
+}
+
+impl<I, P> Filter<I, P>
+where
+    I: Iterator<Item = Result<Message>>,
+    P: FnMut(&Message) -> bool,
+{
+    /// Transform each matching message with `f`
+    pub fn map<T, F: FnMut(Message) -> T>(self, f: F) -> Map<Self, F> {
+        Map { inner: self, f }
+    }
+
+    /// Further skip messages for which `predicate` returns `false`
+    pub fn filter<Q: FnMut(&Message) -> bool>(self, predicate: Q) -> Filter<Self, Q> {
+        Filter {
+            inner: self,
+            predicate,
+        }
+    }
+}
+
+impl<I, P> Iterator for Filter<I, P>
+where
+    I: Iterator<Item = Result<Message>>,
+    P: FnMut(&Message) -> bool,
+{
+    type Item = Result<Message>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.inner.next()? {
+                Ok(message) if !(self.predicate)(&message) => continue,
+                received => return Some(received),
+            }
+        }
+    }
+}
+
+impl Subscriber {
+    /// Iterate over incoming messages, blocking for each one
+    pub fn messages(self) -> Messages {
+        Messages { subscriber: self }
+    }
+
+    /// Iterate over incoming messages transformed by `f`
+    pub fn map<T, F: FnMut(Message) -> T>(self, f: F) -> Map<Messages, F> {
+        Map {
+            inner: self.messages(),
+            f,
+        }
+    }
+
+    /// Iterate over incoming messages for which `predicate` returns `true`
+    pub fn filter<P: FnMut(&Message) -> bool>(self, predicate: P) -> Filter<Messages, P> {
+        Filter {
+            inner: self.messages(),
+            predicate,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::message::Message;
+    use crate::test_support::TestPair;
+    use crate::OxideError;
+    use serde_json::json;
+
+    #[test]
+    fn test_filter_then_map_topics() {
+        let pair = TestPair::pubsub().unwrap();
+        for topic in ["orders", "heartbeat", "orders.eu", "heartbeat"] {
+            pair.sender
+                .publish(&Message::new(topic, json!(null)))
+                .unwrap();
+        }
+        pair.sender.publish_raw("broken", b"not json").unwrap();
+
+        let mut topics = pair
+            .receiver
+            .filter(|msg| msg.topic != "heartbeat")
+            .map(|msg| msg.topic);
+        assert_eq!(topics.next().unwrap().unwrap(), "orders");
+        assert_eq!(topics.next().unwrap().unwrap(), "orders.eu");
+        assert!(matches!(
+            topics.next().unwrap(),
+            Err(OxideError::Serialization(_))
+        ));
+    }
+}
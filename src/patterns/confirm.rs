@@ -0,0 +1,199 @@
+//! Publish/subscribe with delivery acknowledgements
+//!
+//! PUB/SUB never tells the publisher who received a message. Here every
+//! [`ConfirmedSubscriber`] also connects a PUSH socket to the publisher's
+//! PULL socket and sends back the id of each message it receives, so
+//! [`ConfirmedPublisher::publish_confirmed`] can count acknowledgements.
+//!
+//! Acknowledgements are keyed on the message id, so only tracked messages
+//! (see [`Message::tracked`]) can be confirmed. Acks that arrive after the
+//! publisher stopped waiting for them are discarded by the next call.
+//!
+//! The ack sockets are created in the same context as the publisher and
+//! subscriber, so both channels can use `inproc://` endpoints when the
+//! sockets come from builders sharing a context.
+
+use crate::builder::{new_socket, SocketBuilder};
+use crate::error::{OxideError, Result};
+use crate::message::Message;
+use crate::patterns::{deadline_after, recv_bytes_timeout, remaining_ms, Publisher, Subscriber};
+use zmq::Socket;
+
+/// Publisher that collects acknowledgements from [`ConfirmedSubscriber`]s
+pub struct ConfirmedPublisher {
+    publisher: Publisher,
+    acks: Socket,
+}
+
+impl ConfirmedPublisher {
+    /// Bind the publisher to `address` and the ack channel to `ack_address`
+    pub fn new(address: &str, ack_address: &str) -> Result<Self> {
+        Self::from_builder(Publisher::builder(), address, ack_address)
+    }
+
+    /// Bind a publisher made by `builder` to `address`, and the ack channel
+    /// in the same context to `ack_address`
+    pub fn from_builder(
+        builder: SocketBuilder<Publisher>,
+        address: &str,
+        ack_address: &str,
+    ) -> Result<Self> {
+        let context = builder.context_or_default();
+        let publisher = builder.context(&context).bind(address)?;
+        let acks = new_socket(&context, zmq::PULL)?;
+        acks.bind(ack_address)?;
+        Ok(Self { publisher, acks })
+    }
+
+    /// Publish a tracked message and wait for up to `expected_acks`
+    /// acknowledgements
+    ///
+    /// Returns the number of acks received before `timeout_ms` elapsed (-1
+    /// waits until all of them have arrived), returning early once
+    /// `expected_acks` have arrived. Untracked messages are rejected with
+    /// [`OxideError::Configuration`].
+    pub fn publish_confirmed(
+        &self,
+        message: &Message,
+        expected_acks: usize,
+        timeout_ms: i32,
+    ) -> Result<usize> {
+        let id = message.id.as_deref().ok_or_else(|| {
+            OxideError::Configuration("confirmed publishing requires a tracked message".to_string())
+        })?;
+        self.publisher.publish_filtered(message)?;
+
+        let deadline = deadline_after(timeout_ms);
+        let mut acks = 0;
+        while acks < expected_acks {
+            let Some(wait_ms) = remaining_ms(deadline) else {
+                break;
+            };
+            match recv_bytes_timeout(&self.acks, wait_ms)? {
+                Some(ack) if ack == id.as_bytes() => acks += 1,
+                Some(_) => {}
+                None => break,
+            }
+        }
+        Ok(acks)
+    }
+}
+
+/// Subscriber that acknowledges every tracked message it receives
+pub struct ConfirmedSubscriber {
+    subscriber: Subscriber,
+    acks: Socket,
+}
+
+impl ConfirmedSubscriber {
+    /// Connect to a [`ConfirmedPublisher`] at `address` and `ack_address`
+    pub fn new(address: &str, ack_address: &str) -> Result<Self> {
+        Self::from_builder(Subscriber::builder(), address, ack_address)
+    }
+
+    /// Connect a subscriber made by `builder` to `address`, and the ack
+    /// channel in the same context to `ack_address`
+    pub fn from_builder(
+        builder: SocketBuilder<Subscriber>,
+        address: &str,
+        ack_address: &str,
+    ) -> Result<Self> {
+        let context = builder.context_or_default();
+        let subscriber = builder.context(&context).connect(address)?;
+        let acks = new_socket(&context, zmq::PUSH)?;
+        acks.connect(ack_address)?;
+        Ok(Self { subscriber, acks })
+    }
+
+    /// Subscribe to messages with a specific topic prefix
+    pub fn subscribe(&self, topic: &str) -> Result<()> {
+        self.subscriber.subscribe(topic)
+    }
+
+    /// Receive a message and acknowledge it (blocking)
+    pub fn receive(&self) -> Result<Message> {
+        let message = self.subscriber.receive()?;
+        self.acknowledge(&message)?;
+        Ok(message)
+    }
+
+    /// Receive a message with timeout in milliseconds and acknowledge it
+    pub fn receive_timeout(&self, timeout_ms: i32) -> Result<Option<Message>> {
+        let message = self.subscriber.receive_timeout(timeout_ms)?;
+        if let Some(message) = &message {
+            self.acknowledge(message)?;
+        }
+        Ok(message)
+    }
+
+    fn acknowledge(&self, message: &Message) -> Result<()> {
+        if let Some(id) = &message.id {
+            self.acks.send(id.as_bytes(), 0).map_err(OxideError::Send)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_two_subscribers_acknowledge() {
+        let address = "tcp://127.0.0.1:15581";
+        let ack_address = "tcp://127.0.0.1:25581";
+
+        let publisher = ConfirmedPublisher::new(address, ack_address).unwrap();
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let subscriber = ConfirmedSubscriber::new(address, ack_address).unwrap();
+                subscriber.subscribe("alerts").unwrap();
+                thread::spawn(move || subscriber.receive_timeout(5000).unwrap())
+            })
+            .collect();
+        thread::sleep(Duration::from_millis(200));
+
+        let alert = Message::tracked("alerts.fire", json!({"level": "critical"}));
+        assert_eq!(publisher.publish_confirmed(&alert, 2, 5000).unwrap(), 2);
+
+        for handle in handles {
+            let received = handle.join().unwrap().unwrap();
+            assert_eq!(received.id, alert.id);
+        }
+    }
+
+    #[test]
+    fn test_acks_over_inproc_in_a_shared_context() {
+        let context = zmq::Context::new();
+        let publisher = ConfirmedPublisher::from_builder(
+            Publisher::builder().context(&context),
+            "inproc://confirmed",
+            "inproc://confirmed-acks",
+        )
+        .unwrap();
+        let subscriber = ConfirmedSubscriber::from_builder(
+            Subscriber::builder().context(&context),
+            "inproc://confirmed",
+            "inproc://confirmed-acks",
+        )
+        .unwrap();
+        subscriber.subscribe("jobs").unwrap();
+        let receiving = thread::spawn(move || subscriber.receive_timeout(5000).unwrap());
+
+        // Waits for as long as the ack takes
+        let job = Message::tracked("jobs", json!({"id": 3}));
+        assert_eq!(publisher.publish_confirmed(&job, 1, -1).unwrap(), 1);
+        assert_eq!(receiving.join().unwrap().unwrap().id, job.id);
+    }
+
+    #[test]
+    fn test_untracked_message_rejected() {
+        let publisher =
+            ConfirmedPublisher::new("tcp://127.0.0.1:35581", "tcp://127.0.0.1:45581").unwrap();
+        let result = publisher.publish_confirmed(&Message::new("alerts", json!({})), 1, 10);
+        assert!(matches!(result, Err(OxideError::Configuration(_))));
+    }
+}
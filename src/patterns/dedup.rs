@@ -0,0 +1,180 @@
+//! Subscriber that drops repeated messages
+//!
+//! Reconnects and redundant publishers can make PUB/SUB deliver the same
+//! message more than once. A [`DedupSubscriber`] remembers the ids of the
+//! last `window` distinct messages it has seen, evicting the least recently
+//! seen id first, and skips any message whose id is still remembered.
+//!
+//! Memory is bounded by `window` ids (36-byte UUID strings for messages
+//! from [`Message::track`]) plus map overhead, regardless of traffic.
+//! Duplicates that arrive after their id has been evicted get through.
+//! Only tracked messages carry an id, so untracked messages are never
+//! deduplicated.
+
+use crate::error::{OxideError, Result};
+use crate::message::Message;
+use crate::patterns::{deadline_after, remaining_ms, Subscriber};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+
+/// Bounded least-recently-seen set of message ids
+struct RecentIds {
+    window: usize,
+    tick: u64,
+    by_id: HashMap<String, u64>,
+    by_tick: BTreeMap<u64, String>,
+}
+
+impl RecentIds {
+    fn new(window: usize) -> Self {
+        Self {
+            window,
+            tick: 0,
+            by_id: HashMap::new(),
+            by_tick: BTreeMap::new(),
+        }
+    }
+
+    /// Mark `id` as seen, returning whether it was already remembered
+    fn seen(&mut self, id: &str) -> bool {
+        self.tick += 1;
+        if let Some(tick) = self.by_id.get_mut(id) {
+            let id = self.by_tick.remove(tick).unwrap_or_default();
+            *tick = self.tick;
+            self.by_tick.insert(self.tick, id);
+            return true;
+        }
+
+        self.by_id.insert(id.to_string(), self.tick);
+        self.by_tick.insert(self.tick, id.to_string());
+        if self.by_id.len() > self.window {
+            if let Some((_, oldest)) = self.by_tick.pop_first() {
+                self.by_id.remove(&oldest);
+            }
+        }
+        false
+    }
+}
+
+/// [`Subscriber`] that skips tracked messages it has already received
+pub struct DedupSubscriber {
+    subscriber: Subscriber,
+    recent: RefCell<RecentIds>,
+}
+
+impl DedupSubscriber {
+    /// Wrap `subscriber`, remembering up to `window` message ids
+    pub fn new(subscriber: Subscriber, window: usize) -> Result<Self> {
+        if window == 0 {
+            return Err(OxideError::Configuration(
+                "dedup window must be greater than zero".to_string(),
+            ));
+        }
+        Ok(Self {
+            subscriber,
+            recent: RefCell::new(RecentIds::new(window)),
+        })
+    }
+
+    /// The wrapped subscriber, e.g. to change subscriptions
+    pub fn subscriber(&self) -> &Subscriber {
+        &self.subscriber
+    }
+
+    /// Receive the next message not seen before (blocking)
+    pub fn receive(&self) -> Result<Message> {
+        loop {
+            let message = self.subscriber.receive()?;
+            if !self.is_duplicate(&message) {
+                return Ok(message);
+            }
+        }
+    }
+
+    /// Receive the next message not seen before, waiting at most
+    /// `timeout_ms` in total (-1 waits forever)
+    ///
+    /// Duplicates arriving meanwhile are skipped without ending the wait.
+    pub fn receive_timeout(&self, timeout_ms: i32) -> Result<Option<Message>> {
+        let deadline = deadline_after(timeout_ms);
+        loop {
+            let wait_ms = remaining_ms(deadline).unwrap_or(0);
+            match self.subscriber.receive_timeout(wait_ms)? {
+                Some(message) if self.is_duplicate(&message) => continue,
+                received => return Ok(received),
+            }
+        }
+    }
+
+    fn is_duplicate(&self, message: &Message) -> bool {
+        match &message.id {
+            Some(id) => self.recent.borrow_mut().seen(id),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestPair;
+    use serde_json::json;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_duplicates_are_skipped() {
+        let pair = TestPair::pubsub().unwrap();
+        let tracked = Message::tracked("order", json!({"id": 1}));
+        let untracked = Message::new("tick", json!(null));
+        for message in [&tracked, &tracked, &untracked, &untracked] {
+            pair.sender.publish(message).unwrap();
+        }
+
+        let subscriber = DedupSubscriber::new(pair.receiver, 8).unwrap();
+        assert_eq!(subscriber.receive().unwrap().id, tracked.id);
+        assert_eq!(subscriber.receive().unwrap().topic, "tick");
+        assert_eq!(subscriber.receive().unwrap().topic, "tick");
+        assert!(subscriber.receive_timeout(100).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_negative_timeout_waits_for_fresh_message() {
+        let pair = TestPair::pubsub().unwrap();
+        let tracked = Message::tracked("order", json!({"id": 1}));
+        let fresh = Message::tracked("order", json!({"id": 2}));
+        pair.sender.publish(&tracked).unwrap();
+        let subscriber = DedupSubscriber::new(pair.receiver, 8).unwrap();
+        assert_eq!(
+            subscriber.receive_timeout(-1).unwrap().unwrap().id,
+            tracked.id
+        );
+
+        let sender = pair.sender;
+        let publishing = {
+            let fresh = fresh.clone();
+            thread::spawn(move || {
+                sender.publish(&tracked).unwrap();
+                thread::sleep(Duration::from_millis(200));
+                sender.publish(&fresh).unwrap();
+                sender
+            })
+        };
+        let started = Instant::now();
+        let received = subscriber.receive_timeout(-1).unwrap().unwrap();
+        assert_eq!(received.id, fresh.id);
+        assert!(started.elapsed() >= Duration::from_millis(150));
+        publishing.join().unwrap();
+    }
+
+    #[test]
+    fn test_window_evicts_least_recently_seen() {
+        let mut recent = RecentIds::new(2);
+        assert!(!recent.seen("a"));
+        assert!(!recent.seen("b"));
+        assert!(recent.seen("a"));
+        assert!(!recent.seen("c"));
+        assert!(recent.seen("a"));
+        assert!(!recent.seen("b"));
+    }
+}
@@ -0,0 +1,146 @@
+//! Topic-routed handlers for received messages
+//!
+//! A [`Dispatcher`] maps topics to handlers and calls the one registered
+//! for each message's topic, the subscriber-side counterpart of the method
+//! table in [`RpcServer`](crate::jsonrpc::RpcServer). Topics match exactly;
+//! messages on other topics go to the fallback handler, or are dropped if
+//! there is none. The dispatcher does not subscribe for its topics, so the
+//! subscriber decides what arrives in the first place.
+
+use crate::error::Result;
+use crate::message::Message;
+use crate::patterns::Subscriber;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+
+type Handler = Box<dyn Fn(Message) -> Result<()>>;
+
+/// Handlers for received messages, chosen by topic
+#[derive(Default)]
+pub struct Dispatcher {
+    handlers: HashMap<String, Handler>,
+    fallback: Option<Handler>,
+}
+
+impl Dispatcher {
+    /// Create a dispatcher without handlers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Call `handler` for messages on `topic`, replacing any handler
+    /// registered for it before
+    pub fn on<F>(&mut self, topic: impl Into<String>, handler: F)
+    where
+        F: Fn(Message) -> Result<()> + 'static,
+    {
+        self.handlers.insert(topic.into(), Box::new(handler));
+    }
+
+    /// Call `handler` with the payload of messages on `topic` decoded as
+    /// `T`
+    ///
+    /// A payload that does not decode fails the dispatch with
+    /// `OxideError::Serialization` without calling the handler.
+    pub fn on_typed<T, F>(&mut self, topic: impl Into<String>, handler: F)
+    where
+        T: DeserializeOwned,
+        F: Fn(T) -> Result<()> + 'static,
+    {
+        self.on(topic, move |message: Message| {
+            handler(message.payload_as()?)
+        });
+    }
+
+    /// Call `handler` for messages on topics without a handler of their own
+    pub fn fallback<F>(&mut self, handler: F)
+    where
+        F: Fn(Message) -> Result<()> + 'static,
+    {
+        self.fallback = Some(Box::new(handler));
+    }
+
+    /// Topics with a handler, in no particular order
+    pub fn topics(&self) -> impl Iterator<Item = &str> {
+        self.handlers.keys().map(String::as_str)
+    }
+
+    /// Pass `message` to the handler for its topic and return its result
+    pub fn dispatch(&self, message: Message) -> Result<()> {
+        match self.handlers.get(&message.topic).or(self.fallback.as_ref()) {
+            Some(handler) => handler(message),
+            None => Ok(()),
+        }
+    }
+
+    /// Receive one message from `subscriber`, waiting at most `timeout_ms`,
+    /// and dispatch it
+    ///
+    /// Returns `false` if no message arrived in time.
+    pub fn handle_next(&self, subscriber: &Subscriber, timeout_ms: i32) -> Result<bool> {
+        match subscriber.receive_timeout(timeout_ms)? {
+            Some(message) => self.dispatch(message).map(|()| true),
+            None => Ok(false),
+        }
+    }
+
+    /// Receive and dispatch messages until receiving or a handler fails
+    pub fn run(&self, subscriber: &Subscriber) -> Result<()> {
+        loop {
+            self.dispatch(subscriber.receive()?)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestPair;
+    use serde_json::json;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_handlers_only_see_their_topic() {
+        let pair = TestPair::pubsub().unwrap();
+        let sensors = Rc::new(RefCell::new(Vec::new()));
+        let alarms = Rc::new(RefCell::new(Vec::new()));
+        let others = Rc::new(RefCell::new(Vec::new()));
+
+        let mut dispatcher = Dispatcher::new();
+        let seen = Rc::clone(&sensors);
+        dispatcher.on_typed("sensor", move |celsius: f64| {
+            seen.borrow_mut().push(celsius);
+            Ok(())
+        });
+        let seen = Rc::clone(&alarms);
+        dispatcher.on("alarm", move |message| {
+            seen.borrow_mut().push(message.payload);
+            Ok(())
+        });
+        let seen = Rc::clone(&others);
+        dispatcher.fallback(move |message| {
+            seen.borrow_mut().push(message.topic);
+            Ok(())
+        });
+
+        for message in [
+            Message::new("sensor", json!(21.5)),
+            Message::new("alarm", json!("smoke")),
+            Message::new("sensor", json!(22.0)),
+            Message::new("status", json!(null)),
+        ] {
+            pair.sender.publish(&message).unwrap();
+        }
+        for _ in 0..4 {
+            assert!(dispatcher.handle_next(&pair.receiver, 2000).unwrap());
+        }
+
+        assert_eq!(*sensors.borrow(), [21.5, 22.0]);
+        assert_eq!(*alarms.borrow(), [json!("smoke")]);
+        assert_eq!(*others.borrow(), ["status"]);
+        assert!(dispatcher
+            .dispatch(Message::new("sensor", json!("hot")))
+            .is_err());
+    }
+}
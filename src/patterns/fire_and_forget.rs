@@ -0,0 +1,80 @@
+//! One-way notifications to a ROUTER server
+//!
+//! A REQ socket enforces a strict send/receive cycle: after a send it
+//! refuses another (`EFSM`) until the reply has been received, so a
+//! `Requester` cannot notify a server and move on. A [`FireAndForget`]
+//! sender uses a DEALER socket instead, which has no such state machine, and
+//! frames each message like a REQ request (`["", body]`) so a [`Router`]
+//! receives it as usual.
+//!
+//! Delivery is at most once: nothing confirms a notification arrived, and
+//! notifications still queued when the connection drops are lost. Replies
+//! from the server are never read.
+//!
+//! [`Router`]: crate::patterns::Router
+
+use crate::builder::{
+    impl_affinity, impl_buffers, impl_connect_timeout, impl_frames, impl_pattern, impl_plain,
+    impl_tcp_keepalive,
+};
+use crate::endpoint::Endpoint;
+use crate::error::{OxideError, Result};
+use crate::message::Message;
+use crate::metrics::Counters;
+use zmq::Socket;
+
+/// Sender of one-way notifications over a DEALER socket
+pub struct FireAndForget {
+    socket: Socket,
+    counters: Counters,
+}
+
+impl_pattern!(FireAndForget => zmq::DEALER);
+impl_affinity!(FireAndForget);
+impl_plain!(FireAndForget);
+impl_buffers!(FireAndForget);
+impl_frames!(FireAndForget);
+impl_connect_timeout!(FireAndForget);
+impl_tcp_keepalive!(FireAndForget);
+
+impl FireAndForget {
+    /// Create a new sender that connects to the specified address
+    pub fn new(address: impl Into<Endpoint>) -> Result<Self> {
+        Self::builder().connect(address)
+    }
+
+    /// Send a notification without waiting for a reply
+    ///
+    /// Blocks only while the send high-water mark is reached.
+    pub fn notify(&self, message: &Message) -> Result<()> {
+        let body = message.to_bytes()?;
+        self.socket
+            .send_multipart([&[] as &[u8], &body], 0)
+            .map_err(OxideError::Send)?;
+        self.counters.record_sent();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patterns::Router;
+    use serde_json::json;
+
+    #[test]
+    fn test_notifications_reach_router_without_replies() {
+        let address = "tcp://127.0.0.1:15623";
+        let router = Router::new(address).unwrap();
+        let sender = FireAndForget::new(address).unwrap();
+
+        sender.notify(&Message::new("first", json!(1))).unwrap();
+        sender.notify(&Message::new("second", json!(2))).unwrap();
+
+        let first = router.recv_routed_timeout(2000).unwrap().unwrap();
+        let second = router.recv_routed_timeout(2000).unwrap().unwrap();
+        assert_eq!(first.message.topic, "first");
+        assert_eq!(second.message.topic, "second");
+        assert_eq!(first.identity, second.identity);
+    }
+}
@@ -0,0 +1,107 @@
+//! Glue from a pub/sub stream to a push/pull work queue
+//!
+//! A [`Forwarder`] subscribes to some topics of a publisher and pushes each
+//! message it receives to the pullers connected to its PUSH socket, so a
+//! broadcast stream can be spread over a pool of workers that each see a
+//! share of it instead of all of it. Messages go through unchanged.
+//!
+//! Topics filter on the first frame, so they only select messages sent
+//! with [`Publisher::publish_filtered`](crate::Publisher::publish_filtered);
+//! an empty topic forwards everything.
+
+use crate::endpoint::Endpoint;
+use crate::error::Result;
+use crate::patterns::{Pusher, Subscriber};
+use crate::stop::StopHandle;
+
+/// Subscriber whose messages are pushed on to a work queue
+pub struct Forwarder {
+    subscriber: Subscriber,
+    pusher: Pusher,
+}
+
+impl Forwarder {
+    /// Subscribe to `topics` on the publisher at `sub_addr` and bind a
+    /// pusher to `push_addr` for pullers to connect to
+    pub fn new(
+        sub_addr: impl Into<Endpoint>,
+        topics: &[&str],
+        push_addr: impl Into<Endpoint>,
+    ) -> Result<Self> {
+        let subscriber = Subscriber::new(sub_addr)?;
+        for topic in topics {
+            subscriber.subscribe(topic)?;
+        }
+        Ok(Self::from_parts(subscriber, Pusher::new_bind(push_addr)?))
+    }
+
+    /// Forward from an already configured subscriber to an existing pusher
+    pub fn from_parts(subscriber: Subscriber, pusher: Pusher) -> Self {
+        Self { subscriber, pusher }
+    }
+
+    /// Forward messages until `stop` is signalled and return how many were
+    /// forwarded
+    ///
+    /// Pushing blocks while no puller is connected or all of them are at
+    /// their high-water mark, and a stop signalled meanwhile only takes
+    /// effect once the message is sent.
+    pub fn run(&self, stop: &StopHandle) -> Result<u64> {
+        let mut forwarded = 0;
+        while let Some(message) = self.subscriber.receive_interruptible(stop)? {
+            self.pusher.push(&message)?;
+            forwarded += 1;
+        }
+        Ok(forwarded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::Message;
+    use crate::patterns::{Publisher, Puller};
+    use serde_json::json;
+    use std::thread;
+
+    #[test]
+    fn test_published_messages_reach_puller() {
+        let publisher = Publisher::new("tcp://127.0.0.1:15660").unwrap();
+        let forwarder = Forwarder::new(
+            "tcp://127.0.0.1:15660",
+            &["orders"],
+            "tcp://127.0.0.1:25660",
+        )
+        .unwrap();
+        let puller = Puller::new_connect("tcp://127.0.0.1:25660").unwrap();
+        let stop = StopHandle::new().unwrap();
+        let running = {
+            let stop = stop.clone();
+            thread::spawn(move || forwarder.run(&stop).unwrap())
+        };
+
+        // Publish until the subscription has propagated
+        let mut received = None;
+        for _ in 0..200 {
+            publisher
+                .publish_filtered(&Message::new("noise", json!("dropped")))
+                .unwrap();
+            publisher
+                .publish_filtered(&Message::new("orders", json!({"id": 7})))
+                .unwrap();
+            received = puller.pull_timeout(10).unwrap();
+            if received.is_some() {
+                break;
+            }
+        }
+        let received = received.unwrap();
+        assert_eq!(received.topic, "orders");
+        assert_eq!(received.payload, json!({"id": 7}));
+        while let Some(message) = puller.pull_timeout(50).unwrap() {
+            assert_eq!(message.topic, "orders");
+        }
+
+        stop.stop().unwrap();
+        assert!(running.join().unwrap() >= 1);
+    }
+}
@@ -0,0 +1,233 @@
+//! Liveness and readiness probes over request/reply
+//!
+//! A [`HealthServer`] answers [`HEALTH_TOPIC`] requests on its own REP
+//! socket and thread, so a service stays probeable while its main sockets
+//! are busy or stuck. The reply is a [`HealthStatus`] with the server's
+//! uptime and the answers of a readiness check and a message count
+//! callback supplied by the service.
+//! [`HealthClient`] sends the probe and decodes the status, for use in
+//! orchestration scripts and other services.
+
+use crate::endpoint::Endpoint;
+use crate::error::{OxideError, Result};
+use crate::message::Message;
+use crate::metrics::MessageCounts;
+use crate::patterns::{Replier, Requester};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Topic of health probes and their replies
+pub const HEALTH_TOPIC: &str = "__health__";
+
+/// How often the health thread checks whether it should stop
+const HEALTH_POLL_MS: i32 = 100;
+
+/// State of a service as reported by its [`HealthServer`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthStatus {
+    /// What the service's readiness check returned
+    pub ready: bool,
+    /// Milliseconds since the health server started
+    pub uptime_ms: u64,
+    /// Messages the service reports having sent and received, or `None`
+    /// when it reports none
+    pub messages: Option<MessageCounts>,
+}
+
+impl HealthStatus {
+    /// Time since the health server started
+    pub fn uptime(&self) -> Duration {
+        Duration::from_millis(self.uptime_ms)
+    }
+}
+
+/// Health endpoint answering probes on a background thread
+///
+/// Dropping the server stops it; [`HealthServer::shutdown`] also reports
+/// why it stopped.
+pub struct HealthServer {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<Result<()>>>,
+}
+
+impl HealthServer {
+    /// Bind a replier to `address` and answer probes, calling `ready` for
+    /// the readiness and `counts` for the message counts of each reply
+    ///
+    /// `counts` typically adds up the `message_counts()` of the service's
+    /// own sockets, which need the `metrics` feature; return `None` to
+    /// report none. Requests on other topics, and requests that cannot be
+    /// decoded, are answered with an `error` message.
+    pub fn spawn<F, C>(address: impl Into<Endpoint>, ready: F, counts: C) -> Result<Self>
+    where
+        F: Fn() -> bool + Send + 'static,
+        C: Fn() -> Option<MessageCounts> + Send + 'static,
+    {
+        let replier = Replier::new(address)?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || Self::serve(&replier, &stop, ready, counts))
+        };
+        Ok(Self {
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    fn serve<F, C>(replier: &Replier, stop: &AtomicBool, ready: F, counts: C) -> Result<()>
+    where
+        F: Fn() -> bool,
+        C: Fn() -> Option<MessageCounts>,
+    {
+        let started = Instant::now();
+        while !stop.load(Ordering::Relaxed) {
+            let reply = match replier.receive_timeout(HEALTH_POLL_MS) {
+                Ok(Some(request)) if request.topic == HEALTH_TOPIC => {
+                    let status = HealthStatus {
+                        ready: ready(),
+                        uptime_ms: started.elapsed().as_millis() as u64,
+                        messages: counts(),
+                    };
+                    Message::from_value(HEALTH_TOPIC, &status)?
+                }
+                Ok(Some(request)) => Message::new(
+                    "error",
+                    json!({"message": format!("not a health probe: {}", request.topic)}),
+                ),
+                Ok(None) => continue,
+                Err(OxideError::Serialization(e)) => Message::new("error", json!({"message": e})),
+                Err(e) => return Err(e),
+            };
+            replier.reply(&reply)?;
+        }
+        Ok(())
+    }
+
+    /// Stop answering probes and wait for the thread to finish
+    ///
+    /// Returns the error that stopped the server early, if there was one.
+    pub fn shutdown(mut self) -> Result<()> {
+        self.stop()
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.stop.store(true, Ordering::Relaxed);
+        match self.thread.take() {
+            Some(thread) => thread.join().unwrap_or_else(|_| {
+                Err(OxideError::Connection("health thread panicked".to_string()))
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for HealthServer {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+/// Client probing a [`HealthServer`]
+pub struct HealthClient {
+    requester: Requester,
+}
+
+impl HealthClient {
+    /// Connect to the health server at `address`
+    ///
+    /// The socket does not linger, so an unanswered probe never holds up
+    /// dropping the client.
+    pub fn new(address: impl Into<Endpoint>) -> Result<Self> {
+        let requester = Requester::new(address)?;
+        requester
+            .with_raw_socket(|socket| socket.set_linger(0))
+            .map_err(OxideError::SocketOption)?;
+        Ok(Self { requester })
+    }
+
+    /// Probe the server once, waiting at most `timeout_ms`
+    ///
+    /// Fails with `OxideError::Timeout` if no reply arrives in time, after
+    /// which the client can probe again, and `OxideError::Protocol` if the
+    /// reply is not a health status.
+    pub fn status(&mut self, timeout_ms: i32) -> Result<HealthStatus> {
+        let probe = Message::new(HEALTH_TOPIC, json!(null));
+        let Some(reply) = self.requester.request_timeout(&probe, timeout_ms)? else {
+            self.requester.reset()?;
+            return Err(OxideError::Timeout(format!(
+                "no health status after {} ms",
+                timeout_ms
+            )));
+        };
+        if reply.topic != HEALTH_TOPIC {
+            return Err(OxideError::Protocol(format!(
+                "expected a health status, got a {} reply",
+                reply.topic
+            )));
+        }
+        reply
+            .payload_as()
+            .map_err(|e| OxideError::Protocol(format!("malformed health status: {}", e)))
+    }
+
+    /// Connect to `address`, probe it once and disconnect
+    pub fn check(address: impl Into<Endpoint>, timeout_ms: i32) -> Result<HealthStatus> {
+        Self::new(address)?.status(timeout_ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU64;
+
+    #[test]
+    fn test_client_reads_status_of_running_server() {
+        let address = "tcp://127.0.0.1:15662";
+        let ready = Arc::new(AtomicBool::new(false));
+        let handled = Arc::new(AtomicU64::new(0));
+        let server = {
+            let ready = Arc::clone(&ready);
+            let handled = Arc::clone(&handled);
+            HealthServer::spawn(
+                address,
+                move || ready.load(Ordering::SeqCst),
+                move || {
+                    Some(MessageCounts {
+                        sent: 0,
+                        received: handled.load(Ordering::SeqCst),
+                    })
+                },
+            )
+            .unwrap()
+        };
+
+        let starting = HealthClient::check(address, 5000).unwrap();
+        assert!(!starting.ready);
+        assert_eq!(starting.messages.unwrap().received, 0);
+        ready.store(true, Ordering::SeqCst);
+        handled.store(5, Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(20));
+
+        let mut client = HealthClient::new(address).unwrap();
+        let status = client.status(5000).unwrap();
+        assert!(status.ready);
+        assert!(status.uptime() >= Duration::from_millis(20));
+        assert_eq!(
+            status.messages,
+            Some(MessageCounts {
+                sent: 0,
+                received: 5
+            })
+        );
+        server.shutdown().unwrap();
+
+        // Nothing is listening any more
+        assert!(matches!(client.status(100), Err(OxideError::Timeout(_))));
+    }
+}
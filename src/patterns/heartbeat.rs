@@ -0,0 +1,261 @@
+//! Heartbeated request/reply over ROUTER/DEALER
+//!
+//! The server remembers every client it has heard from and, while it is
+//! waiting for requests, sends each of them a heartbeat frame once per
+//! interval. Clients see those frames as [`Event::Heartbeat`], so a slow
+//! server (heartbeats keep arriving) can be told apart from a dead one
+//! (nothing arrives). This is application-level liveness and is independent
+//! of the ZMTP heartbeat options.
+//!
+//! Frames carry no REQ-style empty delimiter, so [`Client`] is the only
+//! supported peer of a [`HeartbeatServer`].
+
+use crate::builder::{impl_connect_timeout, impl_tcp_keepalive, new_socket};
+use crate::error::{OxideError, Result};
+use crate::message::Message;
+use crate::options::SocketOptions;
+use crate::patterns::{deadline_after, remaining_ms};
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use zmq::{Context, Socket};
+
+/// Frame sent in place of a serialized message to signal liveness
+pub const HEARTBEAT_FRAME: &[u8] = b"\x00OXIDE-HEARTBEAT";
+
+/// Something received by a heartbeat-aware client
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// A real message from the server
+    Message(Message),
+    /// A liveness signal from the server
+    Heartbeat,
+}
+
+/// Server side of the heartbeated request/reply pattern
+pub struct HeartbeatServer {
+    socket: Socket,
+    interval: Duration,
+    clients: RefCell<HashSet<Vec<u8>>>,
+    last_heartbeat: Cell<Instant>,
+}
+
+impl HeartbeatServer {
+    /// Create a new server that binds to the specified address and sends
+    /// heartbeats every `interval_ms` milliseconds while idle
+    pub fn new(address: &str, interval_ms: u64) -> Result<Self> {
+        let context = Context::new();
+        let socket = new_socket(&context, zmq::ROUTER)?;
+        socket.bind(address)?;
+        Ok(Self {
+            socket,
+            interval: Duration::from_millis(interval_ms),
+            clients: RefCell::new(HashSet::new()),
+            last_heartbeat: Cell::new(Instant::now()),
+        })
+    }
+
+    /// Receive a request (blocking), heartbeating known clients while waiting
+    ///
+    /// Returns the client identity to pass to [`HeartbeatServer::reply`].
+    pub fn receive(&self) -> Result<(Vec<u8>, Message)> {
+        loop {
+            if let Some(request) = self.receive_timeout(-1)? {
+                return Ok(request);
+            }
+        }
+    }
+
+    /// Receive a request with timeout, heartbeating known clients while waiting
+    ///
+    /// A negative timeout waits forever.
+    pub fn receive_timeout(&self, timeout_ms: i32) -> Result<Option<(Vec<u8>, Message)>> {
+        let deadline = deadline_after(timeout_ms);
+
+        loop {
+            if self.last_heartbeat.get().elapsed() >= self.interval {
+                self.send_heartbeats()?;
+            }
+
+            let until_heartbeat = self
+                .interval
+                .saturating_sub(self.last_heartbeat.get().elapsed())
+                .as_millis() as i64;
+            let wait_ms = match remaining_ms(deadline) {
+                Some(-1) => until_heartbeat,
+                Some(ms) => until_heartbeat.min(i64::from(ms)),
+                None => 0,
+            };
+
+            let ready = self
+                .socket
+                .poll(zmq::POLLIN, wait_ms)
+                .map_err(OxideError::Receive)?;
+
+            if ready > 0 {
+                let mut frames = self.socket.recv_multipart(0).map_err(OxideError::Receive)?;
+                if frames.len() != 2 {
+                    return Err(OxideError::Protocol(format!(
+                        "expected 2 frames from client, got {}",
+                        frames.len()
+                    )));
+                }
+                let body = frames.pop().unwrap_or_default();
+                let identity = frames.pop().unwrap_or_default();
+                self.clients.borrow_mut().insert(identity.clone());
+
+                // Clients announce themselves with a heartbeat frame
+                if body == HEARTBEAT_FRAME {
+                    continue;
+                }
+                return Ok(Some((identity, Message::from_bytes(&body)?)));
+            }
+
+            if remaining_ms(deadline).is_none() {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Send a reply to the client with the given identity
+    pub fn reply(&self, client: &[u8], message: &Message) -> Result<()> {
+        let bytes = message.to_bytes()?;
+        self.socket
+            .send_multipart([client, bytes.as_slice()], 0)
+            .map_err(OxideError::Send)?;
+        Ok(())
+    }
+
+    /// Send a heartbeat to every known client immediately
+    ///
+    /// Busy servers that spend long stretches outside `receive` can call
+    /// this to keep clients informed.
+    pub fn send_heartbeats(&self) -> Result<()> {
+        for client in self.clients.borrow().iter() {
+            self.socket
+                .send_multipart([client.as_slice(), HEARTBEAT_FRAME], 0)
+                .map_err(OxideError::Send)?;
+        }
+        self.last_heartbeat.set(Instant::now());
+        Ok(())
+    }
+
+    /// Read back the socket's current options
+    pub fn options(&self) -> Result<SocketOptions> {
+        SocketOptions::read(&self.socket)
+    }
+}
+
+impl_connect_timeout!(Client);
+impl_tcp_keepalive!(Client);
+
+/// Client side of the heartbeated request/reply pattern
+pub struct Client {
+    socket: Socket,
+}
+
+impl Client {
+    /// Create a new client that connects to the specified address
+    ///
+    /// The client announces itself immediately so the server starts
+    /// heartbeating it before the first request.
+    pub fn new(address: &str) -> Result<Self> {
+        let context = Context::new();
+        let socket = new_socket(&context, zmq::DEALER)?;
+        socket.connect(address)?;
+        socket.send(HEARTBEAT_FRAME, 0).map_err(OxideError::Send)?;
+        Ok(Self { socket })
+    }
+
+    /// Read back the socket's current options
+    pub fn options(&self) -> Result<SocketOptions> {
+        SocketOptions::read(&self.socket)
+    }
+
+    /// Send a request to the server
+    pub fn send(&self, message: &Message) -> Result<()> {
+        let bytes = message.to_bytes()?;
+        self.socket.send(&bytes, 0).map_err(OxideError::Send)?;
+        Ok(())
+    }
+
+    /// Receive the next event (blocking)
+    pub fn receive_event(&self) -> Result<Event> {
+        let bytes = self.socket.recv_bytes(0).map_err(OxideError::Receive)?;
+        Self::decode(&bytes)
+    }
+
+    /// Receive the next event with timeout
+    ///
+    /// A `None` means neither a reply nor a heartbeat arrived in time.
+    pub fn receive_event_timeout(&self, timeout_ms: i32) -> Result<Option<Event>> {
+        let ready = self
+            .socket
+            .poll(zmq::POLLIN, timeout_ms as i64)
+            .map_err(OxideError::Receive)?;
+        if ready == 0 {
+            return Ok(None);
+        }
+        self.receive_event().map(Some)
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Event> {
+        if bytes == HEARTBEAT_FRAME {
+            Ok(Event::Heartbeat)
+        } else {
+            Message::from_bytes(bytes).map(Event::Message)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::thread;
+
+    #[test]
+    fn test_heartbeats_between_replies() {
+        let address = "tcp://127.0.0.1:15566";
+
+        let server = HeartbeatServer::new(address, 50).unwrap();
+        let server_handle = thread::spawn(move || {
+            for _ in 0..2 {
+                let (client, request) = server.receive().unwrap();
+                let reply = Message::new("reply", request.payload);
+                server.reply(&client, &reply).unwrap();
+            }
+        });
+
+        let client = Client::new(address).unwrap();
+        client.send(&Message::new("req", json!({"n": 1}))).unwrap();
+
+        // Heartbeats may already be in flight; skip to the first reply
+        let first = loop {
+            match client.receive_event_timeout(1000).unwrap() {
+                Some(Event::Message(msg)) => break msg,
+                Some(Event::Heartbeat) => continue,
+                None => panic!("no reply from server"),
+            }
+        };
+        assert_eq!(first.payload["n"], 1);
+
+        // The server is idle now, so only heartbeats should arrive
+        for _ in 0..2 {
+            let event = client.receive_event_timeout(1000).unwrap();
+            assert!(matches!(event, Some(Event::Heartbeat)));
+        }
+
+        client.send(&Message::new("req", json!({"n": 2}))).unwrap();
+        let second = loop {
+            match client.receive_event_timeout(1000).unwrap() {
+                Some(Event::Message(msg)) => break msg,
+                Some(Event::Heartbeat) => continue,
+                None => panic!("no reply from server"),
+            }
+        };
+        assert_eq!(second.payload["n"], 2);
+
+        server_handle.join().unwrap();
+    }
+}
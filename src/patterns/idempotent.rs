@@ -0,0 +1,252 @@
+//! Request/reply with idempotency keys, so retries do not repeat side effects
+//!
+//! A requester that times out cannot tell whether its request was lost or
+//! only its reply, so retrying a request that changes state risks applying
+//! it twice. An [`IdempotentRequester`] sends every attempt of a request
+//! with the same key in the [`IDEMPOTENCY_KEY_HEADER`] header, and an
+//! [`IdempotentReplier`] remembers the reply it sent for each key: a retry
+//! gets the remembered reply and the handler does not run again.
+//!
+//! The guarantee is at-most-once execution per key for as long as the
+//! replier remembers the key: until its TTL runs out or it is evicted to
+//! stay within the cache capacity, whichever comes first. The cache lives
+//! in the replier's memory, so it does not survive a restart and is not
+//! shared between repliers. Requests without a key run every time.
+
+use crate::endpoint::Endpoint;
+use crate::error::{OxideError, Result};
+use crate::message::Message;
+use crate::patterns::{Replier, Requester};
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Header carrying the idempotency key of a request
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Requester that retries timed-out requests under one idempotency key
+pub struct IdempotentRequester {
+    requester: Requester,
+}
+
+impl IdempotentRequester {
+    /// Create a new requester that connects to the specified address
+    pub fn new(address: impl Into<Endpoint>) -> Result<Self> {
+        Ok(Self::from_requester(Requester::new(address)?))
+    }
+
+    /// Send requests through an existing requester
+    pub fn from_requester(requester: Requester) -> Self {
+        Self { requester }
+    }
+
+    /// The wrapped requester
+    pub fn requester(&self) -> &Requester {
+        &self.requester
+    }
+
+    /// Send `message` under a fresh key, see
+    /// [`IdempotentRequester::request_with_key`]
+    pub fn request(
+        &mut self,
+        message: &Message,
+        timeout_ms: i32,
+        attempts: u32,
+    ) -> Result<Message> {
+        let key = uuid::Uuid::new_v4().to_string();
+        self.request_with_key(message, &key, timeout_ms, attempts)
+    }
+
+    /// Send `message` under `key`, waiting `timeout_ms` for each of up to
+    /// `attempts` tries
+    ///
+    /// After a timeout the socket is reset and the request sent again with
+    /// the same key. Fails with `OxideError::Timeout` once every attempt
+    /// has timed out; the request may still have been executed.
+    pub fn request_with_key(
+        &mut self,
+        message: &Message,
+        key: &str,
+        timeout_ms: i32,
+        attempts: u32,
+    ) -> Result<Message> {
+        if attempts == 0 {
+            return Err(OxideError::Configuration(
+                "a request needs at least one attempt".to_string(),
+            ));
+        }
+        let keyed = message.clone().with_header(IDEMPOTENCY_KEY_HEADER, key);
+        for _ in 0..attempts {
+            if let Some(reply) = self.requester.request_timeout(&keyed, timeout_ms)? {
+                return Ok(reply);
+            }
+            self.requester.reset()?;
+        }
+        Err(OxideError::Timeout(format!(
+            "no reply to request {} after {} attempts",
+            key, attempts
+        )))
+    }
+}
+
+/// Replies remembered by key, oldest first
+struct ReplyCache {
+    ttl: Duration,
+    capacity: usize,
+    replies: HashMap<String, Message>,
+    order: VecDeque<(Instant, String)>,
+}
+
+impl ReplyCache {
+    fn get(&mut self, key: &str) -> Option<&Message> {
+        self.expire();
+        self.replies.get(key)
+    }
+
+    fn insert(&mut self, key: String, reply: Message) {
+        self.expire();
+        if self.replies.len() == self.capacity {
+            if let Some((_, oldest)) = self.order.pop_front() {
+                self.replies.remove(&oldest);
+            }
+        }
+        self.order.push_back((Instant::now(), key.clone()));
+        self.replies.insert(key, reply);
+    }
+
+    fn expire(&mut self) {
+        while let Some((stored, key)) = self.order.front() {
+            if stored.elapsed() < self.ttl {
+                break;
+            }
+            self.replies.remove(key);
+            self.order.pop_front();
+        }
+    }
+}
+
+/// Replier that answers repeated idempotency keys from a cache
+pub struct IdempotentReplier {
+    replier: Replier,
+    cache: RefCell<ReplyCache>,
+}
+
+impl IdempotentReplier {
+    /// Create a new replier that binds to the specified address and
+    /// remembers up to `capacity` replies for `ttl` each
+    pub fn new(address: impl Into<Endpoint>, ttl: Duration, capacity: usize) -> Result<Self> {
+        Self::from_replier(Replier::new(address)?, ttl, capacity)
+    }
+
+    /// Answer requests through an existing replier
+    pub fn from_replier(replier: Replier, ttl: Duration, capacity: usize) -> Result<Self> {
+        if capacity == 0 {
+            return Err(OxideError::Configuration(
+                "reply cache capacity must be greater than zero".to_string(),
+            ));
+        }
+        Ok(Self {
+            replier,
+            cache: RefCell::new(ReplyCache {
+                ttl,
+                capacity,
+                replies: HashMap::new(),
+                order: VecDeque::new(),
+            }),
+        })
+    }
+
+    /// How long a reply is remembered
+    pub fn ttl(&self) -> Duration {
+        self.cache.borrow().ttl
+    }
+
+    /// Most replies remembered at once
+    pub fn capacity(&self) -> usize {
+        self.cache.borrow().capacity
+    }
+
+    /// Replies currently remembered
+    pub fn cached(&self) -> usize {
+        let mut cache = self.cache.borrow_mut();
+        cache.expire();
+        cache.replies.len()
+    }
+
+    /// Receive one request, waiting at most `timeout_ms`, and answer it
+    ///
+    /// A request whose key has a remembered reply gets that reply;
+    /// otherwise `handler` computes the reply, which is remembered if the
+    /// request has a key. Returns `false` if no request arrived in time.
+    pub fn handle_next<F>(&self, timeout_ms: i32, handler: F) -> Result<bool>
+    where
+        F: FnOnce(Message) -> Message,
+    {
+        let Some(request) = self.replier.receive_timeout(timeout_ms)? else {
+            return Ok(false);
+        };
+        let Some(key) = request.header(IDEMPOTENCY_KEY_HEADER).map(str::to_string) else {
+            self.replier.reply(&handler(request))?;
+            return Ok(true);
+        };
+        let cached = self.cache.borrow_mut().get(&key).cloned();
+        let reply = match cached {
+            Some(reply) => reply,
+            None => {
+                let reply = handler(request);
+                self.cache.borrow_mut().insert(key, reply.clone());
+                reply
+            }
+        };
+        self.replier.reply(&reply)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_repeated_key_runs_handler_once() {
+        let address = "tcp://127.0.0.1:15659";
+        let runs = Arc::new(AtomicUsize::new(0));
+        let server = {
+            let runs = Arc::clone(&runs);
+            thread::spawn(move || {
+                let replier = IdempotentReplier::new(address, Duration::from_secs(60), 16).unwrap();
+                for _ in 0..3 {
+                    assert!(replier
+                        .handle_next(2000, |request| {
+                            let run = runs.fetch_add(1, Ordering::SeqCst);
+                            Message::new("charged", json!({"run": run, "amount": request.payload}))
+                        })
+                        .unwrap());
+                }
+                replier.cached()
+            })
+        };
+
+        let mut requester = IdempotentRequester::new(address).unwrap();
+        let charge = Message::new("charge", json!(25));
+        let first = requester
+            .request_with_key(&charge, "order-17", 2000, 1)
+            .unwrap();
+        let retry = requester
+            .request_with_key(&charge, "order-17", 2000, 1)
+            .unwrap();
+        assert_eq!(first.payload, json!({"run": 0, "amount": 25}));
+        assert_eq!(retry.payload, first.payload);
+
+        // A new key is a new request
+        let other = requester.request(&charge, 2000, 1).unwrap();
+        assert_eq!(other.payload["run"], json!(1));
+
+        assert_eq!(server.join().unwrap(), 2);
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,114 @@
+//! Request/Reply server that knows who sent each request
+//!
+//! A REP socket strips the routing envelope before handing over a request,
+//! so a `Replier` cannot tell its clients apart. An [`IdentifiedReplier`]
+//! binds a ROUTER socket instead and returns each request with a
+//! [`PeerId`] naming the connection it came from, which is enough to log,
+//! count or throttle requests per client. REQ clients talk to it unchanged.
+//!
+//! Unlike a `Replier`, it does not have to answer one request before
+//! receiving the next: replies go to whichever peer they name, in any
+//! order. A reply to a peer that has disconnected is silently dropped.
+
+use crate::endpoint::Endpoint;
+use crate::error::Result;
+use crate::message::Message;
+use crate::patterns::{RoutedMessage, Router};
+
+/// Opaque identity of the connection a request arrived on
+///
+/// ZeroMQ assigns each connection a fresh identity unless the client sets
+/// its own with `SocketBuilder::identity`, so the same client reconnecting
+/// usually gets a new `PeerId`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PeerId(Vec<u8>);
+
+impl PeerId {
+    /// Raw identity frame, for logging
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Request/Reply server over a ROUTER socket exposing peer identities
+pub struct IdentifiedReplier {
+    router: Router,
+}
+
+impl IdentifiedReplier {
+    /// Create a new replier that binds to the specified address
+    pub fn new(address: impl Into<Endpoint>) -> Result<Self> {
+        Ok(Self::from_router(Router::new(address)?))
+    }
+
+    /// Serve requests arriving on an existing router
+    pub fn from_router(router: Router) -> Self {
+        Self { router }
+    }
+
+    /// The underlying router
+    pub fn router(&self) -> &Router {
+        &self.router
+    }
+
+    /// Receive the next request and the peer that sent it (blocking)
+    pub fn receive(&self) -> Result<(PeerId, Message)> {
+        let routed = self.router.recv_routed()?;
+        Ok((PeerId(routed.identity), routed.message))
+    }
+
+    /// Receive the next request, waiting at most `timeout_ms`
+    pub fn receive_timeout(&self, timeout_ms: i32) -> Result<Option<(PeerId, Message)>> {
+        Ok(self
+            .router
+            .recv_routed_timeout(timeout_ms)?
+            .map(|routed| (PeerId(routed.identity), routed.message)))
+    }
+
+    /// Send `message` as the reply to `peer`'s request
+    pub fn reply(&self, peer: &PeerId, message: &Message) -> Result<()> {
+        self.router.send_routed(&RoutedMessage {
+            identity: peer.0.clone(),
+            message: message.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patterns::Requester;
+    use serde_json::json;
+    use std::thread;
+
+    #[test]
+    fn test_requests_carry_distinct_peer_ids() {
+        let address = "tcp://127.0.0.1:15637";
+        let replier = IdentifiedReplier::new(address).unwrap();
+        let server = thread::spawn(move || {
+            let mut peers = Vec::new();
+            for _ in 0..2 {
+                let (peer, request) = replier.receive_timeout(2000).unwrap().unwrap();
+                replier.reply(&peer, &request).unwrap();
+                peers.push(peer);
+            }
+            (replier, peers)
+        });
+
+        let clients: Vec<_> = (0..2)
+            .map(|n| {
+                thread::spawn(move || {
+                    let requester = Requester::new(address).unwrap();
+                    let reply = requester.request(&Message::new("echo", json!(n))).unwrap();
+                    assert_eq!(reply.payload, json!(n));
+                })
+            })
+            .collect();
+        for client in clients {
+            client.join().unwrap();
+        }
+
+        let (_replier, peers) = server.join().unwrap();
+        assert_ne!(peers[0], peers[1]);
+    }
+}
@@ -0,0 +1,112 @@
+//! Symmetric messaging between two peers over DEALER sockets
+//!
+//! REQ/REP makes one side the client and locks both into strict turns. Two
+//! [`Peer`]s have no roles: once one has bound and the other connected,
+//! either side sends whenever it likes and messages flow both ways at the
+//! same time. Which side binds only matters for who has to start first.
+//!
+//! There is no routing. A peer connected to a single other peer is a
+//! point-to-point link; with several connections, sends are round-robined
+//! across them and receives are fair-queued from all of them, with no way
+//! to tell or choose who is on the other end. Use a
+//! [`Router`](crate::patterns::Router) when that matters.
+
+use crate::builder::{
+    impl_affinity, impl_buffers, impl_connect_timeout, impl_frames, impl_pattern, impl_plain,
+    impl_tcp_keepalive,
+};
+use crate::endpoint::Endpoint;
+use crate::error::{OxideError, Result};
+use crate::message::Message;
+use crate::metrics::Counters;
+use crate::patterns::recv_bytes_timeout;
+use zmq::Socket;
+
+/// One end of a full-duplex link to another peer
+pub struct Peer {
+    socket: Socket,
+    counters: Counters,
+}
+
+impl_pattern!(Peer => zmq::DEALER);
+impl_affinity!(Peer);
+impl_plain!(Peer);
+impl_buffers!(Peer);
+impl_frames!(Peer);
+impl_connect_timeout!(Peer);
+impl_tcp_keepalive!(Peer);
+
+impl Peer {
+    /// Create a peer that binds to the specified address and waits for the
+    /// other peer to connect
+    pub fn bind(address: impl Into<Endpoint>) -> Result<Self> {
+        Self::builder().bind(address)
+    }
+
+    /// Create a peer that connects to a peer bound at the specified address
+    pub fn connect(address: impl Into<Endpoint>) -> Result<Self> {
+        Self::builder().connect(address)
+    }
+
+    /// Send a message to the other peer
+    ///
+    /// Queues the message without waiting for the other side, blocking
+    /// only while the send high-water mark is reached. Messages sent
+    /// before the other peer is connected wait in the queue.
+    pub fn send(&self, message: &Message) -> Result<()> {
+        let bytes = message.to_bytes()?;
+        self.socket.send(&bytes, 0).map_err(OxideError::Send)?;
+        self.counters.record_sent();
+        Ok(())
+    }
+
+    /// Receive the next message from the other peer (blocking)
+    pub fn receive(&self) -> Result<Message> {
+        let bytes = self.socket.recv_bytes(0).map_err(OxideError::Receive)?;
+        self.counters.record_received();
+        Message::from_bytes(&bytes)
+    }
+
+    /// Receive the next message, waiting at most `timeout_ms`
+    pub fn receive_timeout(&self, timeout_ms: i32) -> Result<Option<Message>> {
+        match recv_bytes_timeout(&self.socket, timeout_ms)? {
+            Some(bytes) => {
+                self.counters.record_received();
+                Message::from_bytes(&bytes).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_peers_exchange_messages_both_ways() {
+        let context = zmq::Context::new();
+        let endpoint = "inproc://peer-chat";
+        let left = Peer::builder().context(&context).bind(endpoint).unwrap();
+        let right = Peer::builder().context(&context).connect(endpoint).unwrap();
+
+        // Neither side waits for the other before sending again
+        for n in 0..3 {
+            left.send(&Message::new("from left", json!(n))).unwrap();
+        }
+        right
+            .send(&Message::new("from right", json!("hi")))
+            .unwrap();
+
+        let greeting = left.receive_timeout(1000).unwrap().unwrap();
+        assert_eq!(greeting.topic, "from right");
+        assert_eq!(greeting.payload, json!("hi"));
+        for n in 0..3 {
+            let received = right.receive().unwrap();
+            assert_eq!(received.topic, "from left");
+            assert_eq!(received.payload, json!(n));
+        }
+        assert!(left.receive_timeout(50).unwrap().is_none());
+    }
+}
@@ -1,101 +1,398 @@
 //! Push/Pull (pipeline) messaging pattern
 
+use crate::builder::{
+    impl_affinity, impl_bind, impl_buffers, impl_connect_timeout, impl_frames, impl_pattern,
+    impl_plain, impl_tcp_keepalive,
+};
+use crate::endpoint::Endpoint;
 use crate::error::{OxideError, Result};
 use crate::message::Message;
-use zmq::{Context, Socket};
+use crate::metrics::Counters;
+use crate::monitor::SocketMonitor;
+use crate::patterns::{deadline_after, recv_bytes_timeout, remaining_ms, send_bytes_timeout};
+#[cfg(feature = "signals")]
+use crate::signals::{ShutdownToken, SHUTDOWN_POLL_MS};
+use serde::Serialize;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use zmq::Socket;
+
+/// Sequenced messages [`Puller::pull_ordered`] holds back by default while
+/// waiting for a missing one
+pub const DEFAULT_REORDER_WINDOW: usize = 64;
 
 /// Pusher for the push/pull pattern (sends tasks to workers)
 pub struct Pusher {
     socket: Socket,
+    monitor: Option<SocketMonitor>,
+    counters: Counters,
+    next_sequence: Cell<u64>,
 }
 
 impl Pusher {
     /// Create a new pusher that binds to the specified address
-    pub fn new_bind(address: &str) -> Result<Self> {
-        let context = Context::new();
-        let socket = context.socket(zmq::PUSH)?;
-        socket.bind(address)?;
-        Ok(Self { socket })
+    pub fn new_bind(address: impl Into<Endpoint>) -> Result<Self> {
+        Self::builder().bind(address)
     }
 
     /// Create a new pusher that connects to the specified address
-    pub fn new_connect(address: &str) -> Result<Self> {
-        let context = Context::new();
-        let socket = context.socket(zmq::PUSH)?;
-        socket.connect(address)?;
-        Ok(Self { socket })
+    pub fn new_connect(address: impl Into<Endpoint>) -> Result<Self> {
+        Self::builder().connect(address)
+    }
+
+    /// Create a new pusher with a socket monitor that binds to the
+    /// specified address, see [`Pusher::with_monitor`]
+    pub fn new_bind_monitored(address: impl Into<Endpoint>) -> Result<Self> {
+        let (pusher, monitor) = Self::builder().bind_monitored(address)?;
+        Ok(pusher.with_monitor(monitor))
+    }
+
+    /// Create a new pusher with a socket monitor that connects to the
+    /// specified address, see [`Pusher::with_monitor`]
+    pub fn new_connect_monitored(address: impl Into<Endpoint>) -> Result<Self> {
+        let (pusher, monitor) = Self::builder().connect_monitored(address)?;
+        Ok(pusher.with_monitor(monitor))
+    }
+
+    /// Create a new pusher that connects to every address in `addresses`
+    ///
+    /// ZeroMQ round-robins pushed messages across the peers, but only among
+    /// those currently connected: messages pushed before a peer finishes
+    /// connecting go to the others. Use [`Pusher::connect_many_monitored`]
+    /// to be able to wait for the full set when an even spread matters.
+    pub fn connect_many(addresses: &[&str]) -> Result<Self> {
+        Self::builder().open()?.connect_all(addresses)
+    }
+
+    /// Like [`Pusher::connect_many`], with a socket monitor attached
+    /// before connecting so [`Pusher::peer_count`] sees every connection
+    pub fn connect_many_monitored(addresses: &[&str]) -> Result<Self> {
+        let (pusher, monitor) = Self::builder().open_monitored()?;
+        pusher.with_monitor(monitor).connect_all(addresses)
+    }
+
+    fn connect_all(self, addresses: &[&str]) -> Result<Self> {
+        for address in addresses {
+            let endpoint = Endpoint::parse(address)?;
+            self.socket.connect(endpoint.as_str())?;
+        }
+        Ok(self)
+    }
+
+    /// Track connected pullers with `monitor`, which must watch this
+    /// pusher's socket, to enable [`Pusher::peer_count`] and
+    /// [`Pusher::is_peer_alive`]
+    ///
+    /// Monitoring costs an extra inproc socket, so pushers are built
+    /// without it. Attach the monitor from [`SocketBuilder::bind_monitored`]
+    /// or [`SocketBuilder::connect_monitored`] so no connection is missed.
+    ///
+    /// [`SocketBuilder::bind_monitored`]: crate::SocketBuilder::bind_monitored
+    /// [`SocketBuilder::connect_monitored`]: crate::SocketBuilder::connect_monitored
+    pub fn with_monitor(mut self, monitor: SocketMonitor) -> Self {
+        self.monitor = Some(monitor);
+        self
+    }
+
+    fn monitor(&self) -> Result<&SocketMonitor> {
+        self.monitor.as_ref().ok_or_else(|| {
+            OxideError::Configuration(
+                "pusher has no socket monitor, see Pusher::with_monitor".to_string(),
+            )
+        })
+    }
+
+    /// Push a message encoded as CBOR (requires the `cbor` feature)
+    #[cfg(feature = "cbor")]
+    pub fn push_cbor(&self, message: &Message) -> Result<()> {
+        let bytes = message.to_cbor()?;
+        self.socket.send(&bytes, 0).map_err(OxideError::Send)?;
+        self.counters.record_sent();
+        Ok(())
+    }
+
+    /// Best-effort number of connected pullers, based on socket monitor events
+    ///
+    /// Fails with `OxideError::Configuration` unless the pusher was made
+    /// with a monitor, see [`Pusher::with_monitor`].
+    pub fn peer_count(&self) -> Result<usize> {
+        self.monitor()?.peer_count()
+    }
+
+    /// Whether at least one puller is connected, waiting up to `timeout_ms`
+    /// (-1 waits forever) for one to connect if none is
+    ///
+    /// Best effort, like [`Pusher::peer_count`]: a puller that died without
+    /// closing its connection, or whose disconnect event has not arrived
+    /// yet, still counts as alive, and a live puller may be too busy to
+    /// take more messages. Check before pushing into a dead link, not as a
+    /// delivery guarantee. Needs a monitor, like [`Pusher::peer_count`].
+    pub fn is_peer_alive(&self, timeout_ms: i32) -> Result<bool> {
+        let monitor = self.monitor()?;
+        let deadline = deadline_after(timeout_ms);
+        loop {
+            if monitor.peer_count()? > 0 {
+                return Ok(true);
+            }
+            let Some(wait_ms) = remaining_ms(deadline) else {
+                return Ok(false);
+            };
+            monitor.next_event_timeout(wait_ms)?;
+        }
     }
 
     /// Push a message to workers
     pub fn push(&self, message: &Message) -> Result<()> {
+        let bytes = message.to_bytes()?;
+        self.socket.send(&bytes, 0).map_err(OxideError::Send)?;
+        self.counters.record_sent();
+        Ok(())
+    }
+
+    /// Push a message, waiting at most `timeout_ms` for a puller to take it
+    ///
+    /// Returns `false` if the send could not complete in time, because no
+    /// puller is connected or all of them are at their high-water mark;
+    /// the message was then not sent. The socket's previous send timeout
+    /// is put back before returning.
+    pub fn push_timeout(&self, message: &Message, timeout_ms: i32) -> Result<bool> {
+        let bytes = message.to_bytes()?;
+        match send_bytes_timeout(&self.socket, &bytes, timeout_ms) {
+            Ok(()) => {
+                self.counters.record_sent();
+                Ok(true)
+            }
+            Err(OxideError::Send(zmq::Error::EAGAIN)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Push `value` as the payload of a message on `topic`
+    ///
+    /// Fails with `OxideError::Serialization`, without sending, if `value`
+    /// cannot be represented as JSON.
+    pub fn push_value<T: Serialize>(&self, topic: &str, value: &T) -> Result<()> {
+        self.push(&Message::from_value(topic, value)?)
+    }
+
+    /// Push a message stamped with the next sequence number, starting at 0
+    ///
+    /// Returns the sequence number used. A failed push does not use one up,
+    /// so a [`Puller::pull_ordered`] receiver sees no gap for it.
+    pub fn push_sequenced(&self, message: &Message) -> Result<u64> {
+        let sequence = self.next_sequence.get();
+        let mut message = message.clone();
+        message.sequence = Some(sequence);
+        self.push(&message)?;
+        self.next_sequence.set(sequence + 1);
+        Ok(sequence)
+    }
+
+    /// Push a message without blocking
+    ///
+    /// Fails with `OxideError::Send(zmq::Error::EAGAIN)` when no puller is
+    /// connected or all of them are at their high-water mark.
+    pub fn try_push(&self, message: &Message) -> Result<()> {
         let bytes = message.to_bytes()?;
         self.socket
-            .send(&bytes, 0)
-            .map_err(|e| OxideError::Send(e.to_string()))?;
+            .send(&bytes, zmq::DONTWAIT)
+            .map_err(OxideError::Send)?;
+        self.counters.record_sent();
         Ok(())
     }
 }
 
+impl_pattern!(Pusher { monitor, next_sequence } => zmq::PUSH);
+impl_pattern!(Puller { reorder } => zmq::PULL);
+impl_affinity!(Pusher, Puller);
+impl_plain!(Pusher, Puller);
+impl_buffers!(Pusher, Puller);
+impl_bind!(Pusher, Puller);
+impl_frames!(Puller);
+impl_connect_timeout!(Pusher, Puller);
+impl_tcp_keepalive!(Pusher, Puller);
+
 /// Puller for the push/pull pattern (receives tasks from pushers)
 pub struct Puller {
     socket: Socket,
+    counters: Counters,
+    reorder: RefCell<Reorder>,
+}
+
+/// Sequenced messages held back by [`Puller::pull_ordered`]
+struct Reorder {
+    next: u64,
+    window: usize,
+    held: BTreeMap<u64, Message>,
+}
+
+impl Default for Reorder {
+    fn default() -> Self {
+        Self {
+            next: 0,
+            window: DEFAULT_REORDER_WINDOW,
+            held: BTreeMap::new(),
+        }
+    }
+}
+
+impl Reorder {
+    /// Hold a message until its turn, dropping it if its turn has passed
+    fn hold(&mut self, sequence: u64, message: Message) {
+        if sequence >= self.next {
+            self.held.insert(sequence, message);
+        }
+    }
+
+    /// The next message in sequence, or a gap error once the window is full
+    /// without it
+    fn pop(&mut self) -> Result<Option<Message>> {
+        let Some(&lowest) = self.held.keys().next() else {
+            return Ok(None);
+        };
+        if lowest == self.next {
+            self.next += 1;
+            return Ok(self.held.remove(&lowest));
+        }
+        if self.held.len() < self.window {
+            return Ok(None);
+        }
+        let first = self.next;
+        self.next = lowest;
+        Err(OxideError::SequenceGap {
+            first,
+            last: self.next - 1,
+        })
+    }
 }
 
 impl Puller {
     /// Create a new puller that binds to the specified address
-    pub fn new_bind(address: &str) -> Result<Self> {
-        let context = Context::new();
-        let socket = context.socket(zmq::PULL)?;
-        socket.bind(address)?;
-        Ok(Self { socket })
+    pub fn new_bind(address: impl Into<Endpoint>) -> Result<Self> {
+        Self::builder().bind(address)
     }
 
     /// Create a new puller that connects to the specified address
-    pub fn new_connect(address: &str) -> Result<Self> {
-        let context = Context::new();
-        let socket = context.socket(zmq::PULL)?;
-        socket.connect(address)?;
-        Ok(Self { socket })
+    pub fn new_connect(address: impl Into<Endpoint>) -> Result<Self> {
+        Self::builder().connect(address)
     }
 
     /// Pull a message (blocking)
     pub fn pull(&self) -> Result<Message> {
-        let bytes = self
-            .socket
-            .recv_bytes(0)
-            .map_err(|e| OxideError::Receive(e.to_string()))?;
+        let bytes = self.socket.recv_bytes(0).map_err(OxideError::Receive)?;
+        self.counters.record_received();
         Message::from_bytes(&bytes)
     }
 
+    /// Pull a CBOR-encoded message (blocking, requires the `cbor` feature)
+    #[cfg(feature = "cbor")]
+    pub fn pull_cbor(&self) -> Result<Message> {
+        let bytes = self.socket.recv_bytes(0).map_err(OxideError::Receive)?;
+        self.counters.record_received();
+        Message::from_cbor(&bytes)
+    }
+
     /// Pull a message with timeout
+    ///
+    /// The timeout only applies to this call, so a later [`Puller::pull`]
+    /// blocks as usual (it used to inherit the timeout).
     pub fn pull_timeout(&self, timeout_ms: i32) -> Result<Option<Message>> {
-        self.socket
-            .set_rcvtimeo(timeout_ms)
-            .map_err(|e| OxideError::Configuration(e.to_string()))?;
+        match recv_bytes_timeout(&self.socket, timeout_ms)? {
+            Some(bytes) => {
+                self.counters.record_received();
+                Ok(Some(Message::from_bytes(&bytes)?))
+            }
+            None => Ok(None),
+        }
+    }
 
-        match self.socket.recv_bytes(0) {
-            Ok(bytes) => Ok(Some(Message::from_bytes(&bytes)?)),
-            Err(zmq::Error::EAGAIN) => Ok(None),
-            Err(e) => Err(OxideError::Receive(e.to_string())),
+    /// Pull the next message in sequence order, waiting at most
+    /// `timeout_ms` (-1 waits forever)
+    ///
+    /// Messages sent with [`Pusher::push_sequenced`] are held back until
+    /// every earlier sequence number has been returned; messages without a
+    /// sequence number are returned as they arrive. At most the reorder
+    /// window's worth of messages (see [`Puller::set_reorder_window`]) are
+    /// held. When the window fills while a sequence number is still
+    /// missing, the missing numbers are given up on: this call fails with
+    /// `OxideError::SequenceGap` and the next one continues after the gap.
+    /// A message arriving after its turn has passed is dropped.
+    ///
+    /// Sequences start at 0 for each pusher, so this suits a single pusher
+    /// for the lifetime of the puller. A puller joining a running stream
+    /// reports the messages it missed as one gap, and a restarted pusher's
+    /// messages are dropped until its numbers catch up.
+    pub fn pull_ordered(&self, timeout_ms: i32) -> Result<Option<Message>> {
+        let deadline = deadline_after(timeout_ms);
+        loop {
+            if let Some(message) = self.reorder.borrow_mut().pop()? {
+                return Ok(Some(message));
+            }
+            let wait_ms = remaining_ms(deadline).unwrap_or(0);
+            let Some(message) = self.pull_timeout(wait_ms)? else {
+                return Ok(None);
+            };
+            match message.sequence {
+                Some(sequence) => self.reorder.borrow_mut().hold(sequence, message),
+                None => return Ok(Some(message)),
+            }
+        }
+    }
+
+    /// Hold back at most `window` messages in [`Puller::pull_ordered`]
+    /// (default [`DEFAULT_REORDER_WINDOW`])
+    pub fn set_reorder_window(&self, window: usize) -> Result<()> {
+        if window == 0 {
+            return Err(OxideError::Configuration(
+                "reorder window must be at least one message".to_string(),
+            ));
         }
+        self.reorder.borrow_mut().window = window;
+        Ok(())
     }
 
     /// Try to pull a message without blocking
     pub fn try_pull(&self) -> Result<Option<Message>> {
         match self.socket.recv_bytes(zmq::DONTWAIT) {
-            Ok(bytes) => Ok(Some(Message::from_bytes(&bytes)?)),
+            Ok(bytes) => {
+                self.counters.record_received();
+                Ok(Some(Message::from_bytes(&bytes)?))
+            }
             Err(zmq::Error::EAGAIN) => Ok(None),
-            Err(e) => Err(OxideError::Receive(e.to_string())),
+            Err(e) => Err(OxideError::Receive(e)),
         }
     }
 }
 
+#[cfg(feature = "signals")]
+impl Puller {
+    /// Iterate over incoming tasks until `token` is triggered
+    ///
+    /// The token is checked every [`SHUTDOWN_POLL_MS`] milliseconds, so the
+    /// iterator ends promptly even when no tasks arrive.
+    pub fn iter_until<'a>(
+        &'a self,
+        token: &'a ShutdownToken,
+    ) -> impl Iterator<Item = Result<Message>> + 'a {
+        std::iter::from_fn(move || loop {
+            if token.is_shutdown() {
+                return None;
+            }
+            match self.pull_timeout(SHUTDOWN_POLL_MS) {
+                Ok(Some(msg)) => return Some(Ok(msg)),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use serde_json::json;
     use std::thread;
-    use std::time::Duration;
+    use std::time::{Duration, Instant};
 
     #[test]
     fn test_pipeline_basic() {
@@ -124,4 +421,246 @@ mod tests {
 
         worker_handle.join().unwrap();
     }
+
+    #[test]
+    fn test_connect_many_round_robin() {
+        let addresses = ["tcp://127.0.0.1:15571", "tcp://127.0.0.1:25571"];
+        let pullers = [
+            Puller::new_bind(addresses[0]).unwrap(),
+            Puller::new_bind(addresses[1]).unwrap(),
+        ];
+
+        let pusher = Pusher::connect_many_monitored(&addresses).unwrap();
+        for _ in 0..200 {
+            if pusher.peer_count().unwrap() == 2 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(pusher.peer_count().unwrap(), 2);
+
+        for id in 0..4 {
+            pusher
+                .push(&Message::new("task", json!({"id": id})))
+                .unwrap();
+        }
+
+        for puller in &pullers {
+            for _ in 0..2 {
+                assert!(puller.pull_timeout(1000).unwrap().is_some());
+            }
+            assert!(puller.pull_timeout(100).unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn test_timeout_does_not_persist() {
+        let address = "tcp://127.0.0.1:15572";
+
+        let puller = Puller::new_bind(address).unwrap();
+        assert!(puller.pull_timeout(50).unwrap().is_none());
+
+        // Push well after the earlier 50ms timeout would have expired
+        let pusher_handle = thread::spawn(move || {
+            let pusher = Pusher::new_connect(address).unwrap();
+            thread::sleep(Duration::from_millis(300));
+            pusher.push(&Message::new("late", json!({}))).unwrap();
+        });
+
+        let msg = puller.pull().unwrap();
+        assert_eq!(msg.topic, "late");
+
+        pusher_handle.join().unwrap();
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_pipeline_cbor() {
+        let address = "tcp://127.0.0.1:15575";
+
+        let puller = Puller::new_bind(address).unwrap();
+        let pusher = Pusher::new_connect(address).unwrap();
+
+        let task = Message::new("task", json!({"id": 3, "weights": [0.5, 0.25]}));
+        pusher.push_cbor(&task).unwrap();
+
+        let received = puller.pull_cbor().unwrap();
+        assert_eq!(received.topic, task.topic);
+        assert_eq!(received.payload, task.payload);
+    }
+
+    #[test]
+    fn test_push_value_sends_struct_payload() {
+        #[derive(serde::Serialize)]
+        struct Job {
+            id: u32,
+            input: &'static str,
+        }
+
+        let pair = crate::test_support::TestPair::pipeline().unwrap();
+        pair.sender
+            .push_value("jobs", &Job { id: 7, input: "x" })
+            .unwrap();
+        let pulled = pair.receiver.pull_timeout(1000).unwrap().unwrap();
+        assert_eq!(pulled.payload, json!({"id": 7, "input": "x"}));
+
+        // JSON object keys must be strings
+        let unencodable = std::collections::HashMap::from([((1, 2), 3)]);
+        assert!(matches!(
+            pair.sender.push_value("jobs", &unencodable),
+            Err(OxideError::Serialization(_))
+        ));
+        assert!(pair.receiver.pull_timeout(50).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_try_push_reports_eagain() {
+        let pusher = Pusher::new_bind("tcp://127.0.0.1:15580").unwrap();
+
+        match pusher.try_push(&Message::new("task", json!({}))) {
+            Err(OxideError::Send(zmq::Error::EAGAIN)) => {}
+            other => panic!("expected EAGAIN send error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_push_timeout_gives_up_at_full_hwm() {
+        // Over inproc the queues are all there is: no kernel buffer drains them
+        let context = zmq::Context::new();
+        let address = "inproc://push-timeout";
+        let pusher = Pusher::builder()
+            .context(&context)
+            .sndhwm(1)
+            .bind(address)
+            .unwrap();
+        pusher
+            .with_raw_socket(|socket| socket.set_linger(0))
+            .unwrap();
+        let puller = Puller::builder()
+            .context(&context)
+            .rcvhwm(1)
+            .connect(address)
+            .unwrap();
+        assert!(pusher
+            .push_timeout(&Message::new("task", json!(0)), 2000)
+            .unwrap());
+        assert!(puller.pull_timeout(2000).unwrap().is_some());
+
+        // The puller stops reading: fill the queues on both ends, waiting
+        // for the puller's read credit from the first message to land
+        while pusher.try_push(&Message::new("task", json!(1))).is_ok() {}
+        thread::sleep(Duration::from_millis(50));
+        while pusher.try_push(&Message::new("task", json!(1))).is_ok() {}
+        let started = Instant::now();
+        assert!(!pusher
+            .push_timeout(&Message::new("task", json!(2)), 100)
+            .unwrap());
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(
+            pusher
+                .with_raw_socket(|socket| socket.get_sndtimeo())
+                .unwrap(),
+            -1
+        );
+    }
+
+    #[test]
+    fn test_has_more_marks_frame_boundary() {
+        let address = "tcp://127.0.0.1:15601";
+        let puller = Puller::new_bind(address).unwrap();
+        assert!(!puller.has_more().unwrap());
+
+        let context = zmq::Context::new();
+        let raw = context.socket(zmq::PUSH).unwrap();
+        raw.connect(address).unwrap();
+        raw.send_multipart(["header", "body", "trailer"], 0)
+            .unwrap();
+        raw.send("next", 0).unwrap();
+
+        let mut frames = vec![puller.recv_frame().unwrap()];
+        while puller.has_more().unwrap() {
+            frames.push(puller.recv_frame().unwrap());
+        }
+        assert_eq!(
+            frames,
+            [b"header".to_vec(), b"body".to_vec(), b"trailer".to_vec()]
+        );
+
+        assert_eq!(puller.recv_frame().unwrap(), b"next");
+        assert!(!puller.has_more().unwrap());
+    }
+
+    #[test]
+    fn test_is_peer_alive_follows_puller() {
+        let address = "tcp://127.0.0.1:15616";
+        assert!(matches!(
+            Pusher::new_bind(address).unwrap().is_peer_alive(0),
+            Err(OxideError::Configuration(_))
+        ));
+        let pusher = Pusher::new_bind_monitored(address).unwrap();
+        assert!(!pusher.is_peer_alive(0).unwrap());
+
+        let puller = Puller::new_connect(address).unwrap();
+        assert!(pusher.is_peer_alive(2000).unwrap());
+
+        drop(puller);
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while pusher.is_peer_alive(0).unwrap() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(!pusher.is_peer_alive(0).unwrap());
+    }
+
+    #[test]
+    fn test_pull_ordered_restores_shuffled_sequence() {
+        let upstream = "tcp://127.0.0.1:15625";
+        let downstream = "tcp://127.0.0.1:25625";
+        let pusher = Pusher::new_bind(upstream).unwrap();
+        let harness_in = Puller::new_connect(upstream).unwrap();
+        let harness_out = Pusher::new_bind_monitored(downstream).unwrap();
+        let puller = Puller::new_connect(downstream).unwrap();
+        assert!(harness_out.is_peer_alive(2000).unwrap());
+
+        for i in 0..6 {
+            assert_eq!(
+                pusher
+                    .push_sequenced(&Message::new("task", json!(i)))
+                    .unwrap(),
+                i
+            );
+        }
+        let sequenced: Vec<Message> = (0..6)
+            .map(|_| harness_in.pull_timeout(2000).unwrap().unwrap())
+            .collect();
+        // Deliver in a fixed shuffled order, as after a reconnect
+        for i in [3, 0, 5, 1, 4, 2] {
+            harness_out.push(&sequenced[i]).unwrap();
+        }
+
+        let payloads: Vec<_> = (0..6)
+            .map(|_| puller.pull_ordered(2000).unwrap().unwrap().payload)
+            .collect();
+        assert_eq!(payloads, (0..6).map(|i| json!(i)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_reorder_skips_gap_once_window_is_full() {
+        let mut reorder = Reorder {
+            window: 2,
+            ..Reorder::default()
+        };
+        for sequence in [0, 2, 3] {
+            reorder.hold(sequence, Message::new("task", json!(sequence)));
+        }
+
+        assert_eq!(reorder.pop().unwrap().unwrap().payload, json!(0));
+        assert!(matches!(
+            reorder.pop(),
+            Err(OxideError::SequenceGap { first: 1, last: 1 })
+        ));
+        assert_eq!(reorder.pop().unwrap().unwrap().payload, json!(2));
+        reorder.hold(1, Message::new("late", json!(1)));
+        assert_eq!(reorder.pop().unwrap().unwrap().payload, json!(3));
+        assert!(reorder.pop().unwrap().is_none());
+    }
 }
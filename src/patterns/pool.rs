@@ -0,0 +1,156 @@
+//! Pool of connected requesters for one endpoint
+//!
+//! A REQ socket handles one request at a time and cannot be shared between
+//! threads, so a [`RequesterPool`] keeps several connected [`Requester`]s
+//! and lends each to one caller at a time. Size the pool to the number of
+//! requests you expect to have in flight at once: callers beyond that wait
+//! in [`RequesterPool::get`], and every requester holds a TCP connection to
+//! the server.
+
+use crate::error::Result;
+use crate::patterns::Requester;
+use std::ops::Deref;
+use std::sync::{Condvar, Mutex, MutexGuard};
+
+/// Fixed-size pool of requesters connected to the same address
+pub struct RequesterPool {
+    address: String,
+    size: usize,
+    idle: Mutex<Vec<Requester>>,
+    returned: Condvar,
+}
+
+impl RequesterPool {
+    /// Connect `size` requesters to `address`
+    pub fn new(address: &str, size: usize) -> Result<Self> {
+        let idle = (0..size)
+            .map(|_| Requester::new(address))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            address: address.to_string(),
+            size,
+            idle: Mutex::new(idle),
+            returned: Condvar::new(),
+        })
+    }
+
+    /// Borrow a requester, waiting until one is free
+    ///
+    /// The requester goes back to the pool when the guard is dropped.
+    pub fn get(&self) -> PooledRequester<'_> {
+        let mut idle = self.lock_idle();
+        loop {
+            if let Some(requester) = idle.pop() {
+                return PooledRequester {
+                    pool: self,
+                    requester: Some(requester),
+                };
+            }
+            idle = self
+                .returned
+                .wait(idle)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+    }
+
+    /// Number of requesters owned by the pool
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Number of requesters not currently lent out
+    pub fn available(&self) -> usize {
+        self.lock_idle().len()
+    }
+
+    fn lock_idle(&self) -> MutexGuard<'_, Vec<Requester>> {
+        // A panic while holding the lock cannot leave the Vec inconsistent
+        self.idle
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn put_back(&self, requester: Requester) {
+        self.lock_idle().push(requester);
+        self.returned.notify_one();
+    }
+}
+
+/// A requester borrowed from a [`RequesterPool`]
+pub struct PooledRequester<'a> {
+    pool: &'a RequesterPool,
+    requester: Option<Requester>,
+}
+
+impl PooledRequester<'_> {
+    /// Replace the borrowed requester with a freshly connected one
+    ///
+    /// Use this after a request timed out: the REQ socket is still waiting
+    /// for that reply and would reject the next request. If reconnecting
+    /// fails the old requester is returned to the pool unchanged.
+    pub fn discard(mut self) -> Result<()> {
+        let fresh = Requester::new(&self.pool.address)?;
+        self.requester = Some(fresh);
+        Ok(())
+    }
+}
+
+impl Deref for PooledRequester<'_> {
+    type Target = Requester;
+
+    fn deref(&self) -> &Requester {
+        self.requester
+            .as_ref()
+            .expect("pooled requester is present until drop")
+    }
+}
+
+impl Drop for PooledRequester<'_> {
+    fn drop(&mut self) {
+        if let Some(requester) = self.requester.take() {
+            self.pool.put_back(requester);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::Message;
+    use crate::patterns::Replier;
+    use serde_json::json;
+    use std::thread;
+
+    #[test]
+    fn test_pool_reuses_requesters() {
+        let address = "tcp://127.0.0.1:15585";
+        let requests = 12;
+
+        let server = thread::spawn(move || {
+            let replier = Replier::new(address).unwrap();
+            for _ in 0..requests {
+                let request = replier.receive().unwrap();
+                replier.reply(&request).unwrap();
+            }
+        });
+
+        let pool = RequesterPool::new(address, 3).unwrap();
+        thread::scope(|scope| {
+            for n in 0..requests {
+                let pool = &pool;
+                scope.spawn(move || {
+                    let requester = pool.get();
+                    let reply = requester
+                        .request_timeout(&Message::new("echo", json!(n)), 5000)
+                        .unwrap()
+                        .unwrap();
+                    assert_eq!(reply.payload, json!(n));
+                });
+            }
+        });
+
+        server.join().unwrap();
+        assert_eq!(pool.size(), 3);
+        assert_eq!(pool.available(), 3);
+    }
+}
@@ -0,0 +1,105 @@
+//! Publish urgent messages ahead of a backlog
+//!
+//! ZeroMQ sends messages in the order they were handed to the socket. A
+//! [`PriorityPublisher`] holds messages back in one queue per
+//! [`Message::priority`] and, on [`PriorityPublisher::flush`], publishes
+//! the highest priority first, oldest first within a priority.
+//!
+//! Priorities only reorder the messages waiting in the publisher between
+//! flushes. Once a message is in ZeroMQ's own queues it is delivered in
+//! order behind everything published before it, so a backlog that has
+//! already reached the socket, or a slow subscriber, still delays urgent
+//! messages. Flush often enough that the waiting queues stay short.
+
+use crate::endpoint::Endpoint;
+use crate::error::Result;
+use crate::message::Message;
+use crate::patterns::Publisher;
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, VecDeque};
+
+/// Publisher that sends queued messages in priority order
+pub struct PriorityPublisher {
+    publisher: Publisher,
+    queues: RefCell<BTreeMap<Reverse<u8>, VecDeque<Message>>>,
+}
+
+impl PriorityPublisher {
+    /// Create a new priority publisher that binds to the specified address
+    pub fn new(address: impl Into<Endpoint>) -> Result<Self> {
+        Ok(Self::from_publisher(Publisher::new(address)?))
+    }
+
+    /// Schedule messages for an existing publisher
+    pub fn from_publisher(publisher: Publisher) -> Self {
+        Self {
+            publisher,
+            queues: RefCell::new(BTreeMap::new()),
+        }
+    }
+
+    /// The underlying publisher
+    pub fn publisher(&self) -> &Publisher {
+        &self.publisher
+    }
+
+    /// Queue `message` until the next flush
+    pub fn enqueue(&self, message: Message) {
+        self.queues
+            .borrow_mut()
+            .entry(Reverse(message.priority))
+            .or_default()
+            .push_back(message);
+    }
+
+    /// Number of messages waiting to be published
+    pub fn pending(&self) -> usize {
+        self.queues.borrow().values().map(VecDeque::len).sum()
+    }
+
+    /// Publish every queued message, highest priority first
+    ///
+    /// Returns how many were published. If publishing fails, the failed
+    /// message and everything behind it stay queued for the next flush.
+    pub fn flush(&self) -> Result<usize> {
+        let mut queues = self.queues.borrow_mut();
+        let mut published = 0;
+        while let Some(mut entry) = queues.first_entry() {
+            while let Some(message) = entry.get_mut().pop_front() {
+                if let Err(e) = self.publisher.publish(&message) {
+                    entry.get_mut().push_front(message);
+                    return Err(e);
+                }
+                published += 1;
+            }
+            entry.remove();
+        }
+        Ok(published)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestPair;
+    use serde_json::json;
+
+    #[test]
+    fn test_flush_sends_higher_priorities_first() {
+        let pair = TestPair::pubsub().unwrap();
+        let publisher = PriorityPublisher::from_publisher(pair.sender);
+
+        for (topic, priority) in [("low", 0), ("urgent", 9), ("normal", 5), ("urgent.2", 9)] {
+            publisher.enqueue(Message::new(topic, json!(null)).with_priority(priority));
+        }
+        assert_eq!(publisher.pending(), 4);
+        assert_eq!(publisher.flush().unwrap(), 4);
+        assert_eq!(publisher.pending(), 0);
+
+        let topics: Vec<String> = (0..4)
+            .map(|_| pair.receiver.receive_timeout(1000).unwrap().unwrap().topic)
+            .collect();
+        assert_eq!(topics, ["urgent", "urgent.2", "normal", "low"]);
+    }
+}
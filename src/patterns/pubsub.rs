@@ -1,29 +1,146 @@
 //! Publisher/Subscriber messaging pattern
 
+use crate::builder::{
+    impl_affinity, impl_bind, impl_buffers, impl_connect_timeout, impl_frames, impl_multicast,
+    impl_pattern, impl_plain, impl_tcp_keepalive,
+};
+use crate::endpoint::Endpoint;
 use crate::error::{OxideError, Result};
 use crate::message::Message;
-use zmq::{Context, Socket};
+use crate::metrics::Counters;
+use crate::patterns::{
+    deadline_after, recv_bytes_timeout, remaining_ms, SubscriptionSet, SubscriptionTree,
+};
+#[cfg(feature = "signals")]
+use crate::signals::{ShutdownToken, SHUTDOWN_POLL_MS};
+use crate::stop::{StopHandle, Wake};
+use crate::topic::Topic;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashMap};
+use std::io::{ErrorKind, Read, Write};
+use std::sync::mpsc::Sender;
+use std::time::SystemTime;
+use zmq::Socket;
+
+/// Last frame of a stream whose reader failed, in place of the empty
+/// terminator; data chunks are never the last frame, so it cannot be
+/// mistaken for one
+const STREAM_ABORT_FRAME: &[u8] = &[0x18];
 
 /// Publisher for the pub/sub pattern
 pub struct Publisher {
     socket: Socket,
+    counters: Counters,
+    tap: Option<Sender<Message>>,
 }
 
 impl Publisher {
     /// Create a new publisher that binds to the specified address
-    pub fn new(address: &str) -> Result<Self> {
-        let context = Context::new();
-        let socket = context.socket(zmq::PUB)?;
-        socket.bind(address)?;
-        Ok(Self { socket })
+    pub fn new(address: impl Into<Endpoint>) -> Result<Self> {
+        Self::builder().bind(address)
+    }
+
+    /// Create a new publisher that connects to the specified address
+    ///
+    /// Useful when many short-lived publishers report to one long-lived
+    /// collector: the subscriber binds a stable endpoint (see
+    /// [`Subscriber::new_bind`]) and publishers come and go. A connecting
+    /// publisher only applies the subscriber's subscriptions once it has
+    /// processed them, which ZeroMQ does after a send, so the first message
+    /// published on a new connection is usually dropped.
+    pub fn new_connect(address: impl Into<Endpoint>) -> Result<Self> {
+        Self::builder().connect(address)
+    }
+
+    /// Create a binding publisher that also copies messages onto `tap`
+    ///
+    /// Every message sent with `publish`, `publish_filtered` or
+    /// `publish_cbor` is cloned, payload included, and sent on the channel
+    /// after it has gone out on the socket, so local consumers see the same
+    /// traffic without a loopback subscriber. The clone costs an allocation
+    /// per message on top of encoding it. Raw and streamed publishes carry
+    /// no `Message` and are not tapped, and a disconnected channel is
+    /// ignored.
+    pub fn with_tap(address: impl Into<Endpoint>, tap: Sender<Message>) -> Result<Self> {
+        let mut publisher = Self::new(address)?;
+        publisher.tap = Some(tap);
+        Ok(publisher)
     }
 
     /// Publish a message
     pub fn publish(&self, message: &Message) -> Result<()> {
         let bytes = message.to_bytes()?;
-        self.socket
-            .send(&bytes, 0)
-            .map_err(|e| OxideError::Send(e.to_string()))?;
+        self.socket.send(&bytes, 0).map_err(OxideError::Send)?;
+        self.counters.record_sent();
+        self.forward_to_tap(message);
+        Ok(())
+    }
+
+    /// Publish `value` as the payload of a message on `topic`
+    ///
+    /// Shorthand for [`Message::from_value`] followed by
+    /// [`Publisher::publish`]; a value that cannot be represented as JSON
+    /// fails with `OxideError::Serialization` before anything is sent.
+    pub fn publish_value<T: Serialize>(&self, topic: &str, value: &T) -> Result<()> {
+        self.publish(&Message::from_value(topic, value)?)
+    }
+
+    /// Publish a message without blocking
+    ///
+    /// A plain PUB socket never blocks and silently drops messages for
+    /// subscribers at their high-water mark. Built with
+    /// [`SocketBuilder::nodrop`](crate::SocketBuilder::nodrop) it refuses
+    /// them instead: this then fails with `OxideError::Send(zmq::Error::EAGAIN)`
+    /// and counts the message in [`Publisher::dropped_count`], and the caller
+    /// can drop it or retry later.
+    pub fn try_publish(&self, message: &Message) -> Result<()> {
+        let bytes = message.to_bytes()?;
+        match self.socket.send(&bytes, zmq::DONTWAIT) {
+            Ok(()) => {}
+            Err(zmq::Error::EAGAIN) => {
+                self.counters.record_dropped();
+                return Err(OxideError::Send(zmq::Error::EAGAIN));
+            }
+            Err(e) => return Err(OxideError::Send(e)),
+        }
+        self.counters.record_sent();
+        self.forward_to_tap(message);
+        Ok(())
+    }
+
+    /// Messages [`Publisher::try_publish`] could not send because the
+    /// high-water mark was reached (`metrics` feature)
+    ///
+    /// Only counts attempts refused with `EAGAIN` on a publisher built with
+    /// [`SocketBuilder::nodrop`](crate::SocketBuilder::nodrop). Messages a
+    /// plain PUB socket drops silently at the high-water mark never reach
+    /// this crate and are not counted.
+    #[cfg(feature = "metrics")]
+    pub fn dropped_count(&self) -> u64 {
+        self.counters.dropped()
+    }
+
+    /// Publish a message with its topic as a leading frame
+    ///
+    /// Subscriptions match against the topic frame, so topic prefixes filter
+    /// as expected and receivers can inspect the topic before decoding (see
+    /// [`Subscriber::receive_topic_then`]). All `Subscriber` receive methods
+    /// accept both this and the single-frame form sent by `publish`.
+    pub fn publish_filtered(&self, message: &Message) -> Result<()> {
+        self.publish_raw(&message.topic, &message.to_bytes()?)?;
+        self.forward_to_tap(message);
+        Ok(())
+    }
+
+    /// Publish a message encoded as CBOR (requires the `cbor` feature)
+    #[cfg(feature = "cbor")]
+    pub fn publish_cbor(&self, message: &Message) -> Result<()> {
+        let bytes = message.to_cbor()?;
+        self.socket.send(&bytes, 0).map_err(OxideError::Send)?;
+        self.counters.record_sent();
+        self.forward_to_tap(message);
         Ok(())
     }
 
@@ -32,110 +149,1362 @@ impl Publisher {
         // Send topic as first frame
         self.socket
             .send(topic.as_bytes(), zmq::SNDMORE)
-            .map_err(|e| OxideError::Send(e.to_string()))?;
+            .map_err(OxideError::Send)?;
         // Send data as second frame
+        self.socket.send(data, 0).map_err(OxideError::Send)?;
+        self.counters.record_sent();
+        Ok(())
+    }
+
+    /// Publish already serialized bytes as the body of a topic-framed
+    /// message, without copying them
+    ///
+    /// Sent like [`Publisher::publish_filtered`], but `body` is handed to
+    /// ZeroMQ as it is instead of being built from a `Message`, and ZeroMQ
+    /// takes over the allocation rather than copying it. Nothing checks the
+    /// bytes: subscribers decoding `Message`s need `body` to be one, for
+    /// example from [`Message::to_bytes`]. The tap, if any, is not fed.
+    pub fn publish_bytes(&self, topic: &str, body: Vec<u8>) -> Result<()> {
+        self.socket
+            .send(topic.as_bytes(), zmq::SNDMORE)
+            .map_err(OxideError::Send)?;
         self.socket
-            .send(data, 0)
-            .map_err(|e| OxideError::Send(e.to_string()))?;
+            .send(zmq::Message::from(body), 0)
+            .map_err(OxideError::Send)?;
+        self.counters.record_sent();
         Ok(())
     }
+
+    /// Publish the contents of `reader` as a stream of `chunk_size` frames
+    ///
+    /// The topic is sent as the first frame, followed by one frame per chunk
+    /// and a terminating empty frame, all as a single multipart message, so
+    /// topic filtering applies to the whole stream and chunks from different
+    /// streams never interleave. The payload is never held as a
+    /// `serde_json::Value` or a single `Vec`, but ZeroMQ queues the frames
+    /// until the peer takes them, so memory on both sides still grows with
+    /// the stream size. Receive with [`Subscriber::receive_stream`].
+    ///
+    /// If `reader` fails partway through, the stream is ended with an abort
+    /// frame instead of the terminator, so the subscriber gets an error
+    /// rather than the truncated data, and the reader's error is returned
+    /// as `OxideError::Io`.
+    pub fn publish_stream(
+        &self,
+        topic: &str,
+        reader: &mut impl Read,
+        chunk_size: usize,
+    ) -> Result<()> {
+        if chunk_size == 0 {
+            return Err(OxideError::Configuration(
+                "chunk size must be greater than zero".to_string(),
+            ));
+        }
+
+        self.socket
+            .send(topic.as_bytes(), zmq::SNDMORE)
+            .map_err(OxideError::Send)?;
+
+        let mut chunk = vec![0u8; chunk_size];
+        loop {
+            let len = match reader.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(len) => len,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    // Finish the multipart message so the socket stays usable,
+                    // marked so the subscriber does not take it as complete
+                    let _ = self.socket.send(STREAM_ABORT_FRAME, 0);
+                    return Err(OxideError::Io(e));
+                }
+            };
+            self.socket
+                .send(&chunk[..len], zmq::SNDMORE)
+                .map_err(OxideError::Send)?;
+        }
+
+        self.socket
+            .send(&[] as &[u8], 0)
+            .map_err(OxideError::Send)?;
+        self.counters.record_sent();
+        Ok(())
+    }
+
+    fn forward_to_tap(&self, message: &Message) {
+        if let Some(tap) = &self.tap {
+            let _ = tap.send(message.clone());
+        }
+    }
 }
 
+impl_pattern!(Publisher { tap } => zmq::PUB, Subscriber { subscriptions, tree, topic_stats, verify_checksums, skipped } => zmq::SUB);
+impl_affinity!(Publisher, Subscriber);
+impl_plain!(Publisher, Subscriber);
+impl_buffers!(Publisher, Subscriber);
+impl_bind!(Publisher, Subscriber);
+impl_frames!(Subscriber);
+impl_connect_timeout!(Publisher, Subscriber);
+impl_multicast!(Publisher, Subscriber);
+impl_tcp_keepalive!(Subscriber);
+
 /// Subscriber for the pub/sub pattern
 pub struct Subscriber {
     socket: Socket,
+    counters: Counters,
+    subscriptions: Option<RefCell<SubscriptionSet>>,
+    tree: Option<RefCell<SubscriptionTree>>,
+    topic_stats: Option<RefCell<TopicStats>>,
+    verify_checksums: bool,
+    skipped: Cell<u64>,
 }
 
 impl Subscriber {
     /// Create a new subscriber that connects to the specified address
-    pub fn new(address: &str) -> Result<Self> {
-        let context = Context::new();
-        let socket = context.socket(zmq::SUB)?;
-        socket.connect(address)?;
-        Ok(Self { socket })
+    pub fn new(address: impl Into<Endpoint>) -> Result<Self> {
+        Self::builder().connect(address)
+    }
+
+    /// Create a new subscriber and wait up to `timeout_ms` until it has
+    /// connected to `address`
+    ///
+    /// Replaces a fixed sleep after connecting; see
+    /// [`SocketBuilder::connect_and_wait`](crate::SocketBuilder::connect_and_wait)
+    /// for what it does and does not guarantee.
+    pub fn new_wait_connected(address: impl Into<Endpoint>, timeout_ms: i32) -> Result<Self> {
+        Self::builder().connect_and_wait(address, timeout_ms)
+    }
+
+    /// Create a new subscriber that binds to the specified address
+    ///
+    /// Gives the subscriber a stable endpoint that several connecting
+    /// publishers can feed, e.g. a log or metrics collector.
+    pub fn new_bind(address: impl Into<Endpoint>) -> Result<Self> {
+        Self::builder().bind(address)
+    }
+
+    /// Apply `subscriptions` and keep them up to date from now on
+    ///
+    /// Later `subscribe` and `unsubscribe` calls update the set, and
+    /// [`Subscriber::subscriptions`] hands it back for a replacement socket.
+    /// Pass `SubscriptionSet::new()` to start tracking from scratch.
+    pub fn with_subscriptions(mut self, subscriptions: SubscriptionSet) -> Result<Self> {
+        self.subscriptions = Some(RefCell::new(SubscriptionSet::new()));
+        subscriptions.reapply(&self)?;
+        Ok(self)
+    }
+
+    /// The tracked subscriptions, if [`Subscriber::with_subscriptions`] was used
+    pub fn subscriptions(&self) -> Option<SubscriptionSet> {
+        self.subscriptions.as_ref().map(|set| set.borrow().clone())
+    }
+
+    /// Route subscriptions through `tree`, keeping only its
+    /// [`minimal`](SubscriptionTree::minimal) prefixes on the socket
+    ///
+    /// The tree's current subscriptions are applied straight away, and
+    /// later `subscribe` and `unsubscribe` calls update it; subscriptions
+    /// made before this call are left on the socket untouched. Pass
+    /// `SubscriptionTree::new(separator)` to start from scratch.
+    pub fn with_subscription_tree(mut self, tree: SubscriptionTree) -> Result<Self> {
+        for prefix in tree.minimal() {
+            self.socket
+                .set_subscribe(prefix.as_bytes())
+                .map_err(OxideError::SocketOption)?;
+        }
+        self.tree = Some(RefCell::new(tree));
+        Ok(self)
+    }
+
+    /// The subscription tree, if [`Subscriber::with_subscription_tree`]
+    /// was used
+    pub fn subscription_tree(&self) -> Option<SubscriptionTree> {
+        self.tree.as_ref().map(|tree| tree.borrow().clone())
+    }
+
+    /// Apply `change` to the tree and the resulting difference in minimal
+    /// prefixes to the socket, subscribing before unsubscribing so nothing
+    /// covered by both is missed
+    fn retune(
+        &self,
+        tree: &RefCell<SubscriptionTree>,
+        change: impl FnOnce(&mut SubscriptionTree),
+    ) -> Result<()> {
+        let mut updated = tree.borrow().clone();
+        change(&mut updated);
+        let (before, after) = (tree.borrow().minimal(), updated.minimal());
+        for prefix in after.difference(&before) {
+            self.socket
+                .set_subscribe(prefix.as_bytes())
+                .map_err(OxideError::SocketOption)?;
+        }
+        for prefix in before.difference(&after) {
+            self.socket
+                .set_unsubscribe(prefix.as_bytes())
+                .map_err(OxideError::SocketOption)?;
+        }
+        *tree.borrow_mut() = updated;
+        Ok(())
+    }
+
+    /// Reject received messages whose payload checksum does not match
+    ///
+    /// Every receive method that decodes a message then calls
+    /// [`Message::verify_checksum`] and fails with its
+    /// `OxideError::Validation`, for messages without a checksum too, so
+    /// every publisher on the feed must use [`Message::with_checksum`].
+    /// Raw receives such as [`Subscriber::receive_into`] are not checked.
+    pub fn with_checksum_verification(mut self) -> Self {
+        self.verify_checksums = true;
+        self
+    }
+
+    /// Count received messages per topic, see [`Subscriber::topic_stats`]
+    ///
+    /// Every distinct topic received costs an entry of a few dozen bytes
+    /// plus the topic string, kept for the subscriber's lifetime. At most
+    /// `max_topics` topics are tracked; messages on further topics are
+    /// received as usual but not counted, so a subscriber to topics with
+    /// unbounded cardinality (one per order id, say) should pick a cap.
+    pub fn with_topic_stats(mut self, max_topics: usize) -> Self {
+        self.topic_stats = Some(RefCell::new(TopicStats {
+            max_topics,
+            topics: HashMap::new(),
+        }));
+        self
+    }
+
+    /// Message count and last arrival per topic, empty unless
+    /// [`Subscriber::with_topic_stats`] was used
+    pub fn topic_stats(&self) -> HashMap<String, TopicStat> {
+        self.topic_stats
+            .as_ref()
+            .map(|stats| stats.borrow().topics.clone())
+            .unwrap_or_default()
     }
 
     /// Subscribe to messages with a specific topic prefix
     /// Use an empty string to subscribe to all messages
     pub fn subscribe(&self, topic: &str) -> Result<()> {
-        self.socket
-            .set_subscribe(topic.as_bytes())
-            .map_err(|e| OxideError::Configuration(e.to_string()))?;
+        match &self.tree {
+            Some(tree) => self.retune(tree, |tree| tree.insert(topic))?,
+            None => self
+                .socket
+                .set_subscribe(topic.as_bytes())
+                .map_err(OxideError::SocketOption)?,
+        }
+        if let Some(set) = &self.subscriptions {
+            set.borrow_mut().insert(topic);
+        }
         Ok(())
     }
 
+    /// Subscribe to a hierarchical topic or pattern
+    ///
+    /// Subscribes to [`Topic::zmq_prefix`], which may let through more than
+    /// the pattern itself; see the [`topic`](crate::topic) module docs.
+    pub fn subscribe_topic(&self, topic: &Topic) -> Result<()> {
+        self.subscribe(topic.zmq_prefix())
+    }
+
+    /// Subscribe to `topic` until the returned guard is dropped
+    ///
+    /// Dropping the [`ScopedSubscription`] unsubscribes again, so the
+    /// subscription cannot outlive the code that needs it. Over TCP a
+    /// subscriber that goes away takes its subscriptions with it anyway,
+    /// making this mostly tidiness for short-lived sockets; it matters for
+    /// long-lived sockets that many parts of a program subscribe through.
+    /// Subscriptions are counted, so a topic also subscribed elsewhere
+    /// stays subscribed.
+    pub fn subscribe_scoped(&self, topic: &str) -> Result<ScopedSubscription<'_>> {
+        self.subscribe(topic)?;
+        Ok(ScopedSubscription {
+            subscriber: self,
+            topic: topic.to_string(),
+        })
+    }
+
     /// Unsubscribe from a topic
     pub fn unsubscribe(&self, topic: &str) -> Result<()> {
-        self.socket
-            .set_unsubscribe(topic.as_bytes())
-            .map_err(|e| OxideError::Configuration(e.to_string()))?;
+        match &self.tree {
+            Some(tree) => self.retune(tree, |tree| {
+                tree.remove(topic);
+            })?,
+            None => self
+                .socket
+                .set_unsubscribe(topic.as_bytes())
+                .map_err(OxideError::SocketOption)?,
+        }
+        if let Some(set) = &self.subscriptions {
+            set.borrow_mut().remove(topic);
+        }
+        Ok(())
+    }
+
+    /// Change the subscriptions to exactly `desired` with as few
+    /// subscribes and unsubscribes as possible
+    ///
+    /// Prefixes in both the tracked set and `desired` are left alone, so
+    /// messages on them keep arriving throughout; the new prefixes are
+    /// subscribed before the old ones are dropped. Needs the subscriptions
+    /// tracked with [`Subscriber::with_subscriptions`], and fails with
+    /// `OxideError::Configuration` otherwise.
+    pub fn set_subscriptions(&self, desired: &[&str]) -> Result<()> {
+        let Some(mut current) = self.subscriptions() else {
+            return Err(OxideError::Configuration(
+                "set_subscriptions needs a subscriber created with with_subscriptions".to_string(),
+            ));
+        };
+        let desired: BTreeSet<&str> = desired.iter().copied().collect();
+        for &prefix in &desired {
+            if !current.contains(prefix) {
+                self.subscribe(prefix)?;
+            }
+        }
+        let stale: Vec<String> = current
+            .iter()
+            .filter(|prefix| !desired.contains(prefix))
+            .map(str::to_string)
+            .collect();
+        // Repeated subscriptions take as many unsubscribes to drop
+        for prefix in stale {
+            while current.remove(&prefix) {
+                self.unsubscribe(&prefix)?;
+            }
+        }
         Ok(())
     }
 
     /// Receive a message (blocking)
     pub fn receive(&self) -> Result<Message> {
-        let bytes = self
-            .socket
-            .recv_bytes(0)
-            .map_err(|e| OxideError::Receive(e.to_string()))?;
-        Message::from_bytes(&bytes)
+        let bytes = self.socket.recv_bytes(0).map_err(OxideError::Receive)?;
+        self.decode(bytes)
+    }
+
+    /// Receive the next message that decodes, skipping any that do not
+    /// (blocking)
+    ///
+    /// A message that fails with `OxideError::Serialization` is dropped,
+    /// counted in [`Subscriber::skipped_messages`] and logged with the
+    /// `tracing` feature, and the wait goes on; other errors are returned
+    /// as usual. A skipped message is lost for good: nothing tells the
+    /// publisher, and its topic and contents are unknown. The strict
+    /// receive methods remain the way to stop on the first bad message.
+    pub fn receive_lenient(&self) -> Result<Message> {
+        loop {
+            match self.receive() {
+                Err(OxideError::Serialization(error)) => {
+                    self.skipped.set(self.skipped.get() + 1);
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(%error, "skipping a message that does not decode");
+                    #[cfg(not(feature = "tracing"))]
+                    let _ = error;
+                }
+                received => return received,
+            }
+        }
+    }
+
+    /// Messages [`Subscriber::receive_lenient`] has skipped because they
+    /// did not decode
+    pub fn skipped_messages(&self) -> u64 {
+        self.skipped.get()
+    }
+
+    /// Receive the next message with a version of at least `min` (blocking)
+    ///
+    /// Older messages are dropped. Unversioned messages count as version 0.
+    pub fn receive_min_version(&self, min: u32) -> Result<Message> {
+        loop {
+            let message = self.receive()?;
+            if message.version >= min {
+                return Ok(message);
+            }
+        }
+    }
+
+    /// Receive a CBOR-encoded message (blocking, requires the `cbor` feature)
+    #[cfg(feature = "cbor")]
+    pub fn receive_cbor(&self) -> Result<Message> {
+        let bytes = self.socket.recv_bytes(0).map_err(OxideError::Receive)?;
+        self.decode_with(bytes, Message::from_cbor)
+    }
+
+    /// Receive a topic-framed message, decoding it only if `accept` agrees
+    ///
+    /// For messages sent with [`Publisher::publish_filtered`] (or
+    /// `publish_raw`) the topic frame is read first and handed to `accept`;
+    /// when it returns `false` the body is dropped without being parsed and
+    /// `Ok(None)` is returned. Single-frame messages carry no separate
+    /// topic, so they are decoded before `accept` sees their topic.
+    pub fn receive_topic_then(&self, accept: impl FnOnce(&str) -> bool) -> Result<Option<Message>> {
+        let first = self.socket.recv_bytes(0).map_err(OxideError::Receive)?;
+
+        if !self.has_more()? {
+            self.counters.record_received();
+            let message = Message::from_bytes(&first)?;
+            self.check(&message)?;
+            return Ok(accept(&message.topic).then_some(message));
+        }
+
+        if !accept(&String::from_utf8_lossy(&first)) {
+            self.discard_remaining_frames()?;
+            self.counters.record_received();
+            return Ok(None);
+        }
+        self.decode(first).map(Some)
+    }
+
+    /// Receive the raw bytes of the next message into `buf` (blocking)
+    ///
+    /// The buffer is cleared and truncated to the message length, so its
+    /// allocation is reused across calls. Returns the number of bytes
+    /// received; deserialization is left to the caller (e.g. via
+    /// [`Message::from_bytes`]).
+    ///
+    /// For a topic-framed message (from [`Publisher::publish_filtered`] or
+    /// [`Publisher::publish_bytes`]) the topic frame is skipped and `buf`
+    /// holds the body frame; any frames after the body are discarded, so
+    /// the next receive starts at the next message.
+    pub fn receive_into(&self, buf: &mut Vec<u8>) -> Result<usize> {
+        let mut frame = zmq::Message::new();
+        self.socket
+            .recv(&mut frame, 0)
+            .map_err(OxideError::Receive)?;
+        if self.has_more()? {
+            self.socket
+                .recv(&mut frame, 0)
+                .map_err(OxideError::Receive)?;
+            self.discard_remaining_frames()?;
+        }
+        self.counters.record_received();
+        buf.clear();
+        buf.extend_from_slice(&frame);
+        Ok(frame.len())
+    }
+
+    /// Receive a stream sent by [`Publisher::publish_stream`] into `writer`
+    ///
+    /// Chunks are written in the order they were published and the topic is
+    /// returned once the terminating frame arrives. ZeroMQ delivers the
+    /// multipart message atomically, so all chunks are already buffered
+    /// locally when the first one is written. On a write error the rest of
+    /// the stream is discarded.
+    ///
+    /// A stream the publisher aborted because its reader failed fails with
+    /// `OxideError::Io` of kind `UnexpectedEof`, after the chunks sent
+    /// before the failure have been written.
+    pub fn receive_stream(&self, writer: &mut impl Write) -> Result<String> {
+        let topic = self.socket.recv_bytes(0).map_err(OxideError::Receive)?;
+        let topic = String::from_utf8(topic)
+            .map_err(|e| OxideError::Protocol(format!("stream topic is not UTF-8: {}", e)))?;
+
+        let mut frame = zmq::Message::new();
+        let mut write_error = None;
+        let mut terminator = None;
+        while self.has_more()? {
+            self.socket
+                .recv(&mut frame, 0)
+                .map_err(OxideError::Receive)?;
+            if !self.has_more()? {
+                terminator = Some(frame.to_vec());
+            } else if write_error.is_none() {
+                if let Err(e) = writer.write_all(&frame) {
+                    write_error = Some(e);
+                }
+            }
+        }
+
+        self.counters.record_received();
+        if let Some(e) = write_error {
+            return Err(OxideError::Io(e));
+        }
+        match terminator.as_deref() {
+            Some([]) => Ok(topic),
+            Some(STREAM_ABORT_FRAME) => Err(OxideError::Io(std::io::Error::new(
+                ErrorKind::UnexpectedEof,
+                format!("publisher aborted stream {}", topic),
+            ))),
+            _ => Err(OxideError::Protocol(format!(
+                "stream {} has no terminating frame",
+                topic
+            ))),
+        }
     }
 
     /// Receive a message with timeout in milliseconds
     /// Returns None if timeout expires
+    ///
+    /// The socket's receive timeout is restored afterwards; earlier versions
+    /// left it set, so later blocking calls timed out as well.
     pub fn receive_timeout(&self, timeout_ms: i32) -> Result<Option<Message>> {
-        self.socket
-            .set_rcvtimeo(timeout_ms)
-            .map_err(|e| OxideError::Configuration(e.to_string()))?;
+        match recv_bytes_timeout(&self.socket, timeout_ms)? {
+            Some(bytes) => Ok(Some(self.decode(bytes)?)),
+            None => Ok(None),
+        }
+    }
 
-        match self.socket.recv_bytes(0) {
-            Ok(bytes) => Ok(Some(Message::from_bytes(&bytes)?)),
-            Err(zmq::Error::EAGAIN) => Ok(None),
-            Err(e) => Err(OxideError::Receive(e.to_string())),
+    /// Receive the next message that has not outlived its TTL, waiting at
+    /// most `timeout_ms` in total
+    ///
+    /// Expired messages are dropped, see [`Message::is_expired`], which
+    /// relies on the publisher's and subscriber's clocks agreeing.
+    /// Messages without a TTL are always returned.
+    pub fn receive_fresh(&self, timeout_ms: i32) -> Result<Option<Message>> {
+        let deadline = deadline_after(timeout_ms);
+        loop {
+            let wait_ms = remaining_ms(deadline).unwrap_or(0);
+            match self.receive_timeout(wait_ms)? {
+                Some(message) if message.is_expired() => {}
+                received => return Ok(received),
+            }
+        }
+    }
+
+    /// Receive a message, or `None` once `stop` has been signalled
+    /// (blocking)
+    ///
+    /// Unlike [`Subscriber::receive`], another thread can end the wait
+    /// with [`StopHandle::stop`]. A stop signalled while a message is
+    /// waiting wins, so the message stays queued.
+    pub fn receive_interruptible(&self, stop: &StopHandle) -> Result<Option<Message>> {
+        loop {
+            if stop.wait(&self.socket, -1)? == Wake::Stopped {
+                return Ok(None);
+            }
+            if let Some(message) = self.try_receive()? {
+                return Ok(Some(message));
+            }
         }
     }
 
     /// Check if a message is available without blocking
     pub fn try_receive(&self) -> Result<Option<Message>> {
         match self.socket.recv_bytes(zmq::DONTWAIT) {
-            Ok(bytes) => Ok(Some(Message::from_bytes(&bytes)?)),
+            Ok(bytes) => Ok(Some(self.decode(bytes)?)),
             Err(zmq::Error::EAGAIN) => Ok(None),
-            Err(e) => Err(OxideError::Receive(e.to_string())),
+            Err(e) => Err(OxideError::Receive(e)),
+        }
+    }
+
+    /// Receive a message and deserialize its payload into `T`, waiting at
+    /// most `timeout_ms`
+    ///
+    /// Socket failures are returned as `OxideError::Receive`; a message
+    /// that cannot be decoded, or whose payload does not fit `T`, as
+    /// `OxideError::Serialization`. The topic and metadata are discarded.
+    pub fn receive_as<T: DeserializeOwned>(&self, timeout_ms: i32) -> Result<Option<T>> {
+        match self.receive_timeout(timeout_ms)? {
+            Some(message) => message.into_payload_as().map(Some),
+            None => Ok(None),
         }
     }
+
+    /// Decode a message whose first frame has just been received
+    fn decode(&self, first: Vec<u8>) -> Result<Message> {
+        self.decode_with(first, Message::from_bytes)
+    }
+
+    fn decode_with(&self, first: Vec<u8>, parse: fn(&[u8]) -> Result<Message>) -> Result<Message> {
+        let message = if !self.has_more()? {
+            self.counters.record_received();
+            parse(&first)?
+        } else {
+            // Topic-framed: the first frame is the topic, the second the message
+            let body = self.socket.recv_bytes(0).map_err(OxideError::Receive)?;
+            self.discard_remaining_frames()?;
+            self.counters.record_received();
+            parse(&body)?
+        };
+        self.check(&message)?;
+        Ok(message)
+    }
+
+    /// Verify the checksum if asked to, then count the topic
+    fn check(&self, message: &Message) -> Result<()> {
+        if self.verify_checksums {
+            message.verify_checksum()?;
+        }
+        if let Some(stats) = &self.topic_stats {
+            stats.borrow_mut().record(&message.topic);
+        }
+        Ok(())
+    }
+
+    fn discard_remaining_frames(&self) -> Result<()> {
+        let mut frame = zmq::Message::new();
+        while self.has_more()? {
+            self.socket
+                .recv(&mut frame, 0)
+                .map_err(OxideError::Receive)?;
+        }
+        Ok(())
+    }
+}
+
+/// Messages received on one topic, see [`Subscriber::topic_stats`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TopicStat {
+    /// Messages received
+    pub count: u64,
+    /// When the latest one arrived
+    pub last_seen: SystemTime,
+}
+
+struct TopicStats {
+    max_topics: usize,
+    topics: HashMap<String, TopicStat>,
+}
+
+impl TopicStats {
+    fn record(&mut self, topic: &str) {
+        let now = SystemTime::now();
+        if let Some(stat) = self.topics.get_mut(topic) {
+            stat.count += 1;
+            stat.last_seen = now;
+        } else if self.topics.len() < self.max_topics {
+            self.topics.insert(
+                topic.to_string(),
+                TopicStat {
+                    count: 1,
+                    last_seen: now,
+                },
+            );
+        }
+    }
+}
+
+/// Subscription that ends when dropped, see [`Subscriber::subscribe_scoped`]
+#[must_use = "the subscription ends as soon as the guard is dropped"]
+pub struct ScopedSubscription<'a> {
+    subscriber: &'a Subscriber,
+    topic: String,
+}
+
+impl ScopedSubscription<'_> {
+    /// The subscribed topic prefix
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+}
+
+impl Drop for ScopedSubscription<'_> {
+    fn drop(&mut self) {
+        // Unsubscribing only fails once the context has been terminated
+        let _ = self.subscriber.unsubscribe(&self.topic);
+    }
+}
+
+#[cfg(feature = "signals")]
+impl Subscriber {
+    /// Iterate over incoming messages until `token` is triggered
+    ///
+    /// The token is checked every [`SHUTDOWN_POLL_MS`] milliseconds, so the
+    /// iterator ends promptly even when no messages arrive.
+    pub fn iter_until<'a>(
+        &'a self,
+        token: &'a ShutdownToken,
+    ) -> impl Iterator<Item = Result<Message>> + 'a {
+        std::iter::from_fn(move || loop {
+            if token.is_shutdown() {
+                return None;
+            }
+            match self.receive_timeout(SHUTDOWN_POLL_MS) {
+                Ok(Some(msg)) => return Some(Ok(msg)),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::TestPair;
     use serde_json::json;
     use std::thread;
     use std::time::Duration;
 
     #[test]
-    fn test_pubsub_basic() {
-        let address = "tcp://127.0.0.1:5555";
+    fn test_publish_bytes_sends_serialized_message() {
+        let pair = TestPair::pubsub().unwrap();
+        let body = Message::new("prices", json!({"bid": 101}))
+            .to_bytes()
+            .unwrap();
+        // `body` is moved into the publisher and cannot be used afterwards
+        pair.sender.publish_bytes("prices", body).unwrap();
 
-        // Create publisher in a separate thread
-        let pub_handle = thread::spawn(move || {
-            let publisher = Publisher::new(address).unwrap();
-            // Give subscriber time to connect
-            thread::sleep(Duration::from_millis(100));
+        let received = pair.receiver.receive_timeout(1000).unwrap().unwrap();
+        assert_eq!(received.topic, "prices");
+        assert_eq!(received.payload, json!({"bid": 101}));
+    }
 
-            let msg = Message::new("test", json!({"data": "hello"}));
-            publisher.publish(&msg).unwrap();
-        });
+    #[test]
+    fn test_receive_interruptible_returns_on_stop() {
+        let subscriber = Subscriber::new("tcp://127.0.0.1:15631").unwrap();
+        subscriber.subscribe("").unwrap();
+        let stop = StopHandle::new().unwrap();
 
-        // Create subscriber
-        let subscriber = Subscriber::new(address).unwrap();
-        subscriber.subscribe("").unwrap(); // Subscribe to all
+        let stopper = {
+            let stop = stop.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(100));
+                stop.stop().unwrap();
+            })
+        };
+        let started = std::time::Instant::now();
+        assert!(subscriber.receive_interruptible(&stop).unwrap().is_none());
+        assert!(started.elapsed() < Duration::from_secs(1));
+        stopper.join().unwrap();
 
-        pub_handle.join().unwrap();
+        // Stopping is permanent
+        assert!(subscriber.receive_interruptible(&stop).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_clones_of_one_handle_wait_on_several_threads() {
+        let publisher = Publisher::new("tcp://127.0.0.1:25631").unwrap();
+        let stop = StopHandle::new().unwrap();
+        let (received_tx, received_rx) = std::sync::mpsc::channel();
+        let topics = ["left", "right"];
+        let waiters: Vec<_> = topics
+            .iter()
+            .map(|&topic| {
+                let stop = stop.clone();
+                let received_tx = received_tx.clone();
+                thread::spawn(move || {
+                    let subscriber = Subscriber::new("tcp://127.0.0.1:25631").unwrap();
+                    subscriber.subscribe(topic).unwrap();
+                    let mut received = 0;
+                    while let Some(message) = subscriber.receive_interruptible(&stop).unwrap() {
+                        received += 1;
+                        received_tx.send(message.topic).unwrap();
+                    }
+                    received
+                })
+            })
+            .collect();
+
+        // Each waiter only wakes for its own topic, so neither may keep the
+        // other from waiting while it has nothing to receive
+        for topic in topics {
+            let mut delivered = false;
+            for _ in 0..200 {
+                publisher
+                    .publish_filtered(&Message::new(topic, json!(null)))
+                    .unwrap();
+                if let Ok(received) = received_rx.recv_timeout(Duration::from_millis(10)) {
+                    assert_eq!(received, topic);
+                    delivered = true;
+                    break;
+                }
+            }
+            assert!(delivered, "no {} message received", topic);
+            while received_rx.recv_timeout(Duration::from_millis(20)).is_ok() {}
+        }
+        stop.stop().unwrap();
+        for waiter in waiters {
+            assert!(waiter.join().unwrap() >= 1);
+        }
+    }
+
+    #[test]
+    fn test_topic_stats_count_per_topic() {
+        let pair = TestPair::pubsub().unwrap();
+        let subscriber = pair.receiver.with_topic_stats(2);
+        for topic in ["orders", "trades", "orders", "quotes", "orders", "trades"] {
+            pair.sender
+                .publish(&Message::new(topic, json!(null)))
+                .unwrap();
+        }
+        for _ in 0..6 {
+            subscriber.receive_timeout(1000).unwrap().unwrap();
+        }
+
+        let stats = subscriber.topic_stats();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats["orders"].count, 3);
+        assert_eq!(stats["trades"].count, 2);
+        assert!(stats["trades"].last_seen >= stats["orders"].last_seen);
+        // Over the cap, so not tracked
+        assert!(!stats.contains_key("quotes"));
+    }
+
+    #[test]
+    fn test_pubsub_basic() {
+        // The pair waits until the subscription is live, so no sleep is needed
+        let pair = TestPair::pubsub().unwrap();
+
+        let msg = Message::new("test", json!({"data": "hello"}));
+        pair.sender.publish(&msg).unwrap();
 
         // Try to receive with timeout
-        let received = subscriber.receive_timeout(1000).unwrap();
+        let received = pair.receiver.receive_timeout(1000).unwrap();
         assert!(received.is_some());
         let msg = received.unwrap();
         assert_eq!(msg.topic, "test");
     }
+
+    #[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+    struct SensorReading {
+        sensor: String,
+        celsius: f64,
+    }
+
+    #[test]
+    fn test_receive_as_typed_payload() {
+        let pair = TestPair::pubsub().unwrap();
+        pair.sender
+            .publish(&Message::new(
+                "sensors",
+                json!({"sensor": "hall", "celsius": 21.5}),
+            ))
+            .unwrap();
+
+        let reading: SensorReading = pair.receiver.receive_as(1000).unwrap().unwrap();
+        assert_eq!(
+            reading,
+            SensorReading {
+                sensor: "hall".to_string(),
+                celsius: 21.5
+            }
+        );
+        assert!(pair
+            .receiver
+            .receive_as::<SensorReading>(10)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_publish_value_sends_struct_payload() {
+        let pair = TestPair::pubsub().unwrap();
+        let reading = SensorReading {
+            sensor: "hall".to_string(),
+            celsius: 21.5,
+        };
+        pair.sender.publish_value("sensors", &reading).unwrap();
+
+        let received = pair.receiver.receive_timeout(1000).unwrap().unwrap();
+        assert_eq!(received.topic, "sensors");
+        assert_eq!(received.payload, json!({"sensor": "hall", "celsius": 21.5}));
+    }
+
+    #[test]
+    fn test_checksum_verification_rejects_unchecked_messages() {
+        let pair = TestPair::pubsub().unwrap();
+        let subscriber = pair.receiver.with_checksum_verification();
+        pair.sender
+            .publish(&Message::new("feed", json!(1)).with_checksum())
+            .unwrap();
+        pair.sender
+            .publish(&Message::new("feed", json!(2)))
+            .unwrap();
+
+        let checked = subscriber.receive_timeout(1000).unwrap().unwrap();
+        assert_eq!(checked.payload, json!(1));
+        assert!(matches!(
+            subscriber.receive_timeout(1000),
+            Err(OxideError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn test_receive_fresh_skips_expired_messages() {
+        let pair = TestPair::pubsub().unwrap();
+        pair.sender
+            .publish(&Message::new("alert", json!("stale")).with_ttl(Duration::from_millis(20)))
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        pair.sender
+            .publish(&Message::new("alert", json!("live")).with_ttl(Duration::from_secs(60)))
+            .unwrap();
+
+        let fresh = pair.receiver.receive_fresh(1000).unwrap().unwrap();
+        assert_eq!(fresh.payload, json!("live"));
+        assert!(pair.receiver.receive_fresh(50).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_subscription_tree_skips_covered_socket_subscriptions() {
+        use crate::patterns::{Subscription, XPublisher};
+
+        let address = "tcp://127.0.0.1:15645";
+        let publisher = XPublisher::new(address).unwrap();
+        publisher.set_verbose(true).unwrap();
+        let subscriber = Subscriber::new(address)
+            .unwrap()
+            .with_subscription_tree(SubscriptionTree::new('/'))
+            .unwrap();
+        let change = |subscribe, topic: &str| Subscription {
+            subscribe,
+            topic: topic.to_string(),
+        };
+
+        subscriber.subscribe("a/").unwrap();
+        subscriber.subscribe("a/b").unwrap();
+        let first = publisher.recv_subscription_timeout(2000).unwrap();
+        assert_eq!(first, Some(change(true, "a/")));
+        assert!(publisher.recv_subscription_timeout(200).unwrap().is_none());
+
+        // Dropping the covering subscription brings the covered one back
+        subscriber.unsubscribe("a/").unwrap();
+        let changes: Vec<_> = (0..2)
+            .map(|_| publisher.recv_subscription_timeout(2000).unwrap().unwrap())
+            .collect();
+        assert_eq!(changes, [change(true, "a/b"), change(false, "a/")]);
+        assert!(subscriber.subscription_tree().unwrap().covers("a/b/c"));
+    }
+
+    #[test]
+    fn test_receive_as_payload_mismatch() {
+        let pair = TestPair::pubsub().unwrap();
+        pair.sender
+            .publish(&Message::new("sensors", json!({"sensor": "hall"})))
+            .unwrap();
+
+        let result = pair.receiver.receive_as::<SensorReading>(1000);
+        assert!(matches!(result, Err(OxideError::Serialization(_))));
+    }
+
+    #[test]
+    fn test_scoped_subscription_ends_on_drop() {
+        let address = "tcp://127.0.0.1:15615";
+        let publisher = crate::patterns::XPublisher::new(address).unwrap();
+        let subscriber = Subscriber::new(address)
+            .unwrap()
+            .with_subscriptions(SubscriptionSet::new())
+            .unwrap();
+
+        let guard = subscriber.subscribe_scoped("news").unwrap();
+        let change = publisher.recv_subscription_timeout(2000).unwrap().unwrap();
+        assert!(change.subscribe);
+        assert!(subscriber.subscriptions().unwrap().contains("news"));
+
+        drop(guard);
+        let change = publisher.recv_subscription_timeout(2000).unwrap().unwrap();
+        assert!(!change.subscribe);
+        assert_eq!(change.topic, "news");
+        assert!(subscriber.subscriptions().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_receive_lenient_skips_only_undecodable_messages() {
+        let pair = TestPair::pubsub().unwrap();
+        let send_corrupt = || {
+            pair.sender
+                .with_raw_socket(|socket| socket.send("{not a message", 0))
+                .unwrap()
+        };
+        pair.sender
+            .publish(&Message::new("first", json!(1)))
+            .unwrap();
+        send_corrupt();
+        pair.sender
+            .publish(&Message::new("second", json!(2)))
+            .unwrap();
+
+        assert_eq!(pair.receiver.receive_lenient().unwrap().topic, "first");
+        assert_eq!(pair.receiver.receive_lenient().unwrap().topic, "second");
+        assert_eq!(pair.receiver.skipped_messages(), 1);
+
+        // Strict receives still fail on a bad message
+        send_corrupt();
+        assert!(matches!(
+            pair.receiver.receive_timeout(1000),
+            Err(OxideError::Serialization(_))
+        ));
+        assert_eq!(pair.receiver.skipped_messages(), 1);
+    }
+
+    #[test]
+    fn test_set_subscriptions_changes_only_the_difference() {
+        use crate::patterns::{Subscription, XPublisher};
+
+        let address = "tcp://127.0.0.1:15663";
+        let publisher = XPublisher::new(address).unwrap();
+        publisher.set_verbose(true).unwrap();
+        let subscriber = Subscriber::new(address)
+            .unwrap()
+            .with_subscriptions(SubscriptionSet::new())
+            .unwrap();
+        assert!(Subscriber::new(address)
+            .unwrap()
+            .set_subscriptions(&["a"])
+            .is_err());
+        let change = |subscribe, topic: &str| Subscription {
+            subscribe,
+            topic: topic.to_string(),
+        };
+
+        subscriber.set_subscriptions(&["a", "b"]).unwrap();
+        for _ in 0..2 {
+            assert!(
+                publisher
+                    .recv_subscription_timeout(2000)
+                    .unwrap()
+                    .unwrap()
+                    .subscribe
+            );
+        }
+
+        subscriber.set_subscriptions(&["b", "c"]).unwrap();
+        let mut changes = Vec::new();
+        while let Some(received) = publisher.recv_subscription_timeout(200).unwrap() {
+            changes.push(received);
+        }
+        assert_eq!(changes, [change(true, "c"), change(false, "a")]);
+        assert_eq!(
+            subscriber
+                .subscriptions()
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>(),
+            ["b", "c"]
+        );
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_dropped_count_with_stalled_subscriber() {
+        let context = zmq::Context::new();
+        let endpoint = "inproc://dropped-count";
+        let publisher = Publisher::builder()
+            .context(&context)
+            .nodrop(true)
+            .sndhwm(1)
+            .bind(endpoint)
+            .unwrap();
+        let subscriber = Subscriber::builder()
+            .context(&context)
+            .rcvhwm(1)
+            .connect(endpoint)
+            .unwrap();
+        subscriber.subscribe("").unwrap();
+
+        // Wait for the subscription, then stop reading
+        let probe = Message::new("probe", json!(null));
+        let ready = (0..200).any(|_| {
+            let _ = publisher.try_publish(&probe);
+            subscriber.receive_timeout(10).unwrap().is_some()
+        });
+        assert!(ready);
+
+        let message = Message::new("flood", json!({"n": 1}));
+        let refused = (0..100)
+            .filter(|_| publisher.try_publish(&message).is_err())
+            .count();
+        assert!(refused > 0);
+        assert!(publisher.dropped_count() >= refused as u64);
+    }
+
+    #[test]
+    fn test_receive_into_reuses_buffer() {
+        let address = "tcp://127.0.0.1:15567";
+
+        let publisher = Publisher::new(address).unwrap();
+        let subscriber = Subscriber::new(address).unwrap();
+        subscriber.subscribe("").unwrap();
+        thread::sleep(Duration::from_millis(200));
+
+        // Long message first so a shorter one would expose stale bytes
+        let messages = [
+            Message::new("long", json!({"data": "x".repeat(256)})),
+            Message::new("short", json!({"n": 1})),
+            Message::new("mid", json!({"data": "y".repeat(32)})),
+        ];
+        for msg in &messages {
+            publisher.publish(msg).unwrap();
+        }
+
+        let mut buf = Vec::new();
+        for expected in &messages {
+            let len = subscriber.receive_into(&mut buf).unwrap();
+            assert_eq!(len, buf.len());
+            assert_eq!(buf, expected.to_bytes().unwrap());
+
+            let decoded = Message::from_bytes(&buf).unwrap();
+            assert_eq!(decoded.topic, expected.topic);
+            assert_eq!(decoded.payload, expected.payload);
+        }
+
+        // A topic-framed message yields its body, not the topic frame
+        let framed = Message::new("framed", json!([1, 2, 3]));
+        publisher.publish_filtered(&framed).unwrap();
+        publisher.publish(&messages[1]).unwrap();
+        subscriber.receive_into(&mut buf).unwrap();
+        assert_eq!(buf, framed.to_bytes().unwrap());
+        subscriber.receive_into(&mut buf).unwrap();
+        assert_eq!(buf, messages[1].to_bytes().unwrap());
+    }
+
+    #[test]
+    fn test_stream_large_payload() {
+        let address = "tcp://127.0.0.1:15569";
+
+        let publisher = Publisher::new(address).unwrap();
+        let subscriber = Subscriber::new(address).unwrap();
+        subscriber.subscribe("file").unwrap();
+        thread::sleep(Duration::from_millis(200));
+
+        let data: Vec<u8> = (0..5 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let mut reader = data.as_slice();
+        publisher
+            .publish_stream("file", &mut reader, 64 * 1024)
+            .unwrap();
+
+        let mut received = Vec::new();
+        let topic = subscriber.receive_stream(&mut received).unwrap();
+        assert_eq!(topic, "file");
+        assert_eq!(received.len(), data.len());
+        assert!(received == data);
+    }
+
+    #[test]
+    fn test_stream_aborted_by_failing_reader_is_an_error() {
+        struct FailingReader {
+            chunks_left: usize,
+        }
+        impl Read for FailingReader {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                if self.chunks_left == 0 {
+                    return Err(std::io::Error::other("disk went away"));
+                }
+                self.chunks_left -= 1;
+                buf.fill(7);
+                Ok(buf.len())
+            }
+        }
+
+        let pair = TestPair::pubsub().unwrap();
+        let sent = pair
+            .sender
+            .publish_stream("file", &mut FailingReader { chunks_left: 2 }, 16);
+        assert!(matches!(sent, Err(OxideError::Io(_))));
+
+        let mut received = Vec::new();
+        match pair.receiver.receive_stream(&mut received) {
+            Err(OxideError::Io(e)) => assert_eq!(e.kind(), ErrorKind::UnexpectedEof),
+            other => panic!("expected an aborted stream, got {:?}", other),
+        }
+        assert_eq!(received, [7; 32]);
+
+        // The socket is usable for the next stream
+        pair.sender
+            .publish_stream("file", &mut &b"whole"[..], 16)
+            .unwrap();
+        received.clear();
+        assert_eq!(pair.receiver.receive_stream(&mut received).unwrap(), "file");
+        assert_eq!(received, b"whole");
+    }
+
+    #[test]
+    fn test_receive_topic_then_skips_rejected() {
+        let address = "tcp://127.0.0.1:15574";
+
+        let publisher = Publisher::new(address).unwrap();
+        let subscriber = Subscriber::new(address).unwrap();
+        subscriber.subscribe("").unwrap();
+        thread::sleep(Duration::from_millis(200));
+
+        // The rejected body is not JSON, so parsing it would fail
+        publisher.publish_raw("noise", b"not a message").unwrap();
+        let wanted = Message::new("alerts", json!({"level": "high"}));
+        publisher.publish_filtered(&wanted).unwrap();
+
+        let rejected = subscriber
+            .receive_topic_then(|topic| topic == "alerts")
+            .unwrap();
+        assert!(rejected.is_none());
+
+        let accepted = subscriber
+            .receive_topic_then(|topic| topic == "alerts")
+            .unwrap()
+            .unwrap();
+        assert_eq!(accepted.topic, "alerts");
+        assert_eq!(accepted.payload["level"], "high");
+    }
+
+    #[test]
+    fn test_filtered_publish_matches_topic_prefix() {
+        let address = "tcp://127.0.0.1:25574";
+
+        let publisher = Publisher::new(address).unwrap();
+        let subscriber = Subscriber::new(address).unwrap();
+        subscriber.subscribe("sensor").unwrap();
+        thread::sleep(Duration::from_millis(200));
+
+        publisher
+            .publish_filtered(&Message::new("status", json!({})))
+            .unwrap();
+        publisher
+            .publish_filtered(&Message::new("sensor.temp", json!({"c": 21})))
+            .unwrap();
+
+        let msg = subscriber.receive_timeout(1000).unwrap().unwrap();
+        assert_eq!(msg.topic, "sensor.temp");
+        assert!(subscriber.receive_timeout(100).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_subscribe_topic_pattern() {
+        let address = "tcp://127.0.0.1:15576";
+
+        let publisher = Publisher::new(address).unwrap();
+        let subscriber = Subscriber::new(address).unwrap();
+        let pattern = Topic::new("sensors.*");
+        subscriber.subscribe_topic(&pattern).unwrap();
+        thread::sleep(Duration::from_millis(200));
+
+        publisher
+            .publish_filtered(&Message::new("alerts.fire", json!({})))
+            .unwrap();
+        publisher
+            .publish_filtered(&Message::new("sensors.temp", json!({"c": 20})))
+            .unwrap();
+
+        let msg = subscriber.receive_timeout(1000).unwrap().unwrap();
+        assert_eq!(msg.topic, "sensors.temp");
+        assert!(Topic::new(msg.topic).matches(pattern.as_str()));
+        assert!(subscriber.receive_timeout(100).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_receive_min_version() {
+        let address = "tcp://127.0.0.1:15579";
+
+        let publisher = Publisher::new(address).unwrap();
+        let subscriber = Subscriber::new(address).unwrap();
+        subscriber.subscribe("").unwrap();
+        thread::sleep(Duration::from_millis(200));
+
+        publisher
+            .publish(&Message::new("reading", json!({"seq": 1})))
+            .unwrap();
+        publisher
+            .publish(&Message::new_versioned("reading", json!({"seq": 2}), 1))
+            .unwrap();
+        publisher
+            .publish(&Message::new_versioned("reading", json!({"seq": 3}), 3))
+            .unwrap();
+        publisher
+            .publish(&Message::new_versioned("reading", json!({"seq": 4}), 2))
+            .unwrap();
+
+        let msg = subscriber.receive_min_version(2).unwrap();
+        assert_eq!((msg.payload["seq"].as_i64(), msg.version), (Some(3), 3));
+        let msg = subscriber.receive_min_version(2).unwrap();
+        assert_eq!((msg.payload["seq"].as_i64(), msg.version), (Some(4), 2));
+        assert!(subscriber.receive_timeout(100).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_connecting_publishers_binding_subscriber() {
+        let address = "tcp://127.0.0.1:15586";
+
+        let subscriber = Subscriber::new_bind(address).unwrap();
+        subscriber.subscribe("").unwrap();
+        let publishers: Vec<_> = (0..2)
+            .map(|_| Publisher::new_connect(address).unwrap())
+            .collect();
+
+        // The first publish on a new connection is dropped, so retry. Retries
+        // of an earlier publisher may still be arriving, so skip messages
+        // from other sources until this one's shows up.
+        for (n, publisher) in publishers.iter().enumerate() {
+            let msg = Message::new("metrics", json!({"source": n}));
+            let arrived = (0..20).any(|_| {
+                publisher.publish(&msg).unwrap();
+                while let Some(received) = subscriber.receive_timeout(100).unwrap() {
+                    if received.payload["source"] == n {
+                        return true;
+                    }
+                }
+                false
+            });
+            assert!(arrived, "nothing arrived from publisher {}", n);
+        }
+    }
+
+    #[test]
+    fn test_subscriptions_survive_socket_recreation() {
+        let address = "tcp://127.0.0.1:15591";
+
+        let publisher = Publisher::new(address).unwrap();
+        let subscriber = Subscriber::new(address)
+            .unwrap()
+            .with_subscriptions(SubscriptionSet::new())
+            .unwrap();
+        subscriber.subscribe("alerts").unwrap();
+        subscriber.subscribe("sensors").unwrap();
+        subscriber.unsubscribe("sensors").unwrap();
+        let subscriptions = subscriber.subscriptions().unwrap();
+        assert_eq!(subscriptions.iter().collect::<Vec<_>>(), ["alerts"]);
+        drop(subscriber);
+
+        let subscriber = Subscriber::new(address)
+            .unwrap()
+            .with_subscriptions(subscriptions)
+            .unwrap();
+
+        // Publish both topics until the new connection delivers
+        let received = (0..50).find_map(|_| {
+            publisher
+                .publish_filtered(&Message::new("sensors.temp", json!({})))
+                .unwrap();
+            publisher
+                .publish_filtered(&Message::new("alerts.fire", json!({})))
+                .unwrap();
+            subscriber.receive_timeout(100).unwrap()
+        });
+        assert_eq!(received.unwrap().topic, "alerts.fire");
+        while let Some(msg) = subscriber.receive_timeout(100).unwrap() {
+            assert_eq!(msg.topic, "alerts.fire");
+        }
+        assert!(subscriber.subscriptions().unwrap().contains("alerts"));
+    }
+
+    #[test]
+    fn test_tap_sees_published_messages() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let publisher = Publisher::with_tap("tcp://127.0.0.1:15595", tx).unwrap();
+        let subscriber = Subscriber::new("tcp://127.0.0.1:15595").unwrap();
+        subscriber.subscribe("").unwrap();
+        thread::sleep(Duration::from_millis(200));
+
+        for n in 0..3 {
+            publisher
+                .publish(&Message::new("log", json!({"n": n})))
+                .unwrap();
+        }
+
+        for n in 0..3 {
+            let remote = subscriber.receive_timeout(1000).unwrap().unwrap();
+            let local = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+            assert_eq!(remote.payload["n"], n);
+            assert_eq!(local.payload, remote.payload);
+        }
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_wait_connected_returns_once_connected() {
+        let address = "tcp://127.0.0.1:15597";
+        let _publisher = Publisher::new(address).unwrap();
+
+        let started = std::time::Instant::now();
+        Subscriber::new_wait_connected(address, 5000).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_wait_connected_times_out_on_dead_endpoint() {
+        let started = std::time::Instant::now();
+        let result = Subscriber::new_wait_connected("tcp://127.0.0.1:25597", 200);
+        match result {
+            Err(OxideError::Timeout(message)) => assert!(message.contains("127.0.0.1:25597")),
+            _ => panic!("expected a timeout"),
+        }
+        assert!(started.elapsed() >= Duration::from_millis(200));
+    }
 }
@@ -0,0 +1,285 @@
+//! Task queue with acknowledgements and redelivery
+//!
+//! A PULL socket takes a task off the queue as soon as it arrives, so a
+//! worker that crashes mid-task loses it. A [`ReliableQueue`] instead keeps
+//! every task it has handed out until the [`Worker`] acknowledges it with
+//! [`Worker::ack`]. A task that is not acknowledged within the visibility
+//! timeout is handed to the next worker that asks for one.
+//!
+//! Delivery is at least once: a worker that is merely slow, or whose ack
+//! is lost, can see its task processed a second time elsewhere. Make task
+//! handling idempotent, or key it on [`Task::id`], and pick a visibility
+//! timeout comfortably above the longest expected processing time.
+//!
+//! The queue binds a ROUTER socket and workers connect DEALER sockets.
+//! Workers send `["", "READY"]` to ask for a task and `["", "ACK", id]`
+//! when done; the queue answers a `READY` with `["", "TASK", id, body]`.
+//! Queue state lives in memory on a background thread and is lost when the
+//! queue is dropped.
+
+use crate::builder::sealed::Pattern;
+use crate::error::{OxideError, Result};
+use crate::message::Message;
+use crate::patterns::{Dealer, Router};
+use std::cell::Cell;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How often the queue thread checks for new tasks, expired tasks and
+/// whether it should stop
+const QUEUE_POLL_MS: i64 = 10;
+
+const READY: &[u8] = b"READY";
+const ACK: &[u8] = b"ACK";
+const TASK: &[u8] = b"TASK";
+
+/// A task handed to a [`Worker`]
+#[derive(Debug, Clone)]
+pub struct Task {
+    /// Id to pass to [`Worker::ack`]
+    pub id: String,
+    /// The task itself
+    pub message: Message,
+}
+
+struct InFlight {
+    body: Vec<u8>,
+    deadline: Instant,
+}
+
+/// Queue state, owned by the background thread
+struct Dispatcher {
+    router: Router,
+    visibility_timeout: Duration,
+    pending: VecDeque<(String, Vec<u8>)>,
+    ready: VecDeque<Vec<u8>>,
+    in_flight: HashMap<String, InFlight>,
+}
+
+impl Dispatcher {
+    fn run(mut self, tasks: Receiver<(String, Vec<u8>)>, stop: &AtomicBool) -> Result<()> {
+        while !stop.load(Ordering::Relaxed) {
+            self.pending.extend(tasks.try_iter());
+            let readable = Pattern::socket(&self.router)
+                .poll(zmq::POLLIN, QUEUE_POLL_MS)
+                .map_err(OxideError::Receive)?;
+            if readable > 0 {
+                self.read_requests()?;
+            }
+            self.requeue_expired();
+            self.dispatch()?;
+        }
+        Ok(())
+    }
+
+    fn read_requests(&mut self) -> Result<()> {
+        loop {
+            let frames = match Pattern::socket(&self.router).recv_multipart(zmq::DONTWAIT) {
+                Ok(frames) => frames,
+                Err(zmq::Error::EAGAIN) => return Ok(()),
+                Err(e) => return Err(OxideError::Receive(e)),
+            };
+            match frames.as_slice() {
+                [worker, _, kind] if kind == READY => self.ready.push_back(worker.clone()),
+                [_, _, kind, id] if kind == ACK => {
+                    self.in_flight.remove(String::from_utf8_lossy(id).as_ref());
+                }
+                // Anything else is not from a `Worker`; ignore it
+                _ => {}
+            }
+        }
+    }
+
+    fn requeue_expired(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<String> = self
+            .in_flight
+            .iter()
+            .filter(|(_, task)| task.deadline <= now)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in expired {
+            if let Some(task) = self.in_flight.remove(&id) {
+                self.pending.push_front((id, task.body));
+            }
+        }
+    }
+
+    fn dispatch(&mut self) -> Result<()> {
+        while !self.pending.is_empty() {
+            let Some(worker) = self.ready.pop_front() else {
+                break;
+            };
+            let (id, body) = self.pending.pop_front().expect("pending is not empty");
+            Pattern::socket(&self.router)
+                .send_multipart([worker.as_slice(), &[], TASK, id.as_bytes(), &body], 0)
+                .map_err(OxideError::Send)?;
+            self.in_flight.insert(
+                id,
+                InFlight {
+                    body,
+                    deadline: Instant::now() + self.visibility_timeout,
+                },
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Queue handing tasks to [`Worker`]s until they acknowledge them
+pub struct ReliableQueue {
+    tasks: Sender<(String, Vec<u8>)>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<Result<()>>>,
+}
+
+impl ReliableQueue {
+    /// Bind the queue to `address`
+    ///
+    /// Tasks not acknowledged within `visibility_timeout_ms` of being
+    /// handed out are queued again.
+    pub fn new(address: &str, visibility_timeout_ms: u64) -> Result<Self> {
+        let router = Router::new(address)?;
+        let (tasks, received) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || {
+                let dispatcher = Dispatcher {
+                    router,
+                    visibility_timeout: Duration::from_millis(visibility_timeout_ms),
+                    pending: VecDeque::new(),
+                    ready: VecDeque::new(),
+                    in_flight: HashMap::new(),
+                };
+                dispatcher.run(received, &stop)
+            })
+        };
+        Ok(Self {
+            tasks,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Queue a task, returning the id it will be acknowledged with
+    pub fn push(&self, task: &Message) -> Result<String> {
+        let id = uuid::Uuid::new_v4().to_string();
+        self.tasks
+            .send((id.clone(), task.to_bytes()?))
+            .map_err(|_| OxideError::Connection("reliable queue has stopped".to_string()))?;
+        Ok(id)
+    }
+}
+
+impl Drop for ReliableQueue {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Worker taking tasks from a [`ReliableQueue`]
+pub struct Worker {
+    dealer: Dealer,
+    asked: Cell<bool>,
+}
+
+impl Worker {
+    /// Connect to the queue at `address`
+    pub fn new(address: &str) -> Result<Self> {
+        Ok(Self {
+            dealer: Dealer::new(address)?,
+            asked: Cell::new(false),
+        })
+    }
+
+    /// Take the next task (blocking)
+    pub fn pull(&self) -> Result<Task> {
+        loop {
+            if let Some(task) = self.pull_timeout(-1)? {
+                return Ok(task);
+            }
+        }
+    }
+
+    /// Take the next task, waiting at most `timeout_ms`
+    ///
+    /// After a timeout the request for a task stays open, so the next call
+    /// picks up a task that arrives in between.
+    pub fn pull_timeout(&self, timeout_ms: i32) -> Result<Option<Task>> {
+        let socket = self.dealer.socket();
+        if !self.asked.get() {
+            socket
+                .send_multipart([&[] as &[u8], READY], 0)
+                .map_err(OxideError::Send)?;
+            self.asked.set(true);
+        }
+        if socket
+            .poll(zmq::POLLIN, timeout_ms as i64)
+            .map_err(OxideError::Receive)?
+            == 0
+        {
+            return Ok(None);
+        }
+
+        let frames = socket.recv_multipart(0).map_err(OxideError::Receive)?;
+        self.asked.set(false);
+        match frames.as_slice() {
+            [_, kind, id, body] if kind == TASK => Ok(Some(Task {
+                id: String::from_utf8_lossy(id).into_owned(),
+                message: Message::from_bytes(body)?,
+            })),
+            _ => Err(OxideError::Protocol(format!(
+                "expected [\"\", TASK, id, body] from reliable queue, got {} frames",
+                frames.len()
+            ))),
+        }
+    }
+
+    /// Mark the task `task_id` as done so it is not handed out again
+    pub fn ack(&self, task_id: &str) -> Result<()> {
+        self.dealer
+            .socket()
+            .send_multipart([&[] as &[u8], ACK, task_id.as_bytes()], 0)
+            .map_err(OxideError::Send)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_unacked_task_is_redispatched() {
+        let address = "tcp://127.0.0.1:15609";
+        let queue = ReliableQueue::new(address, 300).unwrap();
+        let id = queue
+            .push(&Message::new("resize", json!({"image": 7})))
+            .unwrap();
+
+        // This worker takes the task and "crashes" without acknowledging it
+        let crashed = Worker::new(address).unwrap();
+        let task = crashed.pull_timeout(2000).unwrap().unwrap();
+        assert_eq!(task.id, id);
+        let handed_out = Instant::now();
+        drop(crashed);
+
+        let worker = Worker::new(address).unwrap();
+        let task = worker.pull_timeout(3000).unwrap().unwrap();
+        assert_eq!(task.id, id);
+        assert_eq!(task.message.payload, json!({"image": 7}));
+        assert!(handed_out.elapsed() >= Duration::from_millis(250));
+        worker.ack(&task.id).unwrap();
+
+        // Acknowledged tasks are not handed out again
+        assert!(worker.pull_timeout(600).unwrap().is_none());
+    }
+}
@@ -0,0 +1,135 @@
+//! Publisher that replays recent history to new subscribers
+//!
+//! PUB/SUB only delivers messages published after a subscription reaches
+//! the publisher. A [`ReplayPublisher`] keeps the last `history_len`
+//! messages of every topic and, when a subscription arrives, republishes
+//! the history of each topic matching the subscribed prefix, in the order
+//! the messages were first published.
+//!
+//! Subscriptions are read from the underlying XPUB socket whenever
+//! [`ReplayPublisher::publish`] or [`ReplayPublisher::replay_pending`] is
+//! called, so a publisher that goes quiet should call `replay_pending` in
+//! its loop. A replay is an ordinary publish: every subscriber matching the
+//! topic receives it, including those that already saw the messages live.
+//!
+//! Memory grows with the number of distinct topics times `history_len`
+//! messages; topics are never forgotten, so publishers with unbounded topic
+//! names (one per request id, say) should not use this.
+
+use crate::endpoint::Endpoint;
+use crate::error::{OxideError, Result};
+use crate::message::Message;
+use crate::patterns::XPublisher;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+
+/// Publisher that keeps a bounded per-topic history for late subscribers
+pub struct ReplayPublisher {
+    publisher: XPublisher,
+    history_len: usize,
+    history: RefCell<HashMap<String, VecDeque<(u64, Message)>>>,
+    published: Cell<u64>,
+}
+
+impl ReplayPublisher {
+    /// Create a publisher that binds to `address` and keeps the last
+    /// `history_len` messages of each topic
+    pub fn new(address: impl Into<Endpoint>, history_len: usize) -> Result<Self> {
+        if history_len == 0 {
+            return Err(OxideError::Configuration(
+                "replay history must hold at least one message".to_string(),
+            ));
+        }
+        let publisher = XPublisher::new(address)?;
+        // Without verbose mode only the first subscriber to a topic is seen
+        publisher.set_verbose(true)?;
+        Ok(Self {
+            publisher,
+            history_len,
+            history: RefCell::new(HashMap::new()),
+            published: Cell::new(0),
+        })
+    }
+
+    /// The underlying publisher
+    pub fn publisher(&self) -> &XPublisher {
+        &self.publisher
+    }
+
+    /// Publish a message and add it to its topic's history
+    ///
+    /// Subscriptions that arrived since the last call are replayed first.
+    /// Messages are sent with their topic as a leading frame, see
+    /// `Publisher::publish_filtered`.
+    pub fn publish(&self, message: &Message) -> Result<()> {
+        self.replay_pending(0)?;
+        self.publisher.publish_filtered(message)?;
+
+        let sequence = self.published.get();
+        self.published.set(sequence + 1);
+        let mut history = self.history.borrow_mut();
+        let topic = history.entry(message.topic.clone()).or_default();
+        if topic.len() == self.history_len {
+            topic.pop_front();
+        }
+        topic.push_back((sequence, message.clone()));
+        Ok(())
+    }
+
+    /// Replay history for new subscriptions, waiting at most `timeout_ms`
+    /// for the first one
+    ///
+    /// Returns how many messages were republished.
+    pub fn replay_pending(&self, timeout_ms: i32) -> Result<usize> {
+        let mut replayed = 0;
+        let mut wait_ms = timeout_ms;
+        while let Some(subscription) = self.publisher.recv_subscription_timeout(wait_ms)? {
+            if subscription.subscribe {
+                replayed += self.replay(&subscription.topic)?;
+            }
+            wait_ms = 0;
+        }
+        Ok(replayed)
+    }
+
+    /// Republish the history of every topic starting with `prefix`
+    fn replay(&self, prefix: &str) -> Result<usize> {
+        let history = self.history.borrow();
+        let mut messages: Vec<&(u64, Message)> = history
+            .iter()
+            .filter(|(topic, _)| topic.starts_with(prefix))
+            .flat_map(|(_, messages)| messages)
+            .collect();
+        messages.sort_by_key(|(sequence, _)| *sequence);
+        for (_, message) in &messages {
+            self.publisher.publish_filtered(message)?;
+        }
+        Ok(messages.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patterns::Subscriber;
+    use serde_json::json;
+
+    #[test]
+    fn test_late_subscriber_receives_last_messages_in_order() {
+        let address = "tcp://127.0.0.1:15622";
+        let publisher = ReplayPublisher::new(address, 3).unwrap();
+        for i in 0..5 {
+            publisher.publish(&Message::new("ticks", json!(i))).unwrap();
+        }
+
+        let subscriber = Subscriber::new(address).unwrap();
+        subscriber.subscribe("ticks").unwrap();
+        assert_eq!(publisher.replay_pending(2000).unwrap(), 3);
+
+        let payloads: Vec<_> = (0..3)
+            .map(|_| subscriber.receive_timeout(1000).unwrap().unwrap().payload)
+            .collect();
+        assert_eq!(payloads, [json!(2), json!(3), json!(4)]);
+        assert!(subscriber.receive_timeout(100).unwrap().is_none());
+    }
+}
@@ -1,98 +1,338 @@
 //! Request/Reply messaging pattern
 
+use crate::builder::{
+    impl_affinity, impl_bind, impl_buffers, impl_connect_timeout, impl_frames, impl_pattern,
+    impl_plain, impl_tcp_keepalive, new_socket,
+};
+use crate::endpoint::Endpoint;
 use crate::error::{OxideError, Result};
 use crate::message::Message;
+use crate::metrics::Counters;
+use crate::patterns::{recv_bytes_timeout, remaining_ms, send_bytes_timeout};
+#[cfg(feature = "signals")]
+use crate::signals::{ShutdownToken, SHUTDOWN_POLL_MS};
+use crate::stop::{StopHandle, Wake};
+use serde::Serialize;
+use std::time::{Duration, Instant};
 use zmq::{Context, Socket};
 
+/// What happened to a request, see [`Requester::request_diagnostic`]
+#[derive(Debug, Clone)]
+pub struct RequestOutcome {
+    /// Whether the request left the socket before the timeout
+    pub sent: bool,
+    /// The reply, if one arrived in time
+    pub reply: Option<Message>,
+    /// Time spent sending and waiting
+    pub elapsed: Duration,
+}
+
 /// Requester for the request/reply pattern (client side)
+///
+/// A REQ socket enforces a strict send/receive cycle. Sending a second
+/// request before the reply to the first has been received fails with
+/// `EFSM`, and so does receiving without a request outstanding. The cycle
+/// is also left half-way when waiting for a reply times out, for example
+/// in [`Requester::request_timeout`]: the socket still expects that reply
+/// and refuses new requests. [`Requester::reset`] recovers from both.
 pub struct Requester {
     socket: Socket,
+    counters: Counters,
+    context: Context,
 }
 
 impl Requester {
     /// Create a new requester that connects to the specified address
-    pub fn new(address: &str) -> Result<Self> {
-        let context = Context::new();
-        let socket = context.socket(zmq::REQ)?;
-        socket.connect(address)?;
-        Ok(Self { socket })
+    pub fn new(address: impl Into<Endpoint>) -> Result<Self> {
+        Self::builder().connect(address)
     }
 
     /// Send a request and wait for a reply
     pub fn request(&self, message: &Message) -> Result<Message> {
         let bytes = message.to_bytes()?;
-        self.socket
-            .send(&bytes, 0)
-            .map_err(|e| OxideError::Send(e.to_string()))?;
+        self.socket.send(&bytes, 0).map_err(OxideError::Send)?;
+        self.counters.record_sent();
 
-        let reply_bytes = self
-            .socket
-            .recv_bytes(0)
-            .map_err(|e| OxideError::Receive(e.to_string()))?;
+        let reply_bytes = self.socket.recv_bytes(0).map_err(OxideError::Receive)?;
+        self.counters.record_received();
         Message::from_bytes(&reply_bytes)
     }
 
+    /// Send `value` as the payload of a request on `topic` and wait for a
+    /// reply
+    ///
+    /// Fails with `OxideError::Serialization`, without sending, if `value`
+    /// cannot be represented as JSON.
+    pub fn request_value<T: Serialize>(&self, topic: &str, value: &T) -> Result<Message> {
+        self.request(&Message::from_value(topic, value)?)
+    }
+
     /// Send a request and wait for a reply with timeout
+    ///
+    /// Only the wait for this reply is bounded; the previous receive timeout
+    /// is put back before returning.
     pub fn request_timeout(&self, message: &Message, timeout_ms: i32) -> Result<Option<Message>> {
         let bytes = message.to_bytes()?;
-        self.socket
-            .send(&bytes, 0)
-            .map_err(|e| OxideError::Send(e.to_string()))?;
+        self.socket.send(&bytes, 0).map_err(OxideError::Send)?;
+        self.counters.record_sent();
 
-        self.socket
-            .set_rcvtimeo(timeout_ms)
-            .map_err(|e| OxideError::Configuration(e.to_string()))?;
+        match recv_bytes_timeout(&self.socket, timeout_ms)? {
+            Some(bytes) => {
+                self.counters.record_received();
+                Ok(Some(Message::from_bytes(&bytes)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Send a request and wait for a reply, bounding both steps
+    ///
+    /// A send that cannot complete within `send_timeout_ms` (for example
+    /// because no replier is connected to a requester built with
+    /// `immediate(true)`) fails with `OxideError::Send(zmq::Error::EAGAIN)`;
+    /// nothing was sent and the requester can be used again. A reply that
+    /// does not arrive within `recv_timeout_ms` fails with
+    /// `OxideError::Receive(zmq::Error::EAGAIN)`, after which the REQ socket
+    /// still expects that reply until [`Requester::reset`]. Both previous
+    /// timeouts are put back before returning.
+    pub fn request_timeout_full(
+        &self,
+        message: &Message,
+        send_timeout_ms: i32,
+        recv_timeout_ms: i32,
+    ) -> Result<Message> {
+        let bytes = message.to_bytes()?;
+        send_bytes_timeout(&self.socket, &bytes, send_timeout_ms)?;
+        self.counters.record_sent();
+
+        match recv_bytes_timeout(&self.socket, recv_timeout_ms)? {
+            Some(bytes) => {
+                self.counters.record_received();
+                Message::from_bytes(&bytes)
+            }
+            None => Err(OxideError::Receive(zmq::Error::EAGAIN)),
+        }
+    }
 
-        match self.socket.recv_bytes(0) {
-            Ok(reply_bytes) => Ok(Some(Message::from_bytes(&reply_bytes)?)),
-            Err(zmq::Error::EAGAIN) => Ok(None),
-            Err(e) => Err(OxideError::Receive(e.to_string())),
+    /// Send a request and wait for a reply, reporting how far it got
+    ///
+    /// `timeout_ms` bounds the send and the wait for the reply together.
+    /// A timeout is not an error: the outcome tells whether the request was
+    /// sent at all (`sent == false` means no replier took it, e.g. with
+    /// `immediate(true)` and no peer) or sent without a reply arriving in
+    /// time. After the latter the REQ socket still expects that reply and
+    /// needs a [`Requester::reset`].
+    pub fn request_diagnostic(&self, message: &Message, timeout_ms: i32) -> Result<RequestOutcome> {
+        let bytes = message.to_bytes()?;
+        let started = Instant::now();
+        let outcome = |sent, reply| RequestOutcome {
+            sent,
+            reply,
+            elapsed: started.elapsed(),
+        };
+
+        match send_bytes_timeout(&self.socket, &bytes, timeout_ms) {
+            Ok(()) => self.counters.record_sent(),
+            Err(OxideError::Send(zmq::Error::EAGAIN)) => return Ok(outcome(false, None)),
+            Err(e) => return Err(e),
+        }
+
+        let remaining = Duration::from_millis(timeout_ms.max(0) as u64)
+            .saturating_sub(started.elapsed())
+            .as_millis() as i32;
+        match recv_bytes_timeout(&self.socket, remaining)? {
+            Some(bytes) => {
+                self.counters.record_received();
+                Ok(outcome(true, Some(Message::from_bytes(&bytes)?)))
+            }
+            None => Ok(outcome(true, None)),
+        }
+    }
+
+    /// Send a request without waiting for the reply
+    ///
+    /// Collect the reply with [`Requester::await_reply`]. Until it has been
+    /// received the REQ socket refuses further requests.
+    pub fn send_request(&self, message: &Message) -> Result<()> {
+        let bytes = message.to_bytes()?;
+        self.socket.send(&bytes, 0).map_err(OxideError::Send)?;
+        self.counters.record_sent();
+        Ok(())
+    }
+
+    /// Wait for the reply to a request sent with
+    /// [`Requester::send_request`], until `deadline` or until `stop` is
+    /// stopped, whichever comes first
+    ///
+    /// Returns the reply, `Ok(None)` once the deadline passes, or
+    /// `OxideError::Cancelled` when stopped. A stop signalled while the
+    /// reply is waiting wins. After either of the last two the socket still
+    /// expects the reply: call this again to keep waiting, or
+    /// [`Requester::reset`] to give up on it.
+    pub fn await_reply(&self, deadline: Instant, stop: &StopHandle) -> Result<Option<Message>> {
+        let deadline = Some(deadline);
+        loop {
+            // Past the deadline, still check once for a reply already there
+            let wait_ms = remaining_ms(deadline).unwrap_or(0);
+            match stop.wait(&self.socket, i64::from(wait_ms))? {
+                Wake::Stopped => return Err(OxideError::Cancelled),
+                Wake::TimedOut if remaining_ms(deadline).is_none() => return Ok(None),
+                Wake::TimedOut => continue,
+                Wake::Readable => {}
+            }
+            match self.socket.recv_bytes(zmq::DONTWAIT) {
+                Ok(bytes) => {
+                    self.counters.record_received();
+                    return Message::from_bytes(&bytes).map(Some);
+                }
+                Err(zmq::Error::EAGAIN) => continue,
+                Err(e) => return Err(OxideError::Receive(e)),
+            }
         }
     }
+
+    /// Replace the REQ socket with a fresh one connected to the same
+    /// endpoint, leaving any request in progress behind
+    ///
+    /// The new socket reconnects to `options().last_endpoint` and takes
+    /// over the old socket's high-water marks, timeouts, linger, identity,
+    /// affinity, kernel buffer sizes, connect timeout, TCP keepalive
+    /// settings and PLAIN credentials; CURVE keys are not carried over. A reply to the
+    /// abandoned request is lost with the old socket.
+    pub fn reset(&mut self) -> Result<()> {
+        let endpoint = self.options()?.last_endpoint;
+        if endpoint.is_empty() {
+            return Err(OxideError::Configuration(
+                "requester has no endpoint to reconnect to".to_string(),
+            ));
+        }
+        let socket = new_socket(&self.context, zmq::REQ)?;
+        copy_options(&self.socket, &socket).map_err(OxideError::SocketOption)?;
+        socket.connect(&endpoint)?;
+        // Don't let a request stuck in the old socket delay its close
+        self.socket
+            .set_linger(0)
+            .map_err(OxideError::SocketOption)?;
+        self.socket = socket;
+        Ok(())
+    }
 }
 
+/// Copy the options a requester may have been built with onto `to`
+fn copy_options(from: &Socket, to: &Socket) -> zmq::Result<()> {
+    to.set_sndhwm(from.get_sndhwm()?)?;
+    to.set_rcvhwm(from.get_rcvhwm()?)?;
+    to.set_sndtimeo(from.get_sndtimeo()?)?;
+    to.set_rcvtimeo(from.get_rcvtimeo()?)?;
+    to.set_linger(from.get_linger()?)?;
+    to.set_immediate(from.is_immediate()?)?;
+    to.set_affinity(from.get_affinity()?)?;
+    to.set_sndbuf(from.get_sndbuf()?)?;
+    to.set_rcvbuf(from.get_rcvbuf()?)?;
+    to.set_connect_timeout(from.get_connect_timeout()?)?;
+    to.set_tcp_keepalive(from.get_tcp_keepalive()?)?;
+    to.set_tcp_keepalive_idle(from.get_tcp_keepalive_idle()?)?;
+    to.set_tcp_keepalive_cnt(from.get_tcp_keepalive_cnt()?)?;
+    to.set_tcp_keepalive_intvl(from.get_tcp_keepalive_intvl()?)?;
+    let identity = from.get_identity()?;
+    if !identity.is_empty() {
+        to.set_identity(&identity)?;
+    }
+    if from.get_mechanism()? == zmq::Mechanism::ZMQ_PLAIN && !from.is_plain_server()? {
+        let username = from.get_plain_username()?.unwrap_or_default();
+        let password = from.get_plain_password()?.unwrap_or_default();
+        to.set_plain_username(Some(&username))?;
+        to.set_plain_password(Some(&password))?;
+    }
+    Ok(())
+}
+
+impl_pattern!(Requester { context = Context::clone } => zmq::REQ, Replier => zmq::REP);
+impl_affinity!(Requester, Replier);
+impl_plain!(Requester, Replier);
+impl_buffers!(Requester, Replier);
+impl_bind!(Replier);
+impl_frames!(Requester, Replier);
+impl_connect_timeout!(Requester);
+impl_tcp_keepalive!(Requester);
+
 /// Replier for the request/reply pattern (server side)
+///
+/// A REP socket enforces the mirror image of the REQ cycle: it must
+/// receive a request before it can reply, and must reply before it can
+/// receive the next one; anything else fails with `EFSM`. A receive that
+/// times out receives nothing, so it moves the cycle on as little as it
+/// moves the timeout: the next step is still a receive.
 pub struct Replier {
     socket: Socket,
+    counters: Counters,
 }
 
 impl Replier {
     /// Create a new replier that binds to the specified address
-    pub fn new(address: &str) -> Result<Self> {
-        let context = Context::new();
-        let socket = context.socket(zmq::REP)?;
-        socket.bind(address)?;
-        Ok(Self { socket })
+    pub fn new(address: impl Into<Endpoint>) -> Result<Self> {
+        Self::builder().bind(address)
     }
 
     /// Receive a request (blocking)
     pub fn receive(&self) -> Result<Message> {
-        let bytes = self
-            .socket
-            .recv_bytes(0)
-            .map_err(|e| OxideError::Receive(e.to_string()))?;
+        let bytes = self.socket.recv_bytes(0).map_err(OxideError::Receive)?;
+        self.counters.record_received();
         Message::from_bytes(&bytes)
     }
 
     /// Receive a request with timeout
+    ///
+    /// The timeout only applies to this call; a later
+    /// [`Replier::receive`] blocks again. `Ok(None)` leaves no request to
+    /// answer, so do not [`Replier::reply`] after it; receive again.
     pub fn receive_timeout(&self, timeout_ms: i32) -> Result<Option<Message>> {
-        self.socket
-            .set_rcvtimeo(timeout_ms)
-            .map_err(|e| OxideError::Configuration(e.to_string()))?;
-
-        match self.socket.recv_bytes(0) {
-            Ok(bytes) => Ok(Some(Message::from_bytes(&bytes)?)),
-            Err(zmq::Error::EAGAIN) => Ok(None),
-            Err(e) => Err(OxideError::Receive(e.to_string())),
+        match recv_bytes_timeout(&self.socket, timeout_ms)? {
+            Some(bytes) => {
+                self.counters.record_received();
+                Ok(Some(Message::from_bytes(&bytes)?))
+            }
+            None => Ok(None),
         }
     }
 
-    /// Send a reply
+    /// Send a reply to the request received last
+    ///
+    /// Fails with `OxideError::Send(zmq::Error::EFSM)`, sending nothing, if
+    /// there is no request awaiting a reply. The socket stays usable.
     pub fn reply(&self, message: &Message) -> Result<()> {
         let bytes = message.to_bytes()?;
-        self.socket
-            .send(&bytes, 0)
-            .map_err(|e| OxideError::Send(e.to_string()))?;
+        self.socket.send(&bytes, 0).map_err(OxideError::Send)?;
+        self.counters.record_sent();
+        Ok(())
+    }
+
+    /// Reply with `value` as the payload of a message on `topic`
+    ///
+    /// Fails with `OxideError::Serialization`, without sending, if `value`
+    /// cannot be represented as JSON; a reply is then still owed.
+    pub fn reply_value<T: Serialize>(&self, topic: &str, value: &T) -> Result<()> {
+        self.reply(&Message::from_value(topic, value)?)
+    }
+}
+
+#[cfg(feature = "signals")]
+impl Replier {
+    /// Answer requests with `handler` until `token` is triggered
+    ///
+    /// The token is checked every [`SHUTDOWN_POLL_MS`] milliseconds while
+    /// idle. A handler error stops the loop and is returned without sending
+    /// a reply.
+    pub fn serve_until<F>(&self, token: &ShutdownToken, mut handler: F) -> Result<()>
+    where
+        F: FnMut(Message) -> Result<Message>,
+    {
+        while !token.is_shutdown() {
+            if let Some(request) = self.receive_timeout(SHUTDOWN_POLL_MS)? {
+                let reply = handler(request)?;
+                self.reply(&reply)?;
+            }
+        }
         Ok(())
     }
 }
@@ -134,4 +374,275 @@ mod tests {
 
         server_handle.join().unwrap();
     }
+
+    #[test]
+    fn test_request_timeout_full_distinguishes_timeouts() {
+        let address = "tcp://127.0.0.1:15592";
+        let request = Message::new("ping", json!(null));
+
+        // With immediate set and no replier, the send itself cannot complete
+        let requester = Requester::builder()
+            .immediate(true)
+            .connect(address)
+            .unwrap();
+        match requester.request_timeout_full(&request, 100, 1000) {
+            Err(OxideError::Send(zmq::Error::EAGAIN)) => {}
+            other => panic!("expected a send timeout, got {:?}", other.map(|m| m.topic)),
+        }
+
+        // A replier that never answers turns it into a reply timeout
+        let replier = Replier::new(address).unwrap();
+        match requester.request_timeout_full(&request, 2000, 100) {
+            Err(OxideError::Receive(zmq::Error::EAGAIN)) => {}
+            other => panic!("expected a reply timeout, got {:?}", other.map(|m| m.topic)),
+        }
+        assert_eq!(replier.receive().unwrap().topic, "ping");
+    }
+
+    #[test]
+    fn test_replier_bound_to_two_transports() {
+        let tcp = "tcp://127.0.0.1:15596";
+        let ipc_path = std::env::temp_dir().join("oxide-msg-15596.ipc");
+        let _ = std::fs::remove_file(&ipc_path);
+        let ipc = format!("ipc://{}", ipc_path.display());
+
+        let replier = Replier::new(tcp).unwrap();
+        replier.bind(&ipc).unwrap();
+        assert_eq!(replier.options().unwrap().last_endpoint, ipc);
+
+        let server = thread::spawn(move || {
+            for _ in 0..2 {
+                let request = replier.receive_timeout(5000).unwrap().unwrap();
+                replier.reply(&request).unwrap();
+            }
+        });
+
+        for address in [tcp, ipc.as_str()] {
+            let requester = Requester::new(address).unwrap();
+            let reply = requester
+                .request_timeout(&Message::new("echo", json!(address)), 5000)
+                .unwrap()
+                .unwrap();
+            assert_eq!(reply.payload, json!(address));
+        }
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_request_diagnostic_reports_unanswered_request() {
+        let address = "tcp://127.0.0.1:15605";
+        let replier = Replier::new(address).unwrap();
+        let requester = Requester::new(address).unwrap();
+
+        let outcome = requester
+            .request_diagnostic(&Message::new("ping", json!(null)), 200)
+            .unwrap();
+        assert!(outcome.sent);
+        assert!(outcome.reply.is_none());
+        assert!(outcome.elapsed > Duration::ZERO);
+        assert_eq!(replier.receive().unwrap().topic, "ping");
+    }
+
+    #[test]
+    fn test_reset_recovers_from_efsm() {
+        let address = "tcp://127.0.0.1:15634";
+        let replier = Replier::new(address).unwrap();
+        let mut requester = Requester::builder().rcvhwm(7).connect(address).unwrap();
+
+        // Give up on the first reply straight away, then send again
+        let first = Message::new("ping", json!(1));
+        assert!(requester.request_timeout(&first, 0).unwrap().is_none());
+        assert!(matches!(
+            requester.request(&Message::new("ping", json!(2))),
+            Err(OxideError::Send(zmq::Error::EFSM))
+        ));
+
+        requester.reset().unwrap();
+        assert_eq!(requester.options().unwrap().rcvhwm, 7);
+        let server = thread::spawn(move || {
+            // The first request may or may not have left the abandoned
+            // socket; a reply to it is dropped
+            loop {
+                let request = replier.receive_timeout(2000).unwrap().unwrap();
+                replier
+                    .reply(&Message::new("pong", request.payload.clone()))
+                    .unwrap();
+                if request.payload == json!(3) {
+                    return replier;
+                }
+            }
+        });
+        let reply = requester.request(&Message::new("ping", json!(3))).unwrap();
+        assert_eq!(reply.payload, json!(3));
+        drop(server.join().unwrap());
+    }
+
+    #[test]
+    fn test_reset_keeps_kernel_buffer_sizes() {
+        let mut requester = Requester::builder()
+            .sndbuf(128 * 1024)
+            .rcvbuf(256 * 1024)
+            .connect("tcp://127.0.0.1:25634")
+            .unwrap();
+        requester.reset().unwrap();
+        let options = requester.options().unwrap();
+        assert_eq!(options.sndbuf, 128 * 1024);
+        assert_eq!(options.rcvbuf, 256 * 1024);
+    }
+
+    #[test]
+    fn test_raw_socket_option_keeps_request_reply_working() {
+        let address = "tcp://127.0.0.1:15650";
+        let replier = Replier::new(address).unwrap();
+        let requester = Requester::new(address).unwrap();
+        requester
+            .with_raw_socket(|socket| socket.set_tos(0x10))
+            .unwrap();
+        replier
+            .with_raw_socket(|socket| socket.set_reconnect_ivl_max(5000))
+            .unwrap();
+        assert_eq!(
+            requester
+                .with_raw_socket(|socket| socket.get_tos())
+                .unwrap(),
+            0x10
+        );
+
+        let server = thread::spawn(move || {
+            let request = replier.receive_timeout(2000).unwrap().unwrap();
+            replier.reply(&request).unwrap();
+        });
+        let reply = requester
+            .request_timeout(&Message::new("ping", json!(1)), 2000)
+            .unwrap()
+            .unwrap();
+        assert_eq!(reply.payload, json!(1));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_replier_usable_after_receive_timeout() {
+        let pair = crate::test_support::TestPair::reqrep().unwrap();
+        let replier = pair.receiver;
+        assert!(replier.receive_timeout(50).unwrap().is_none());
+        assert_eq!(replier.options().unwrap().rcvtimeo, -1);
+        // Nothing was received, so there is nothing to reply to
+        assert!(matches!(
+            replier.reply(&Message::new("early", json!(null))),
+            Err(OxideError::Send(zmq::Error::EFSM))
+        ));
+
+        let server = thread::spawn(move || {
+            let request = replier.receive().unwrap();
+            replier.reply(&request).unwrap();
+            replier
+        });
+        let reply = pair
+            .sender
+            .request_timeout(&Message::new("ping", json!(1)), 2000)
+            .unwrap()
+            .unwrap();
+        assert_eq!(reply.payload, json!(1));
+        drop(server.join().unwrap());
+    }
+
+    #[test]
+    fn test_request_value_and_reply_value_send_struct_payloads() {
+        #[derive(serde::Serialize)]
+        struct Quote {
+            symbol: &'static str,
+            bid: u32,
+        }
+
+        let pair = crate::test_support::TestPair::reqrep().unwrap();
+        let replier = pair.receiver;
+        let server = thread::spawn(move || {
+            let request = replier.receive_timeout(2000).unwrap().unwrap();
+            assert_eq!(request.payload, json!({"symbol": "ACME"}));
+            replier
+                .reply_value(
+                    "quote",
+                    &Quote {
+                        symbol: "ACME",
+                        bid: 101,
+                    },
+                )
+                .unwrap();
+            replier
+        });
+
+        let reply = pair
+            .sender
+            .request_value("quote", &json!({"symbol": "ACME"}))
+            .unwrap();
+        assert_eq!(reply.payload, json!({"symbol": "ACME", "bid": 101}));
+        drop(server.join().unwrap());
+    }
+
+    #[test]
+    fn test_await_reply_returns_reply() {
+        let address = "tcp://127.0.0.1:15640";
+        let replier = Replier::new(address).unwrap();
+        let server = thread::spawn(move || {
+            let request = replier.receive_timeout(2000).unwrap().unwrap();
+            replier.reply(&request).unwrap();
+            replier
+        });
+        let requester = Requester::new(address).unwrap();
+        let stop = StopHandle::new().unwrap();
+
+        requester
+            .send_request(&Message::new("echo", json!(1)))
+            .unwrap();
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let reply = requester.await_reply(deadline, &stop).unwrap().unwrap();
+        assert_eq!(reply.payload, json!(1));
+        drop(server.join().unwrap());
+    }
+
+    #[test]
+    fn test_await_reply_gives_up_at_deadline() {
+        let address = "tcp://127.0.0.1:25640";
+        let replier = Replier::new(address).unwrap();
+        let requester = Requester::new(address).unwrap();
+        let stop = StopHandle::new().unwrap();
+
+        requester
+            .send_request(&Message::new("echo", json!(1)))
+            .unwrap();
+        let started = Instant::now();
+        let deadline = started + Duration::from_millis(100);
+        assert!(requester.await_reply(deadline, &stop).unwrap().is_none());
+        assert!(started.elapsed() >= Duration::from_millis(100));
+        // Received but never answered
+        assert_eq!(replier.receive().unwrap().topic, "echo");
+    }
+
+    #[test]
+    fn test_await_reply_is_cancelled_by_stop() {
+        let address = "tcp://127.0.0.1:25641";
+        let replier = Replier::new(address).unwrap();
+        let requester = Requester::new(address).unwrap();
+        let stop = StopHandle::new().unwrap();
+        let stopper = {
+            let stop = stop.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(100));
+                stop.stop().unwrap();
+            })
+        };
+
+        requester
+            .send_request(&Message::new("echo", json!(1)))
+            .unwrap();
+        let started = Instant::now();
+        let deadline = started + Duration::from_secs(10);
+        assert!(matches!(
+            requester.await_reply(deadline, &stop),
+            Err(OxideError::Cancelled)
+        ));
+        assert!(started.elapsed() < Duration::from_secs(2));
+        stopper.join().unwrap();
+        assert_eq!(replier.receive().unwrap().topic, "echo");
+    }
 }
@@ -0,0 +1,307 @@
+//! ROUTER/DEALER messaging with explicit routing
+//!
+//! A ROUTER socket prefixes every incoming message with a frame holding the
+//! sender's identity and uses that frame to pick the peer for outgoing
+//! messages, so a [`Router`] can answer many clients in any order.
+//!
+//! REQ peers put an empty delimiter frame between the routing frames and
+//! the body, and expect one back: a request arrives at the router as
+//! `[identity, "", body]`. DEALER peers send whatever frames they are given;
+//! [`Dealer`] follows the REQ convention so it can talk to a `Router`, a
+//! REP socket or a ROUTER that expects REQ clients. [`Router::recv_routed`]
+//! also accepts `[identity, body]` from DEALER peers that skip the
+//! delimiter, but [`Router::send_routed`] always writes one.
+
+use crate::builder::{
+    impl_affinity, impl_bind, impl_buffers, impl_connect_timeout, impl_frames, impl_pattern,
+    impl_plain, impl_tcp_keepalive,
+};
+use crate::endpoint::Endpoint;
+use crate::error::{OxideError, Result};
+use crate::message::Message;
+use crate::metrics::Counters;
+use crate::patterns::recv_bytes_timeout;
+use serde::Serialize;
+use zmq::Socket;
+
+/// A message together with the identity of the peer it came from or goes to
+#[derive(Debug, Clone)]
+pub struct RoutedMessage {
+    /// Routing identity of the peer
+    pub identity: Vec<u8>,
+    /// The message itself
+    pub message: Message,
+}
+
+/// Server side of the ROUTER/DEALER pattern
+pub struct Router {
+    socket: Socket,
+    counters: Counters,
+}
+
+impl_pattern!(Router => zmq::ROUTER, Dealer => zmq::DEALER);
+impl_affinity!(Router, Dealer);
+impl_plain!(Router, Dealer);
+impl_buffers!(Router, Dealer);
+impl_bind!(Router);
+impl_frames!(Router, Dealer);
+impl_connect_timeout!(Dealer);
+impl_tcp_keepalive!(Dealer);
+
+impl Router {
+    /// Create a new router that binds to the specified address
+    pub fn new(address: impl Into<Endpoint>) -> Result<Self> {
+        Self::builder().bind(address)
+    }
+
+    /// Let a new connection take over an identity that is already in use
+    /// (`ZMQ_ROUTER_HANDOVER`)
+    ///
+    /// When a client with a fixed identity reconnects, the router may not
+    /// have noticed yet that the old connection is dead. By default it then
+    /// keeps routing to the old connection and ignores the new one, so
+    /// replies vanish until the old connection times out. With handover
+    /// enabled the new connection replaces the old one straight away;
+    /// messages still queued for the old connection are lost.
+    pub fn set_handover(&self, enabled: bool) -> Result<()> {
+        self.socket
+            .set_router_handover(enabled)
+            .map_err(OxideError::SocketOption)
+    }
+
+    /// Send a message to the peer named by `routed.identity`
+    ///
+    /// Messages for unknown identities are silently dropped by ZeroMQ.
+    pub fn send_routed(&self, routed: &RoutedMessage) -> Result<()> {
+        let body = routed.message.to_bytes()?;
+        self.socket
+            .send_multipart([routed.identity.as_slice(), &[], &body], 0)
+            .map_err(OxideError::Send)?;
+        self.counters.record_sent();
+        Ok(())
+    }
+
+    /// Receive the next message and the identity of its sender (blocking)
+    pub fn recv_routed(&self) -> Result<RoutedMessage> {
+        let frames = self.socket.recv_multipart(0).map_err(OxideError::Receive)?;
+        self.counters.record_received();
+        Self::decode(frames)
+    }
+
+    /// Receive the next routed message, waiting at most `timeout_ms`
+    pub fn recv_routed_timeout(&self, timeout_ms: i32) -> Result<Option<RoutedMessage>> {
+        let Some(identity) = recv_bytes_timeout(&self.socket, timeout_ms)? else {
+            return Ok(None);
+        };
+        let mut frames = vec![identity];
+        while self.socket.get_rcvmore().map_err(OxideError::Receive)? {
+            frames.push(self.socket.recv_bytes(0).map_err(OxideError::Receive)?);
+        }
+        self.counters.record_received();
+        Self::decode(frames).map(Some)
+    }
+
+    fn decode(mut frames: Vec<Vec<u8>>) -> Result<RoutedMessage> {
+        let body = match frames.as_slice() {
+            [_, delimiter, _] if delimiter.is_empty() => frames.pop(),
+            [_, _] => frames.pop(),
+            _ => None,
+        };
+        match body {
+            Some(body) => Ok(RoutedMessage {
+                identity: frames.swap_remove(0),
+                message: Message::from_bytes(&body)?,
+            }),
+            None => Err(OxideError::Protocol(format!(
+                "expected [identity, \"\", body] from ROUTER, got {} frames",
+                frames.len()
+            ))),
+        }
+    }
+}
+
+/// Client side of the ROUTER/DEALER pattern
+///
+/// Unlike a `Requester`, a dealer may send several messages before reading
+/// any replies.
+pub struct Dealer {
+    socket: Socket,
+    counters: Counters,
+}
+
+impl Dealer {
+    /// Create a new dealer that connects to the specified address
+    pub fn new(address: impl Into<Endpoint>) -> Result<Self> {
+        Self::builder().connect(address)
+    }
+
+    /// Send an empty message to ROUTER peers as soon as a connection is
+    /// made (`ZMQ_PROBE_ROUTER`)
+    ///
+    /// The router then learns the dealer's identity before any real data
+    /// arrives, which lets it fill its routing table up front. The probe
+    /// reaches it as `[identity, ""]`: read it with
+    /// [`recv_frame`](Router::recv_frame), since [`Router::recv_routed`]
+    /// fails on a body that is not a message.
+    ///
+    /// Only connections made after the call are probed. `Dealer::new` and
+    /// [`SocketBuilder::connect`](crate::SocketBuilder::connect) connect
+    /// straight away, so enable it with
+    /// [`SocketBuilder::probe_router`](crate::SocketBuilder::probe_router)
+    /// to probe that first connection.
+    pub fn set_probe_router(&self, enabled: bool) -> Result<()> {
+        self.socket
+            .set_probe_router(enabled)
+            .map_err(OxideError::SocketOption)
+    }
+
+    /// Send a message, preceded by an empty delimiter frame
+    pub fn send(&self, message: &Message) -> Result<()> {
+        let body = message.to_bytes()?;
+        self.socket
+            .send_multipart([&[] as &[u8], &body], 0)
+            .map_err(OxideError::Send)?;
+        self.counters.record_sent();
+        Ok(())
+    }
+
+    /// Send `value` as the payload of a message on `topic`
+    ///
+    /// Fails with `OxideError::Serialization`, without sending, if `value`
+    /// cannot be represented as JSON.
+    pub fn send_value<T: Serialize>(&self, topic: &str, value: &T) -> Result<()> {
+        self.send(&Message::from_value(topic, value)?)
+    }
+
+    /// Receive the next message (blocking)
+    pub fn receive(&self) -> Result<Message> {
+        let frames = self.socket.recv_multipart(0).map_err(OxideError::Receive)?;
+        self.counters.record_received();
+        Self::decode(frames)
+    }
+
+    /// Receive the next message, waiting at most `timeout_ms`
+    pub fn receive_timeout(&self, timeout_ms: i32) -> Result<Option<Message>> {
+        let Some(first) = recv_bytes_timeout(&self.socket, timeout_ms)? else {
+            return Ok(None);
+        };
+        let mut frames = vec![first];
+        while self.socket.get_rcvmore().map_err(OxideError::Receive)? {
+            frames.push(self.socket.recv_bytes(0).map_err(OxideError::Receive)?);
+        }
+        self.counters.record_received();
+        Self::decode(frames).map(Some)
+    }
+
+    fn decode(frames: Vec<Vec<u8>>) -> Result<Message> {
+        match frames.as_slice() {
+            [delimiter, body] if delimiter.is_empty() => Message::from_bytes(body),
+            [body] => Message::from_bytes(body),
+            _ => Err(OxideError::Protocol(format!(
+                "expected [\"\", body] from DEALER, got {} frames",
+                frames.len()
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_routed_round_trip() {
+        let address = "tcp://127.0.0.1:15588";
+
+        let router = Router::new(address).unwrap();
+        let dealer = Dealer::new(address).unwrap();
+        dealer
+            .send(&Message::new("hello", json!({"n": 1})))
+            .unwrap();
+
+        let request = router.recv_routed_timeout(2000).unwrap().unwrap();
+        assert_eq!(request.message.topic, "hello");
+        assert!(!request.identity.is_empty());
+
+        router
+            .send_routed(&RoutedMessage {
+                identity: request.identity,
+                message: Message::new("reply", json!({"n": 2})),
+            })
+            .unwrap();
+        let reply = dealer.receive().unwrap();
+        assert_eq!(reply.topic, "reply");
+        assert_eq!(reply.payload, json!({"n": 2}));
+    }
+
+    #[test]
+    fn test_handover_routes_to_reconnected_identity() {
+        let address = "tcp://127.0.0.1:15606";
+        let router = Router::new(address).unwrap();
+        router.set_handover(true).unwrap();
+
+        let old = Dealer::builder()
+            .identity(b"client-1")
+            .connect(address)
+            .unwrap();
+        old.send(&Message::new("hello", json!(1))).unwrap();
+        let first = router.recv_routed_timeout(2000).unwrap().unwrap();
+        assert_eq!(first.identity, b"client-1");
+
+        // The old connection is still up when the client comes back
+        let new = Dealer::builder()
+            .identity(b"client-1")
+            .connect(address)
+            .unwrap();
+        new.send(&Message::new("hello", json!(2))).unwrap();
+        let second = router.recv_routed_timeout(2000).unwrap().unwrap();
+        assert_eq!(second.message.payload, json!(2));
+
+        router
+            .send_routed(&RoutedMessage {
+                identity: b"client-1".to_vec(),
+                message: Message::new("reply", json!(null)),
+            })
+            .unwrap();
+        assert_eq!(new.receive_timeout(2000).unwrap().unwrap().topic, "reply");
+    }
+
+    #[test]
+    fn test_probing_dealer_reveals_identity_on_connect() {
+        let address = "tcp://127.0.0.1:15648";
+        let router = Router::new(address).unwrap();
+        let dealer = Dealer::builder()
+            .identity(b"prober")
+            .probe_router(true)
+            .connect(address)
+            .unwrap();
+        dealer.set_probe_router(false).unwrap();
+
+        router.socket.set_rcvtimeo(2000).unwrap();
+        assert_eq!(router.recv_frame().unwrap(), b"prober");
+        assert!(router.has_more().unwrap());
+        assert!(router.recv_frame().unwrap().is_empty());
+        assert!(!router.has_more().unwrap());
+    }
+
+    #[test]
+    fn test_dealer_send_value_sends_struct_payload() {
+        #[derive(serde::Serialize)]
+        struct Order {
+            id: u64,
+            qty: u32,
+        }
+
+        let address = "tcp://127.0.0.1:15641";
+        let router = Router::new(address).unwrap();
+        let dealer = Dealer::new(address).unwrap();
+        dealer
+            .send_value("orders", &Order { id: 9, qty: 3 })
+            .unwrap();
+
+        let routed = router.recv_routed_timeout(2000).unwrap().unwrap();
+        assert_eq!(routed.message.topic, "orders");
+        assert_eq!(routed.message.payload, json!({"id": 9, "qty": 3}));
+    }
+}
@@ -0,0 +1,121 @@
+//! Wait on several receiving sockets at once
+//!
+//! [`select_receive`] polls a set of [`Receivable`] sockets and reads one
+//! message from the first that has one ready. When several are ready at
+//! the same time the lowest index wins, so a busy socket early in the list
+//! can starve later ones; rotate the slice between calls if that matters.
+
+use crate::builder::sealed::Pattern;
+use crate::error::{OxideError, Result};
+use crate::message::Message;
+use crate::patterns::{Puller, Replier, Subscriber};
+use zmq::Socket;
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// Pattern types that [`select_receive`] can wait on
+///
+/// Implemented by [`Subscriber`], [`Puller`] and [`Replier`]; the trait is
+/// sealed.
+pub trait Receivable: private::Sealed {
+    /// The socket to poll for incoming messages
+    #[doc(hidden)]
+    fn poll_socket(&self) -> &Socket;
+
+    /// Receive the next message once the socket is readable
+    #[doc(hidden)]
+    fn receive_ready(&self) -> Result<Message>;
+}
+
+impl private::Sealed for Subscriber {}
+
+impl Receivable for Subscriber {
+    fn poll_socket(&self) -> &Socket {
+        Pattern::socket(self)
+    }
+
+    fn receive_ready(&self) -> Result<Message> {
+        self.receive()
+    }
+}
+
+impl private::Sealed for Puller {}
+
+impl Receivable for Puller {
+    fn poll_socket(&self) -> &Socket {
+        Pattern::socket(self)
+    }
+
+    fn receive_ready(&self) -> Result<Message> {
+        self.pull()
+    }
+}
+
+impl private::Sealed for Replier {}
+
+impl Receivable for Replier {
+    fn poll_socket(&self) -> &Socket {
+        Pattern::socket(self)
+    }
+
+    fn receive_ready(&self) -> Result<Message> {
+        self.receive()
+    }
+}
+
+/// Receive from whichever of `sockets` has a message first
+///
+/// Waits up to `timeout_ms` (-1 waits forever) and returns the index of
+/// the socket in `sockets` together with its message, or `None` on
+/// timeout. Only one message is read per call. A `Replier` chosen here
+/// must reply before it can receive again.
+pub fn select_receive(
+    sockets: &[&dyn Receivable],
+    timeout_ms: i32,
+) -> Result<Option<(usize, Message)>> {
+    let mut items: Vec<_> = sockets
+        .iter()
+        .map(|socket| socket.poll_socket().as_poll_item(zmq::POLLIN))
+        .collect();
+    let ready = zmq::poll(&mut items, timeout_ms as i64).map_err(OxideError::Receive)?;
+    if ready == 0 {
+        return Ok(None);
+    }
+
+    match items.iter().position(|item| item.is_readable()) {
+        Some(index) => Ok(Some((index, sockets[index].receive_ready()?))),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patterns::Pusher;
+    use crate::test_support::TestPair;
+    use serde_json::json;
+
+    #[test]
+    fn test_select_returns_ready_socket() {
+        let pubsub = TestPair::pubsub().unwrap();
+        let address = "tcp://127.0.0.1:15603";
+        let puller = Puller::new_bind(address).unwrap();
+        let pusher = Pusher::new_connect(address).unwrap();
+        let sockets: [&dyn Receivable; 2] = [&pubsub.receiver, &puller];
+
+        assert!(select_receive(&sockets, 50).unwrap().is_none());
+
+        pusher.push(&Message::new("task", json!({"n": 1}))).unwrap();
+        let (index, message) = select_receive(&sockets, 2000).unwrap().unwrap();
+        assert_eq!((index, message.topic.as_str()), (1, "task"));
+
+        pubsub
+            .sender
+            .publish(&Message::new("news", json!(null)))
+            .unwrap();
+        let (index, message) = select_receive(&sockets, 2000).unwrap().unwrap();
+        assert_eq!((index, message.topic.as_str()), (0, "news"));
+    }
+}
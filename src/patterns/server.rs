@@ -0,0 +1,166 @@
+//! Request/reply server running on its own thread
+//!
+//! Pattern types own a ZeroMQ socket, which may be moved to another thread
+//! but not used from two at once, so they are `Send` but not `Sync`. A
+//! [`ServerHandle`] keeps a [`Replier`] on a dedicated thread and is itself
+//! `Send + Sync`, so it can be shared (for example in an `Arc`) by the
+//! parts of an application that need to stop the server or read its
+//! statistics.
+
+use crate::error::{OxideError, Result};
+use crate::message::Message;
+use crate::patterns::Replier;
+use serde_json::json;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// How often the server thread checks whether it should stop
+const SERVER_POLL_MS: i32 = 100;
+
+/// Request counts reported by [`ServerHandle::stats`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ServerStats {
+    /// Requests passed to the handler
+    pub handled: u64,
+    /// Requests that could not be decoded and got an `error` reply
+    pub rejected: u64,
+}
+
+#[derive(Default)]
+struct SharedState {
+    stop: AtomicBool,
+    handled: AtomicU64,
+    rejected: AtomicU64,
+}
+
+/// Handle to a [`Replier`] serving requests on a background thread
+///
+/// Dropping the handle stops the server as well, but discards its result;
+/// call [`ServerHandle::shutdown`] to see why it stopped.
+pub struct ServerHandle {
+    state: Arc<SharedState>,
+    thread: Option<JoinHandle<Result<()>>>,
+}
+
+impl ServerHandle {
+    /// Bind a replier to `address` and answer every request with `handler`
+    ///
+    /// A request that cannot be decoded is answered with an `error` message
+    /// so the requester is not left waiting. Any other socket error stops
+    /// the server and is returned by [`ServerHandle::shutdown`].
+    pub fn spawn<F>(address: &str, handler: F) -> Result<Self>
+    where
+        F: Fn(Message) -> Message + Send + 'static,
+    {
+        let replier = Replier::new(address)?;
+        let state = Arc::new(SharedState::default());
+        let thread = {
+            let state = Arc::clone(&state);
+            thread::spawn(move || Self::serve(&replier, &state, handler))
+        };
+        Ok(Self {
+            state,
+            thread: Some(thread),
+        })
+    }
+
+    fn serve<F>(replier: &Replier, state: &SharedState, handler: F) -> Result<()>
+    where
+        F: Fn(Message) -> Message,
+    {
+        while !state.stop.load(Ordering::Relaxed) {
+            match replier.receive_timeout(SERVER_POLL_MS) {
+                Ok(Some(request)) => {
+                    let reply = handler(request);
+                    state.handled.fetch_add(1, Ordering::Relaxed);
+                    replier.reply(&reply)?;
+                }
+                Ok(None) => {}
+                Err(OxideError::Serialization(e)) => {
+                    state.rejected.fetch_add(1, Ordering::Relaxed);
+                    replier.reply(&Message::new("error", json!({"message": e})))?;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Requests served so far
+    pub fn stats(&self) -> ServerStats {
+        ServerStats {
+            handled: self.state.handled.load(Ordering::Relaxed),
+            rejected: self.state.rejected.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Whether the server thread is still running
+    pub fn is_running(&self) -> bool {
+        self.thread
+            .as_ref()
+            .is_some_and(|thread| !thread.is_finished())
+    }
+
+    /// Stop the server and wait for its thread to finish
+    ///
+    /// The request being handled, if any, is answered first. Returns the
+    /// error that stopped the server early, if there was one.
+    pub fn shutdown(mut self) -> Result<()> {
+        self.stop()
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.state.stop.store(true, Ordering::Relaxed);
+        match self.thread.take() {
+            Some(thread) => thread.join().unwrap_or_else(|_| {
+                Err(OxideError::Connection("server thread panicked".to_string()))
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for ServerHandle {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patterns::Requester;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_server_handle_across_threads() {
+        assert_send_sync::<ServerHandle>();
+        let address = "tcp://127.0.0.1:15599";
+
+        let server = Arc::new(
+            ServerHandle::spawn(address, |request| Message::new("echo", request.payload)).unwrap(),
+        );
+
+        let client = {
+            let server = Arc::clone(&server);
+            thread::spawn(move || {
+                let requester = Requester::new(address).unwrap();
+                for n in 0..3 {
+                    let reply = requester
+                        .request_timeout(&Message::new("ping", json!(n)), 5000)
+                        .unwrap()
+                        .unwrap();
+                    assert_eq!(reply.payload, json!(n));
+                }
+                server.stats()
+            })
+        };
+        assert_eq!(client.join().unwrap().handled, 3);
+
+        assert!(server.is_running());
+        let server = Arc::into_inner(server).unwrap();
+        server.shutdown().unwrap();
+    }
+}
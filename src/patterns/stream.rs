@@ -0,0 +1,217 @@
+//! Length-prefixed messages over raw TCP connections
+//!
+//! A ZeroMQ STREAM socket talks plain TCP to peers that do not speak ZMTP.
+//! Every message it receives is two frames, `[identity, data]`, where the
+//! identity names the TCP connection and `data` is whatever bytes arrived,
+//! with no relation to how the peer wrote them. An empty `data` frame
+//! signals that a connection was opened or closed. Sending works the same
+//! way: the identity frame picks the connection, and sending an empty
+//! `data` frame closes it.
+//!
+//! [`Stream`] restores message boundaries with a simple framing: each
+//! message is a 4-byte big-endian length followed by that many bytes, in
+//! both directions. Bytes are buffered per connection until a whole message
+//! has arrived, and the buffer is discarded when the connection closes.
+
+use crate::builder::{
+    impl_affinity, impl_bind, impl_buffers, impl_pattern, impl_plain, impl_tcp_keepalive,
+};
+use crate::endpoint::Endpoint;
+use crate::error::{OxideError, Result};
+use crate::metrics::Counters;
+use crate::patterns::{deadline_after, recv_bytes_timeout, remaining_ms};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use zmq::Socket;
+
+/// Size of the length prefix in front of every message
+const PREFIX_LEN: usize = 4;
+
+/// Largest message accepted from a peer; longer lengths are treated as a
+/// protocol violation rather than buffered
+pub const MAX_FRAME_LEN: usize = 64 * 1024 * 1024;
+
+/// Raw TCP socket exchanging length-prefixed messages
+pub struct Stream {
+    socket: Socket,
+    counters: Counters,
+    buffers: RefCell<HashMap<Vec<u8>, Vec<u8>>>,
+}
+
+impl_pattern!(Stream { buffers } => zmq::STREAM);
+impl_affinity!(Stream);
+impl_plain!(Stream);
+impl_buffers!(Stream);
+impl_bind!(Stream);
+impl_tcp_keepalive!(Stream);
+
+impl Stream {
+    /// Create a new stream socket that binds to the specified TCP address
+    pub fn new(address: impl Into<Endpoint>) -> Result<Self> {
+        Self::builder().bind(address)
+    }
+
+    /// Send `bytes` with a length prefix to the connection named `identity`
+    pub fn send_framed(&self, identity: &[u8], bytes: &[u8]) -> Result<()> {
+        let len = u32::try_from(bytes.len())
+            .ok()
+            .filter(|&len| len as usize <= MAX_FRAME_LEN)
+            .ok_or_else(|| {
+                OxideError::Configuration(format!(
+                    "message of {} bytes exceeds the {} byte frame limit",
+                    bytes.len(),
+                    MAX_FRAME_LEN
+                ))
+            })?;
+        let mut frame = Vec::with_capacity(PREFIX_LEN + bytes.len());
+        frame.extend_from_slice(&len.to_be_bytes());
+        frame.extend_from_slice(bytes);
+        self.socket
+            .send_multipart([identity, &frame], 0)
+            .map_err(OxideError::Send)?;
+        self.counters.record_sent();
+        Ok(())
+    }
+
+    /// Receive the next complete message and the identity of its
+    /// connection (blocking)
+    pub fn recv_framed(&self) -> Result<(Vec<u8>, Vec<u8>)> {
+        loop {
+            if let Some(message) = self.recv_framed_timeout(-1)? {
+                return Ok(message);
+            }
+        }
+    }
+
+    /// Receive the next complete message, waiting at most `timeout_ms`
+    ///
+    /// A peer announcing a message longer than [`MAX_FRAME_LEN`] fails with
+    /// `OxideError::Protocol`: its connection is closed and its buffered
+    /// bytes are dropped, so the peer gets disconnected and has to connect
+    /// again to send anything more.
+    pub fn recv_framed_timeout(&self, timeout_ms: i32) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        let deadline = deadline_after(timeout_ms);
+        loop {
+            if let Some(message) = self.take_complete()? {
+                self.counters.record_received();
+                return Ok(Some(message));
+            }
+            let wait_ms = remaining_ms(deadline).unwrap_or(0);
+            let Some(identity) = recv_bytes_timeout(&self.socket, wait_ms)? else {
+                return Ok(None);
+            };
+            let data = self.socket.recv_bytes(0).map_err(OxideError::Receive)?;
+            self.buffer(identity, data);
+        }
+    }
+
+    /// Add bytes received on a connection to its buffer
+    fn buffer(&self, identity: Vec<u8>, data: Vec<u8>) {
+        let mut buffers = self.buffers.borrow_mut();
+        if data.is_empty() {
+            // Connect or disconnect notification
+            if buffers.remove(&identity).is_none() {
+                buffers.insert(identity, Vec::new());
+            }
+            return;
+        }
+        buffers
+            .entry(identity)
+            .or_default()
+            .extend_from_slice(&data);
+    }
+
+    /// Close the connection named `identity`
+    fn close(&self, identity: &[u8]) -> Result<()> {
+        self.socket
+            .send_multipart([identity, &[][..]], 0)
+            .map_err(OxideError::Send)
+    }
+
+    /// Take a complete message out of any connection's buffer
+    fn take_complete(&self) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        let mut buffers = self.buffers.borrow_mut();
+        for (identity, buffer) in buffers.iter_mut() {
+            let Some(prefix) = buffer.get(..PREFIX_LEN) else {
+                continue;
+            };
+            let len = u32::from_be_bytes(prefix.try_into().expect("prefix is 4 bytes")) as usize;
+            if len > MAX_FRAME_LEN {
+                let identity = identity.clone();
+                buffers.remove(&identity);
+                drop(buffers);
+                self.close(&identity)?;
+                return Err(OxideError::Protocol(format!(
+                    "peer announced a {} byte message, over the {} byte limit",
+                    len, MAX_FRAME_LEN
+                )));
+            }
+            if buffer.len() >= PREFIX_LEN + len {
+                let rest = buffer.split_off(PREFIX_LEN + len);
+                let message = std::mem::replace(buffer, rest).split_off(PREFIX_LEN);
+                return Ok(Some((identity.clone(), message)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_framed_exchange_with_plain_tcp_client() {
+        let address = "127.0.0.1:15619";
+        let stream = Stream::new(format!("tcp://{}", address)).unwrap();
+        let mut client = TcpStream::connect(address).unwrap();
+
+        // Split the message across writes so it arrives in pieces
+        client.write_all(&5u32.to_be_bytes()[..2]).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        client.write_all(&5u32.to_be_bytes()[2..]).unwrap();
+        client.write_all(b"hel").unwrap();
+        thread::sleep(Duration::from_millis(50));
+        client.write_all(b"lo").unwrap();
+        client.write_all(&[0, 0, 0, 1, b'!']).unwrap();
+
+        let (identity, message) = stream.recv_framed_timeout(2000).unwrap().unwrap();
+        assert_eq!(message, b"hello");
+        let (_, message) = stream.recv_framed_timeout(2000).unwrap().unwrap();
+        assert_eq!(message, b"!");
+
+        stream.send_framed(&identity, b"world!").unwrap();
+        let mut reply = [0u8; 10];
+        client.read_exact(&mut reply).unwrap();
+        assert_eq!(&reply[..4], &6u32.to_be_bytes());
+        assert_eq!(&reply[4..], b"world!");
+    }
+
+    #[test]
+    fn test_oversize_message_disconnects_peer() {
+        let address = "127.0.0.1:25619";
+        let stream = Stream::new(format!("tcp://{}", address)).unwrap();
+        let mut client = TcpStream::connect(address).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+
+        let announced = (MAX_FRAME_LEN as u32 + 1).to_be_bytes();
+        client.write_all(&announced).unwrap();
+        assert!(matches!(
+            stream.recv_framed_timeout(2000),
+            Err(OxideError::Protocol(_))
+        ));
+
+        // The close goes out while the stream keeps receiving, and the
+        // connection's buffer is gone for good
+        assert!(stream.recv_framed_timeout(100).unwrap().is_none());
+        let mut rest = Vec::new();
+        assert_eq!(client.read_to_end(&mut rest).unwrap(), 0);
+        assert!(stream.buffers.borrow().is_empty());
+    }
+}
@@ -0,0 +1,103 @@
+//! Record of a subscriber's active subscriptions
+//!
+//! ZeroMQ resends a SUB socket's subscriptions itself when a TCP connection
+//! drops and comes back, but they die with the socket. A [`SubscriptionSet`]
+//! remembers them so they can be applied to a replacement socket with
+//! [`SubscriptionSet::reapply`].
+//!
+//! Entries are the raw prefixes handed to `ZMQ_SUBSCRIBE`, so the set
+//! filters exactly like the socket did: `subscribe_topic` records
+//! [`Topic::zmq_prefix`](crate::topic::Topic::zmq_prefix) rather than the
+//! pattern, and overlapping prefixes such as `""` and `"sensors"` are kept
+//! separately. Like ZeroMQ, the set counts repeated subscriptions to the
+//! same prefix, and it takes as many unsubscribes to drop one.
+
+use crate::error::Result;
+use crate::patterns::Subscriber;
+use std::collections::BTreeMap;
+
+/// Active subscription prefixes with their subscribe counts
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SubscriptionSet {
+    prefixes: BTreeMap<String, usize>,
+}
+
+impl SubscriptionSet {
+    /// Create an empty set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a subscription to `prefix`
+    pub fn insert(&mut self, prefix: &str) {
+        *self.prefixes.entry(prefix.to_string()).or_default() += 1;
+    }
+
+    /// Record an unsubscribe from `prefix`
+    ///
+    /// Returns `false` if there was no subscription to remove.
+    pub fn remove(&mut self, prefix: &str) -> bool {
+        match self.prefixes.get_mut(prefix) {
+            Some(count) if *count > 1 => *count -= 1,
+            Some(_) => {
+                self.prefixes.remove(prefix);
+            }
+            None => return false,
+        }
+        true
+    }
+
+    /// Whether `prefix` is currently subscribed
+    pub fn contains(&self, prefix: &str) -> bool {
+        self.prefixes.contains_key(prefix)
+    }
+
+    /// Subscribed prefixes in sorted order, each listed once
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.prefixes.keys().map(String::as_str)
+    }
+
+    /// Number of distinct subscribed prefixes
+    pub fn len(&self) -> usize {
+        self.prefixes.len()
+    }
+
+    /// Whether the set has no subscriptions
+    pub fn is_empty(&self) -> bool {
+        self.prefixes.is_empty()
+    }
+
+    /// Subscribe `subscriber` to every recorded prefix, as often as recorded
+    ///
+    /// Goes through [`Subscriber::subscribe`], so a subscriber tracking its
+    /// own set records these subscriptions too.
+    pub fn reapply(&self, subscriber: &Subscriber) -> Result<()> {
+        for (prefix, count) in &self.prefixes {
+            for _ in 0..*count {
+                subscriber.subscribe(prefix)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_repeated_subscriptions() {
+        let mut set = SubscriptionSet::new();
+        set.insert("sensors");
+        set.insert("sensors");
+        set.insert("");
+        assert_eq!(set.iter().collect::<Vec<_>>(), ["", "sensors"]);
+
+        assert!(set.remove("sensors"));
+        assert!(set.contains("sensors"));
+        assert!(set.remove("sensors"));
+        assert!(!set.contains("sensors"));
+        assert!(!set.remove("sensors"));
+        assert_eq!(set.len(), 1);
+    }
+}
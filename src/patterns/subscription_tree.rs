@@ -0,0 +1,148 @@
+//! Hierarchical subscriptions with a configurable separator
+//!
+//! A [`SubscriptionTree`] treats topics as paths split on a separator such
+//! as `/` and answers whether a subscription covers a topic: `a/b` covers
+//! `a/b` and everything below it (`a/b/c`), but not the sibling `a/bc`. A
+//! subscription ending in the separator, such as `a/`, covers everything
+//! below `a` but not `a` itself, and the empty subscription covers every
+//! topic.
+//!
+//! A subscription covered by another one adds nothing at the socket:
+//! ZeroMQ matches byte prefixes, and a covering subscription is always a
+//! byte prefix of the ones it covers. [`SubscriptionTree::minimal`] lists
+//! the subscriptions that remain, and a [`Subscriber`] given a tree with
+//! [`Subscriber::with_subscription_tree`] keeps only those on its socket.
+//! The socket still filters by byte prefix, so it lets through a sibling
+//! such as `a/bc` for `a/b`; check [`SubscriptionTree::covers`] on receipt
+//! where that matters.
+//!
+//! Like ZeroMQ, the tree counts repeated subscriptions to the same topic,
+//! and it takes as many removals to drop one.
+//!
+//! [`Subscriber`]: crate::patterns::Subscriber
+//! [`Subscriber::with_subscription_tree`]: crate::patterns::Subscriber::with_subscription_tree
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Active subscriptions arranged by topic hierarchy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubscriptionTree {
+    separator: char,
+    topics: BTreeMap<String, usize>,
+}
+
+impl SubscriptionTree {
+    /// Create an empty tree splitting topics on `separator`
+    pub fn new(separator: char) -> Self {
+        Self {
+            separator,
+            topics: BTreeMap::new(),
+        }
+    }
+
+    /// The separator topics are split on
+    pub fn separator(&self) -> char {
+        self.separator
+    }
+
+    /// Record a subscription to `topic`
+    pub fn insert(&mut self, topic: &str) {
+        *self.topics.entry(topic.to_string()).or_default() += 1;
+    }
+
+    /// Record the removal of a subscription to `topic`
+    ///
+    /// Returns `false` if there was no subscription to remove.
+    pub fn remove(&mut self, topic: &str) -> bool {
+        match self.topics.get_mut(topic) {
+            Some(count) if *count > 1 => *count -= 1,
+            Some(_) => {
+                self.topics.remove(topic);
+            }
+            None => return false,
+        }
+        true
+    }
+
+    /// Whether some subscription covers `topic`
+    pub fn covers(&self, topic: &str) -> bool {
+        self.covering(topic)
+            .any(|ancestor| self.topics.contains_key(ancestor))
+    }
+
+    /// The fewest ZeroMQ prefixes that deliver every subscribed topic:
+    /// the subscriptions no other subscription covers
+    pub fn minimal(&self) -> BTreeSet<String> {
+        self.topics
+            .keys()
+            .filter(|topic| {
+                !self.covering(topic).any(|ancestor| {
+                    ancestor != topic.as_str() && self.topics.contains_key(ancestor)
+                })
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Number of distinct subscribed topics
+    pub fn len(&self) -> usize {
+        self.topics.len()
+    }
+
+    /// Whether the tree has no subscriptions
+    pub fn is_empty(&self) -> bool {
+        self.topics.is_empty()
+    }
+
+    /// Every subscription that would cover `topic`: the empty one, each
+    /// ancestor with and without its trailing separator, and the topic
+    /// itself
+    fn covering<'a>(&self, topic: &'a str) -> impl Iterator<Item = &'a str> {
+        let width = self.separator.len_utf8();
+        let ancestors = topic
+            .match_indices(self.separator)
+            .flat_map(move |(at, _)| [&topic[..at], &topic[..at + width]]);
+        std::iter::once("")
+            .chain(ancestors)
+            .chain(std::iter::once(topic))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_covers_follows_hierarchy() {
+        let mut tree = SubscriptionTree::new('/');
+        tree.insert("a/b");
+        assert!(tree.covers("a/b"));
+        assert!(tree.covers("a/b/c"));
+        assert!(!tree.covers("a/bc"));
+        assert!(!tree.covers("a"));
+
+        tree.insert("x/");
+        assert!(tree.covers("x/y"));
+        assert!(!tree.covers("x"));
+
+        tree.insert("");
+        assert!(tree.covers("anything"));
+    }
+
+    #[test]
+    fn test_minimal_drops_covered_subscriptions() {
+        let mut tree = SubscriptionTree::new('/');
+        tree.insert("a/b/c");
+        tree.insert("a/b");
+        tree.insert("a/bc");
+        tree.insert("q");
+        assert_eq!(
+            tree.minimal().into_iter().collect::<Vec<_>>(),
+            ["a/b", "a/bc", "q"]
+        );
+
+        assert!(tree.remove("a/b"));
+        assert!(tree.minimal().contains("a/b/c"));
+        assert!(!tree.remove("a/b"));
+    }
+}
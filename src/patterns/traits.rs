@@ -0,0 +1,130 @@
+//! Pattern-agnostic sending and receiving
+//!
+//! Each pattern names its operations after its role (`publish`, `push`,
+//! `pull`, ...). [`MessageSender`] and [`MessageReceiver`] give generic code
+//! one name for each direction, as in [`drain`]:
+//!
+//! | Sender      | `send_msg`                                |
+//! |-------------|-------------------------------------------|
+//! | `Publisher` | `publish`                                 |
+//! | `Pusher`    | `push`                                    |
+//! | `Requester` | `request`, discarding the reply           |
+//!
+//! | Receiver     | `recv_msg`  | `recv_timeout`    |
+//! |--------------|-------------|-------------------|
+//! | `Subscriber` | `receive`   | `receive_timeout` |
+//! | `Puller`     | `pull`      | `pull_timeout`    |
+//! | `Replier`    | `receive`   | `receive_timeout` |
+//!
+//! The socket types' own rules still apply behind the traits: a `Replier`
+//! must reply before it can receive again, which generic code knows
+//! nothing about, and a `Requester` blocks in `send_msg` until its reply
+//! arrives, because a REQ socket cannot send again before that.
+
+use crate::error::Result;
+use crate::message::Message;
+use crate::patterns::{Publisher, Puller, Pusher, Replier, Requester, Subscriber};
+
+/// A pattern that sends messages
+pub trait MessageSender {
+    /// Send one message
+    fn send_msg(&self, message: &Message) -> Result<()>;
+}
+
+/// A pattern that receives messages
+pub trait MessageReceiver {
+    /// Receive the next message (blocking)
+    fn recv_msg(&self) -> Result<Message>;
+
+    /// Receive the next message, waiting at most `timeout_ms`
+    fn recv_timeout(&self, timeout_ms: i32) -> Result<Option<Message>>;
+}
+
+impl MessageSender for Publisher {
+    fn send_msg(&self, message: &Message) -> Result<()> {
+        self.publish(message)
+    }
+}
+
+impl MessageSender for Pusher {
+    fn send_msg(&self, message: &Message) -> Result<()> {
+        self.push(message)
+    }
+}
+
+impl MessageSender for Requester {
+    fn send_msg(&self, message: &Message) -> Result<()> {
+        self.request(message).map(drop)
+    }
+}
+
+impl MessageReceiver for Subscriber {
+    fn recv_msg(&self) -> Result<Message> {
+        self.receive()
+    }
+
+    fn recv_timeout(&self, timeout_ms: i32) -> Result<Option<Message>> {
+        self.receive_timeout(timeout_ms)
+    }
+}
+
+impl MessageReceiver for Puller {
+    fn recv_msg(&self) -> Result<Message> {
+        self.pull()
+    }
+
+    fn recv_timeout(&self, timeout_ms: i32) -> Result<Option<Message>> {
+        self.pull_timeout(timeout_ms)
+    }
+}
+
+impl MessageReceiver for Replier {
+    fn recv_msg(&self) -> Result<Message> {
+        self.receive()
+    }
+
+    fn recv_timeout(&self, timeout_ms: i32) -> Result<Option<Message>> {
+        self.receive_timeout(timeout_ms)
+    }
+}
+
+/// Receive messages until none arrives for `idle_ms` milliseconds
+///
+/// Returns everything received, in order. Fails on the first receive error,
+/// dropping the messages received before it.
+pub fn drain(receiver: &impl MessageReceiver, idle_ms: i32) -> Result<Vec<Message>> {
+    let mut messages = Vec::new();
+    while let Some(message) = receiver.recv_timeout(idle_ms)? {
+        messages.push(message);
+    }
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestPair;
+    use serde_json::json;
+
+    fn send_three(sender: &impl MessageSender) {
+        for i in 0..3 {
+            sender.send_msg(&Message::new("n", json!(i))).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_drain_works_over_puller_and_subscriber() {
+        let pipeline = TestPair::pipeline().unwrap();
+        send_three(&pipeline.sender);
+        let pulled = drain(&pipeline.receiver, 200).unwrap();
+
+        let pubsub = TestPair::pubsub().unwrap();
+        send_three(&pubsub.sender);
+        let received = drain(&pubsub.receiver, 200).unwrap();
+
+        for messages in [pulled, received] {
+            let payloads: Vec<_> = messages.into_iter().map(|m| m.payload).collect();
+            assert_eq!(payloads, [json!(0), json!(1), json!(2)]);
+        }
+    }
+}
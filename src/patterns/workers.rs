@@ -0,0 +1,135 @@
+//! Multithreaded request/reply server
+//!
+//! The classic ZeroMQ pattern: a ROUTER socket accepts requests from any
+//! number of `Requester`s and a proxy fans them out over an inproc DEALER
+//! socket to a pool of worker threads, each answering on its own REP socket.
+//! Replies travel back along the same route, so clients see an ordinary
+//! request/reply server that can handle several requests at once.
+
+use crate::builder::new_socket;
+use crate::error::{OxideError, Result};
+use crate::message::Message;
+use serde_json::json;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use zmq::{Context, Socket};
+
+static NEXT_POOL_ID: AtomicUsize = AtomicUsize::new(0);
+
+type Handler = Arc<dyn Fn(Message) -> Message + Send + Sync>;
+
+/// Request/reply server that handles requests on a pool of worker threads
+pub struct WorkerPool {
+    context: Context,
+    frontend: Socket,
+    backend: Socket,
+    backend_address: String,
+    workers: usize,
+    handler: Handler,
+}
+
+impl WorkerPool {
+    /// Create a pool that binds to `frontend_addr` and answers requests with
+    /// `handler` on `workers` threads
+    ///
+    /// Nothing is processed until [`WorkerPool::run`] is called.
+    pub fn new<F>(frontend_addr: &str, workers: usize, handler: F) -> Result<Self>
+    where
+        F: Fn(Message) -> Message + Send + Sync + 'static,
+    {
+        if workers == 0 {
+            return Err(OxideError::Configuration(
+                "worker pool needs at least one worker".to_string(),
+            ));
+        }
+
+        let context = Context::new();
+        let frontend = new_socket(&context, zmq::ROUTER)?;
+        frontend.bind(frontend_addr)?;
+
+        let id = NEXT_POOL_ID.fetch_add(1, Ordering::Relaxed);
+        let backend_address = format!("inproc://oxide-workers-{}", id);
+        let backend = new_socket(&context, zmq::DEALER)?;
+        backend.bind(&backend_address)?;
+
+        Ok(Self {
+            context,
+            frontend,
+            backend,
+            backend_address,
+            workers,
+            handler: Arc::new(handler),
+        })
+    }
+
+    /// Start the workers and route requests to them (blocks forever)
+    ///
+    /// A request that cannot be decoded is answered with an `error` message
+    /// so the requester is not left waiting.
+    pub fn run(&self) -> Result<()> {
+        for _ in 0..self.workers {
+            let socket = new_socket(&self.context, zmq::REP)?;
+            socket.connect(&self.backend_address)?;
+            let handler = Arc::clone(&self.handler);
+            thread::spawn(move || Self::work(socket, handler));
+        }
+
+        Ok(zmq::proxy(&self.frontend, &self.backend)?)
+    }
+
+    fn work(socket: Socket, handler: Handler) {
+        while let Ok(bytes) = socket.recv_bytes(0) {
+            let reply = match Message::from_bytes(&bytes) {
+                Ok(request) => handler(request),
+                Err(e) => Message::new("error", json!({"message": e.to_string()})),
+            };
+            let sent = reply
+                .to_bytes()
+                .and_then(|bytes| socket.send(bytes, 0).map_err(OxideError::from));
+            if sent.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patterns::Requester;
+    use std::time::Duration;
+
+    #[test]
+    fn test_worker_pool_concurrent_requests() {
+        let address = "tcp://127.0.0.1:15577";
+
+        let pool = WorkerPool::new(address, 4, |request| {
+            // Simulate work so requests overlap across workers
+            thread::sleep(Duration::from_millis(20));
+            let n = request.payload["n"].as_i64().unwrap_or(0);
+            Message::new("doubled", json!({"n": n, "result": n * 2}))
+        })
+        .unwrap();
+        thread::spawn(move || pool.run());
+
+        let clients: Vec<_> = (0..5)
+            .map(|client| {
+                thread::spawn(move || {
+                    let requester = Requester::new(address).unwrap();
+                    for i in 0..4 {
+                        let n = client * 10 + i;
+                        let request = Message::new("double", json!({"n": n}));
+                        let reply = requester.request_timeout(&request, 5000).unwrap().unwrap();
+                        assert_eq!(reply.payload["n"], n);
+                        assert_eq!(reply.payload["result"], n * 2);
+                    }
+                })
+            })
+            .collect();
+
+        for client in clients {
+            client.join().unwrap();
+        }
+    }
+}
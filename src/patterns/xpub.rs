@@ -0,0 +1,179 @@
+//! Publisher that observes its subscribers' subscriptions
+//!
+//! An XPUB socket publishes like PUB but also delivers subscription changes
+//! as messages. Each control message is a single frame: one byte, `1` for
+//! subscribe or `0` for unsubscribe, followed by the topic prefix bytes. By
+//! default ZeroMQ only reports the first subscription to a topic and the
+//! last unsubscription; [`XPublisher::set_verbose`] reports every one.
+//!
+//! Because a subscription only reaches the XPUB socket once the subscriber
+//! is attached, [`wait_until_subscribed`] is a reliable way past the
+//! slow-joiner problem: a message published after it returns reaches the
+//! subscriber that sent that subscription, with no sleep.
+
+use crate::builder::{
+    impl_affinity, impl_bind, impl_buffers, impl_frames, impl_multicast, impl_pattern, impl_plain,
+    impl_tcp_keepalive,
+};
+use crate::endpoint::Endpoint;
+use crate::error::{OxideError, Result};
+use crate::message::Message;
+use crate::metrics::Counters;
+use crate::patterns::{deadline_after, recv_bytes_timeout, remaining_ms};
+use zmq::Socket;
+
+/// A subscription change reported by an [`XPublisher`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subscription {
+    /// `true` for a subscribe, `false` for an unsubscribe
+    pub subscribe: bool,
+    /// Topic prefix the change applies to (empty for "all topics")
+    pub topic: String,
+}
+
+impl Subscription {
+    fn decode(frame: &[u8]) -> Result<Self> {
+        match frame.split_first() {
+            Some((&flag @ (0 | 1), topic)) => Ok(Self {
+                subscribe: flag == 1,
+                topic: String::from_utf8_lossy(topic).into_owned(),
+            }),
+            _ => Err(OxideError::Protocol(
+                "XPUB frame is not a subscription message".to_string(),
+            )),
+        }
+    }
+}
+
+/// Publisher over an XPUB socket
+pub struct XPublisher {
+    socket: Socket,
+    counters: Counters,
+}
+
+impl_pattern!(XPublisher => zmq::XPUB);
+impl_affinity!(XPublisher);
+impl_plain!(XPublisher);
+impl_buffers!(XPublisher);
+impl_bind!(XPublisher);
+impl_frames!(XPublisher);
+impl_multicast!(XPublisher);
+impl_tcp_keepalive!(XPublisher);
+
+impl XPublisher {
+    /// Create a new publisher that binds to the specified address
+    pub fn new(address: impl Into<Endpoint>) -> Result<Self> {
+        Self::builder().bind(address)
+    }
+
+    /// Report every subscription and unsubscription, not just the first
+    /// and last for each topic (`ZMQ_XPUB_VERBOSE`)
+    pub fn set_verbose(&self, verbose: bool) -> Result<()> {
+        self.socket
+            .set_xpub_verbose(verbose)
+            .map_err(OxideError::SocketOption)
+    }
+
+    /// Publish a message
+    pub fn publish(&self, message: &Message) -> Result<()> {
+        let bytes = message.to_bytes()?;
+        self.socket.send(&bytes, 0).map_err(OxideError::Send)?;
+        self.counters.record_sent();
+        Ok(())
+    }
+
+    /// Publish a message with its topic as a leading frame, see
+    /// `Publisher::publish_filtered`
+    pub fn publish_filtered(&self, message: &Message) -> Result<()> {
+        let bytes = message.to_bytes()?;
+        self.socket
+            .send(message.topic.as_bytes(), zmq::SNDMORE)
+            .map_err(OxideError::Send)?;
+        self.socket.send(&bytes, 0).map_err(OxideError::Send)?;
+        self.counters.record_sent();
+        Ok(())
+    }
+
+    /// Receive the next subscription change (blocking)
+    pub fn recv_subscription(&self) -> Result<Subscription> {
+        let frame = self.socket.recv_bytes(0).map_err(OxideError::Receive)?;
+        Subscription::decode(&frame)
+    }
+
+    /// Receive the next subscription change, waiting at most `timeout_ms`
+    pub fn recv_subscription_timeout(&self, timeout_ms: i32) -> Result<Option<Subscription>> {
+        match recv_bytes_timeout(&self.socket, timeout_ms)? {
+            Some(frame) => Ok(Some(Subscription::decode(&frame)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Wait up to `timeout_ms` (-1 waits forever) for a subscriber to
+/// subscribe, returning its subscription
+///
+/// Unsubscriptions arriving meanwhile are skipped. Fails with
+/// `OxideError::Timeout` if no subscription arrives in time.
+pub fn wait_until_subscribed(publisher: &XPublisher, timeout_ms: i32) -> Result<Subscription> {
+    let deadline = deadline_after(timeout_ms);
+    loop {
+        let Some(wait_ms) = remaining_ms(deadline) else {
+            return Err(OxideError::Timeout(format!(
+                "no subscription after {} ms",
+                timeout_ms
+            )));
+        };
+        match publisher.recv_subscription_timeout(wait_ms)? {
+            Some(subscription) if subscription.subscribe => return Ok(subscription),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patterns::Subscriber;
+    use serde_json::json;
+
+    #[test]
+    fn test_reports_subscription() {
+        let address = "tcp://127.0.0.1:15583";
+
+        let publisher = XPublisher::new(address).unwrap();
+        publisher.set_verbose(true).unwrap();
+        let subscriber = Subscriber::new(address).unwrap();
+        subscriber.subscribe("foo").unwrap();
+
+        let subscription = publisher.recv_subscription_timeout(2000).unwrap();
+        assert_eq!(
+            subscription,
+            Some(Subscription {
+                subscribe: true,
+                topic: "foo".to_string()
+            })
+        );
+
+        subscriber.unsubscribe("foo").unwrap();
+        let subscription = publisher.recv_subscription_timeout(2000).unwrap().unwrap();
+        assert!(!subscription.subscribe);
+        assert_eq!(subscription.topic, "foo");
+    }
+
+    #[test]
+    fn test_first_message_delivered_after_wait_until_subscribed() {
+        let address = "tcp://127.0.0.1:15642";
+        let publisher = XPublisher::new(address).unwrap();
+        let subscriber = Subscriber::new(address).unwrap();
+        subscriber.subscribe("ticks").unwrap();
+
+        let subscription = wait_until_subscribed(&publisher, 2000).unwrap();
+        assert_eq!(subscription.topic, "ticks");
+        publisher
+            .publish_filtered(&Message::new("ticks", json!(1)))
+            .unwrap();
+
+        let first = subscriber.receive_timeout(2000).unwrap().unwrap();
+        assert_eq!(first.payload, json!(1));
+    }
+}
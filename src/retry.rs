@@ -0,0 +1,78 @@
+//! Retry transient failures with exponential backoff
+//!
+//! ```no_run
+//! use oxide_msg::{retry, Message, Pusher};
+//! use std::time::Duration;
+//!
+//! let pusher = Pusher::new_bind("tcp://*:5557")?;
+//! let task = Message::new("task", serde_json::json!({"n": 1}));
+//! retry::with_backoff(5, Duration::from_millis(10), || pusher.try_push(&task))?;
+//! # Ok::<(), oxide_msg::OxideError>(())
+//! ```
+//!
+//! Only errors for which [`OxideError::is_retryable`] holds are retried:
+//! sends that hit the high-water mark or their send timeout (`EAGAIN`) and
+//! interrupted calls (`EINTR`). Any other error is returned at once.
+
+use crate::error::{OxideError, Result};
+use std::thread;
+use std::time::Duration;
+
+/// Run `op` up to `attempts` times, sleeping between failed attempts
+///
+/// The first retry waits `base_delay`, and each further one twice as long
+/// as the last. Returns the first success, the first non-retryable error,
+/// or the error of the final attempt. `attempts` must be at least 1.
+pub fn with_backoff<T>(
+    attempts: u32,
+    base_delay: Duration,
+    mut op: impl FnMut() -> Result<T>,
+) -> Result<T> {
+    if attempts == 0 {
+        return Err(OxideError::Configuration(
+            "with_backoff needs at least one attempt".to_string(),
+        ));
+    }
+    let mut delay = base_delay;
+    for _ in 1..attempts {
+        match op() {
+            Err(e) if e.is_retryable() => {
+                thread::sleep(delay);
+                delay = delay.saturating_mul(2);
+            }
+            result => return result,
+        }
+    }
+    op()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_succeeds_after_transient_failures() {
+        let mut calls = 0;
+        let result = with_backoff(5, Duration::from_millis(1), || {
+            calls += 1;
+            if calls < 3 {
+                Err(OxideError::Send(zmq::Error::EAGAIN))
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn test_fatal_errors_are_not_retried() {
+        let mut calls = 0;
+        let result: Result<()> = with_backoff(5, Duration::from_millis(1), || {
+            calls += 1;
+            Err(OxideError::Send(zmq::Error::ETERM))
+        });
+        assert!(matches!(result, Err(OxideError::Send(zmq::Error::ETERM))));
+        assert_eq!(calls, 1);
+    }
+}
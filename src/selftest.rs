@@ -0,0 +1,85 @@
+//! Round-trip checks for startup health checks and smoke tests
+//!
+//! [`loopback_reqrep`] provides both sides of a request/reply exchange on one
+//! address, so it can check that the address can be bound and that messages
+//! make it through the stack, with no peer process involved.
+
+use crate::builder::sealed::Pattern;
+use crate::endpoint::Endpoint;
+use crate::error::{OxideError, Result};
+use crate::message::Message;
+use crate::patterns::{Replier, Requester};
+use serde_json::json;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long the loopback waits for the echo before giving up
+const LOOPBACK_TIMEOUT_MS: i32 = 5000;
+
+/// Bind an echoing replier to `address`, send it one request and return the
+/// round-trip time
+///
+/// The replier runs on a background thread. Both sockets are closed and the
+/// thread joined before returning, so the address is free again afterwards.
+/// Fails with the bind error if `address` cannot be bound,
+/// `OxideError::Timeout` if no echo arrives within five seconds, and
+/// `OxideError::Protocol` if the echo does not match the request.
+pub fn loopback_reqrep(address: impl Into<Endpoint>) -> Result<Duration> {
+    let address = address.into();
+    // A shared context lets inproc addresses work as well
+    let context = zmq::Context::new();
+    let replier = Replier::builder().context(&context).bind(address.clone())?;
+    let requester = Requester::builder().context(&context).connect(address)?;
+    // Neither socket should hold the caller up over an unanswered message
+    for socket in [replier.socket(), requester.socket()] {
+        socket.set_linger(0).map_err(OxideError::SocketOption)?;
+    }
+
+    let echo = thread::spawn(move || -> Result<()> {
+        if let Some(request) = replier.receive_timeout(LOOPBACK_TIMEOUT_MS)? {
+            replier.reply(&request)?;
+        }
+        Ok(())
+    });
+
+    let probe = Message::tracked("oxide_msg.selftest", json!(null));
+    let started = Instant::now();
+    let reply = requester.request_timeout(&probe, LOOPBACK_TIMEOUT_MS);
+    let elapsed = started.elapsed();
+    drop(requester);
+    let echoed = echo
+        .join()
+        .map_err(|_| OxideError::Connection("loopback replier thread panicked".to_string()))?;
+
+    match reply? {
+        Some(reply) if reply.id == probe.id => {
+            echoed?;
+            Ok(elapsed)
+        }
+        Some(_) => Err(OxideError::Protocol(
+            "loopback reply does not match the request".to_string(),
+        )),
+        None => Err(OxideError::Timeout(format!(
+            "no loopback reply after {} ms",
+            LOOPBACK_TIMEOUT_MS
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loopback_reqrep_measures_round_trip_and_frees_address() {
+        let address = "tcp://127.0.0.1:15647";
+        let elapsed = loopback_reqrep(address).unwrap();
+        assert!(elapsed > Duration::ZERO);
+
+        // The helper's sockets are closed: the address binds again, and the
+        // helper itself can run again
+        drop(Replier::new(address).unwrap());
+        assert!(loopback_reqrep(address).is_ok());
+        assert!(loopback_reqrep("inproc://selftest").is_ok());
+    }
+}
@@ -0,0 +1,185 @@
+//! Pluggable wire encodings for [`Message`]
+//!
+//! A [`Serializer`] turns messages into bytes and back. This module does not
+//! depend on ZeroMQ, so it is available in message-only builds.
+
+use crate::error::{OxideError, Result};
+use crate::message::Message;
+
+/// Encoding of messages on the wire
+pub trait Serializer {
+    /// Encode `message` into bytes
+    fn serialize(&self, message: &Message) -> Result<Vec<u8>>;
+
+    /// Decode a message from `bytes`
+    fn deserialize(&self, bytes: &[u8]) -> Result<Message>;
+}
+
+/// JSON encoding, the format used by `Message::to_bytes`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonSerializer;
+
+impl Serializer for JsonSerializer {
+    fn serialize(&self, message: &Message) -> Result<Vec<u8>> {
+        message.to_bytes()
+    }
+
+    fn deserialize(&self, bytes: &[u8]) -> Result<Message> {
+        Message::from_bytes(bytes)
+    }
+}
+
+/// CBOR encoding, see `Message::to_cbor` (requires the `cbor` feature)
+#[cfg(feature = "cbor")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CborSerializer;
+
+#[cfg(feature = "cbor")]
+impl Serializer for CborSerializer {
+    fn serialize(&self, message: &Message) -> Result<Vec<u8>> {
+        message.to_cbor()
+    }
+
+    fn deserialize(&self, bytes: &[u8]) -> Result<Message> {
+        Message::from_cbor(bytes)
+    }
+}
+
+/// JSON encoding with top-level keys renamed, for peers that name the
+/// message fields differently
+///
+/// Only whole fields of [`Message`] can be renamed; keys nested inside the
+/// payload or headers are left alone. A wire name should not be the name of
+/// another field that keeps its own name, or decoding cannot tell the two
+/// apart.
+///
+/// ```
+/// use oxide_msg::{CompatSerializer, Message, Serializer};
+/// use serde_json::json;
+///
+/// let serializer = CompatSerializer::new()
+///     .rename("topic", "msg_type")
+///     .rename("payload", "data");
+/// let bytes = serializer.serialize(&Message::new("orders", json!(7))).unwrap();
+/// let wire: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+/// assert_eq!(wire["msg_type"], json!("orders"));
+/// assert_eq!(serializer.deserialize(&bytes).unwrap().payload, json!(7));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CompatSerializer {
+    renames: Vec<(String, String)>,
+}
+
+impl CompatSerializer {
+    /// Create a serializer that renames nothing until told to
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write the message field `field` under the key `wire_name`, and read
+    /// it back from there
+    pub fn rename(mut self, field: impl Into<String>, wire_name: impl Into<String>) -> Self {
+        self.renames.push((field.into(), wire_name.into()));
+        self
+    }
+
+    /// Move the values under each `from` key to its `to` key
+    fn rekey<'a>(
+        value: &mut serde_json::Value,
+        pairs: impl Iterator<Item = (&'a str, &'a str)> + Clone,
+    ) -> Result<()> {
+        let Some(object) = value.as_object_mut() else {
+            return Err(OxideError::Serialization(
+                "message is not a JSON object".to_string(),
+            ));
+        };
+        // Take every value out before putting any back, so swapped names
+        // do not overwrite each other
+        let moved: Vec<_> = pairs
+            .filter_map(|(from, to)| object.remove(from).map(|moved| (to, moved)))
+            .collect();
+        for (to, moved) in moved {
+            object.insert(to.to_string(), moved);
+        }
+        Ok(())
+    }
+}
+
+impl Serializer for CompatSerializer {
+    fn serialize(&self, message: &Message) -> Result<Vec<u8>> {
+        let mut value = serde_json::to_value(message)?;
+        Self::rekey(
+            &mut value,
+            self.renames
+                .iter()
+                .map(|(field, wire)| (field.as_str(), wire.as_str())),
+        )?;
+        Ok(serde_json::to_vec(&value)?)
+    }
+
+    fn deserialize(&self, bytes: &[u8]) -> Result<Message> {
+        let mut value: serde_json::Value = serde_json::from_slice(bytes)?;
+        Self::rekey(
+            &mut value,
+            self.renames
+                .iter()
+                .map(|(field, wire)| (wire.as_str(), field.as_str())),
+        )?;
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn round_trip(serializer: &dyn Serializer) {
+        let msg = Message::new_versioned("sensors.temp", json!({"celsius": 21.5}), 2).track();
+        let bytes = serializer.serialize(&msg).unwrap();
+        let decoded = serializer.deserialize(&bytes).unwrap();
+        assert_eq!(decoded.topic, msg.topic);
+        assert_eq!(decoded.payload, msg.payload);
+        assert_eq!(decoded.version, 2);
+        assert_eq!(decoded.id, msg.id);
+    }
+
+    // Runs without any socket, including in `--no-default-features` builds
+    #[test]
+    fn test_json_round_trip() {
+        round_trip(&JsonSerializer);
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn test_cbor_round_trip() {
+        round_trip(&CborSerializer);
+    }
+
+    #[test]
+    fn test_compat_renames_top_level_keys() {
+        let serializer = CompatSerializer::new()
+            .rename("topic", "msg_type")
+            .rename("payload", "data");
+        round_trip(&serializer);
+
+        let msg = Message::new("orders.created", json!({"topic": "nested", "id": 7}));
+        let wire: serde_json::Value =
+            serde_json::from_slice(&serializer.serialize(&msg).unwrap()).unwrap();
+        assert_eq!(wire["msg_type"], json!("orders.created"));
+        assert_eq!(wire["data"], json!({"topic": "nested", "id": 7}));
+        assert!(wire.get("topic").is_none());
+        assert!(wire.get("payload").is_none());
+
+        // What the other service sends decodes with the usual names
+        let foreign = json!({"msg_type": "orders.shipped", "data": [1, 2]});
+        let decoded = serializer
+            .deserialize(&serde_json::to_vec(&foreign).unwrap())
+            .unwrap();
+        assert_eq!(decoded.topic, "orders.shipped");
+        assert_eq!(decoded.payload, json!([1, 2]));
+        assert!(JsonSerializer
+            .deserialize(&serde_json::to_vec(&foreign).unwrap())
+            .is_err());
+    }
+}
@@ -0,0 +1,81 @@
+//! Graceful shutdown for receive loops (requires the `signals` feature)
+
+use crate::error::{OxideError, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// How often loop helpers wake up to check for shutdown, in milliseconds
+pub const SHUTDOWN_POLL_MS: i32 = 100;
+
+/// Shared flag telling receive loops to stop
+///
+/// Clones share the same flag, so one can be handed to a signal handler or
+/// another thread while loops hold the rest.
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownToken {
+    flag: Arc<AtomicBool>,
+}
+
+impl ShutdownToken {
+    /// Create a token that has not been triggered
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask every loop holding this token to stop
+    pub fn shutdown(&self) {
+        self.flag.store(true, Ordering::SeqCst);
+    }
+
+    /// Check whether shutdown has been requested
+    pub fn is_shutdown(&self) -> bool {
+        self.flag.load(Ordering::SeqCst)
+    }
+}
+
+/// Install a Ctrl-C (SIGINT) handler that triggers the returned token
+///
+/// Only one handler can be installed per process; a second call fails with
+/// a configuration error.
+pub fn install_ctrlc_handler() -> Result<ShutdownToken> {
+    let token = ShutdownToken::new();
+    let handler_token = token.clone();
+    ctrlc::set_handler(move || handler_token.shutdown())
+        .map_err(|e| OxideError::Configuration(e.to_string()))?;
+    Ok(token)
+}
+
+#[cfg(all(test, feature = "transport"))]
+mod tests {
+    use super::*;
+    use crate::message::Message;
+    use crate::patterns::{Replier, Requester};
+    use serde_json::json;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_serve_until_exits_on_shutdown() {
+        let address = "tcp://127.0.0.1:15568";
+        let token = ShutdownToken::new();
+
+        let replier = Replier::new(address).unwrap();
+        let server_token = token.clone();
+        let server_handle = thread::spawn(move || {
+            replier.serve_until(&server_token, |request| {
+                Ok(Message::new("echo", request.payload))
+            })
+        });
+
+        let requester = Requester::new(address).unwrap();
+        let reply = requester
+            .request(&Message::new("ping", json!({"n": 1})))
+            .unwrap();
+        assert_eq!(reply.payload["n"], 1);
+
+        let started = Instant::now();
+        token.shutdown();
+        server_handle.join().unwrap().unwrap();
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
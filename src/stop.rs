@@ -0,0 +1,118 @@
+//! Interrupting blocking receives from another thread
+//!
+//! A blocking ZeroMQ receive only returns when a message arrives, so a
+//! thread parked in one cannot be told to shut down. The usual fix is to
+//! poll the data socket together with a control socket and send a byte on
+//! the control socket to wake the poll. A [`StopHandle`] owns an inproc
+//! PUB socket it signals on, and every wait polls a SUB socket of its own
+//! connected to it, so any number of threads can wait on clones of one
+//! handle at the same time; [`Subscriber::receive_interruptible`] polls it.
+//!
+//! [`Requester::await_reply`] polls it together with a deadline.
+//!
+//! Stopping is permanent: once [`StopHandle::stop`] has been called, every
+//! interruptible wait using the handle returns at once.
+//!
+//! [`Subscriber::receive_interruptible`]: crate::Subscriber::receive_interruptible
+//! [`Requester::await_reply`]: crate::Requester::await_reply
+
+use crate::builder::new_socket;
+use crate::error::{OxideError, Result};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use zmq::{Context, Socket};
+
+static NEXT_STOP_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Cloneable handle that interrupts blocking receives waiting on it
+///
+/// Clones share the same control channel and can be moved to other
+/// threads; a stop wakes the waits on all of them.
+#[derive(Clone)]
+pub struct StopHandle {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    stopped: AtomicBool,
+    endpoint: String,
+    // Declared before the context so it closes first
+    signal: Mutex<Socket>,
+    context: Context,
+}
+
+impl StopHandle {
+    /// Create a handle with its own inproc control channel
+    pub fn new() -> Result<Self> {
+        let context = Context::new();
+        let id = NEXT_STOP_ID.fetch_add(1, Ordering::Relaxed);
+        let endpoint = format!("inproc://oxide-stop-{}", id);
+        let signal = new_socket(&context, zmq::PUB)?;
+        signal.bind(&endpoint)?;
+        Ok(Self {
+            inner: Arc::new(Inner {
+                stopped: AtomicBool::new(false),
+                endpoint,
+                signal: Mutex::new(signal),
+                context,
+            }),
+        })
+    }
+
+    /// Wake every receive waiting on this handle, now and from now on
+    pub fn stop(&self) -> Result<()> {
+        if self.inner.stopped.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        // Waits starting from now on see the flag instead
+        self.inner
+            .signal
+            .lock()
+            .unwrap()
+            .send(&[] as &[u8], 0)
+            .map_err(OxideError::Send)
+    }
+
+    /// Whether [`StopHandle::stop`] has been called
+    pub fn is_stopped(&self) -> bool {
+        self.inner.stopped.load(Ordering::SeqCst)
+    }
+
+    /// Wait at most `timeout_ms` (-1 waits forever) until `socket` is
+    /// readable or the handle is stopped
+    pub(crate) fn wait(&self, socket: &Socket, timeout_ms: i64) -> Result<Wake> {
+        if self.is_stopped() {
+            return Ok(Wake::Stopped);
+        }
+        let control = new_socket(&self.inner.context, zmq::SUB)?;
+        control.connect(&self.inner.endpoint)?;
+        control
+            .set_subscribe(b"")
+            .map_err(OxideError::SocketOption)?;
+        // The subscription reaches the signal socket before any stop sent
+        // after this check, so a stop cannot slip in between
+        if self.is_stopped() {
+            return Ok(Wake::Stopped);
+        }
+        let mut items = [
+            socket.as_poll_item(zmq::POLLIN),
+            control.as_poll_item(zmq::POLLIN),
+        ];
+        zmq::poll(&mut items, timeout_ms).map_err(OxideError::Receive)?;
+        Ok(if items[1].is_readable() {
+            Wake::Stopped
+        } else if items[0].is_readable() {
+            Wake::Readable
+        } else {
+            Wake::TimedOut
+        })
+    }
+}
+
+/// Why [`StopHandle::wait`] returned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Wake {
+    Readable,
+    Stopped,
+    TimedOut,
+}
@@ -0,0 +1,184 @@
+//! Helpers for tests that need connected pattern sockets
+//!
+//! Available in this crate's tests and, for downstream crates, with the
+//! `testing` feature. Each [`TestPair`] gets its own `inproc://` endpoint on
+//! a context shared by both sockets, so pairs never collide on ports and
+//! tests can run in parallel.
+//!
+//! Readiness is checked with probe messages rather than a fixed sleep.
+//! Socket monitor events cannot be used for this: inproc connections do not
+//! report any, and even over TCP a connected subscriber may not have its
+//! subscription registered at the publisher yet.
+
+use crate::error::{OxideError, Result};
+use crate::message::Message;
+use crate::patterns::{Publisher, Puller, Pusher, Replier, Requester, Subscriber};
+use serde_json::Value;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use zmq::Context;
+
+static NEXT_ENDPOINT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Topic of the probe messages used to wait for a subscriber
+pub const PROBE_TOPIC: &str = "\u{0}oxide-probe";
+
+/// How long [`TestPair::pubsub`] waits for the subscription to take effect
+const READY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A unique `inproc://` endpoint name
+pub fn unique_endpoint() -> String {
+    let id = NEXT_ENDPOINT_ID.fetch_add(1, Ordering::Relaxed);
+    format!("inproc://oxide-test-{}-{}", std::process::id(), id)
+}
+
+/// Two connected sockets on a private inproc endpoint
+///
+/// `sender` is the side that sends first (publisher, pusher, requester) and
+/// `receiver` the other one.
+pub struct TestPair<S, R> {
+    /// Sending side
+    pub sender: S,
+    /// Receiving side
+    pub receiver: R,
+    endpoint: String,
+    context: Context,
+}
+
+impl<S, R> TestPair<S, R> {
+    /// The endpoint both sockets use
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// The context shared by both sockets, for adding more peers
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+}
+
+impl TestPair<Publisher, Subscriber> {
+    /// A publisher and a subscriber to all topics, ready to deliver
+    ///
+    /// Returns once a probe message has made it through, so nothing
+    /// published afterwards is lost to the slow-joiner race.
+    pub fn pubsub() -> Result<Self> {
+        let context = Context::new();
+        let endpoint = unique_endpoint();
+        let sender = Publisher::builder().context(&context).bind(&endpoint)?;
+        let receiver = Subscriber::builder().context(&context).connect(&endpoint)?;
+        receiver.subscribe("")?;
+
+        let probe = Message::new(PROBE_TOPIC, Value::Null);
+        let deadline = Instant::now() + READY_TIMEOUT;
+        loop {
+            sender.publish_filtered(&probe)?;
+            if receiver.receive_timeout(1)?.is_some() {
+                break;
+            }
+            if Instant::now() >= deadline {
+                return Err(OxideError::Connection(format!(
+                    "subscriber on {} did not become ready",
+                    endpoint
+                )));
+            }
+        }
+        while receiver.try_receive()?.is_some() {}
+
+        Ok(Self {
+            sender,
+            receiver,
+            endpoint,
+            context,
+        })
+    }
+}
+
+impl TestPair<Pusher, Puller> {
+    /// A binding pusher and a connected puller
+    ///
+    /// PUSH queues messages until a peer is attached, so no readiness wait
+    /// is needed.
+    pub fn pipeline() -> Result<Self> {
+        let context = Context::new();
+        let endpoint = unique_endpoint();
+        let sender = Pusher::builder().context(&context).bind(&endpoint)?;
+        let receiver = Puller::builder().context(&context).connect(&endpoint)?;
+        Ok(Self {
+            sender,
+            receiver,
+            endpoint,
+            context,
+        })
+    }
+}
+
+impl TestPair<Requester, Replier> {
+    /// A connected requester and a binding replier
+    pub fn reqrep() -> Result<Self> {
+        let context = Context::new();
+        let endpoint = unique_endpoint();
+        let receiver = Replier::builder().context(&context).bind(&endpoint)?;
+        let sender = Requester::builder().context(&context).connect(&endpoint)?;
+        Ok(Self {
+            sender,
+            receiver,
+            endpoint,
+            context,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::thread;
+
+    #[test]
+    fn test_pairs_run_concurrently() {
+        let handles: Vec<_> = (0..2)
+            .map(|n| {
+                thread::spawn(move || {
+                    let pair = TestPair::pubsub().unwrap();
+                    for i in 0..10 {
+                        pair.sender
+                            .publish(&Message::new("pair", json!({"pair": n, "seq": i})))
+                            .unwrap();
+                    }
+                    for i in 0..10 {
+                        let msg = pair.receiver.receive_timeout(1000).unwrap().unwrap();
+                        assert_eq!(msg.payload, json!({"pair": n, "seq": i}));
+                    }
+                    pair.endpoint().to_string()
+                })
+            })
+            .collect();
+
+        let endpoints: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_ne!(endpoints[0], endpoints[1]);
+    }
+
+    #[test]
+    fn test_pipeline_and_reqrep_pairs() {
+        let pipeline = TestPair::pipeline().unwrap();
+        pipeline
+            .sender
+            .push(&Message::new("task", json!(1)))
+            .unwrap();
+        assert_eq!(pipeline.receiver.pull().unwrap().payload, json!(1));
+
+        let reqrep = TestPair::reqrep().unwrap();
+        let replier = reqrep.receiver;
+        let server = thread::spawn(move || {
+            let request = replier.receive().unwrap();
+            replier.reply(&request).unwrap();
+        });
+        let reply = reqrep
+            .sender
+            .request(&Message::new("echo", json!("hi")))
+            .unwrap();
+        assert_eq!(reply.payload, json!("hi"));
+        server.join().unwrap();
+    }
+}
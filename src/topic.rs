@@ -0,0 +1,134 @@
+//! Hierarchical dotted topics
+//!
+//! Topics such as `sensors.room1.temp` are made of segments separated by
+//! [`SEPARATOR`]. Patterns use [`WILDCARD`] to match exactly one segment, so
+//! `sensors.*.temp` matches `sensors.room1.temp` but not `sensors.temp`.
+//!
+//! ZeroMQ itself only matches byte prefixes of the first frame, so
+//! [`Topic::zmq_prefix`] keeps everything up to the first wildcard and the
+//! subscriber receives a superset of the pattern: `sensors.*.temp`
+//! subscribes to `sensors.`, and `sensors.room1` would also let
+//! `sensors.room10` through. Check [`Topic::matches`] on receipt when the
+//! difference matters. Prefix subscriptions only apply to topic-framed
+//! messages (see `Publisher::publish_filtered`).
+
+use std::fmt;
+
+/// Separator between topic segments
+pub const SEPARATOR: char = '.';
+
+/// Pattern segment matching any single segment
+pub const WILDCARD: &str = "*";
+
+/// A dotted, hierarchical topic name or pattern
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Topic {
+    name: String,
+}
+
+impl Topic {
+    /// Create a topic from a dotted name such as `sensors.room1.temp`
+    pub fn new(name: impl Into<String>) -> Self {
+        Self { name: name.into() }
+    }
+
+    /// The full topic name
+    pub fn as_str(&self) -> &str {
+        &self.name
+    }
+
+    /// Iterate over the segments of the topic
+    pub fn segments(&self) -> impl Iterator<Item = &str> {
+        self.name.split(SEPARATOR)
+    }
+
+    /// The topic one level up, or `None` for a single-segment topic
+    pub fn parent(&self) -> Option<Topic> {
+        self.name
+            .rsplit_once(SEPARATOR)
+            .map(|(parent, _)| Topic::new(parent))
+    }
+
+    /// Check whether this topic matches `pattern`
+    ///
+    /// Segments must match one for one; a `*` segment in the pattern
+    /// matches any single segment.
+    pub fn matches(&self, pattern: &str) -> bool {
+        let mut segments = self.segments();
+        let mut pattern_segments = pattern.split(SEPARATOR);
+        loop {
+            match (segments.next(), pattern_segments.next()) {
+                (None, None) => return true,
+                (Some(segment), Some(expected)) => {
+                    if expected != WILDCARD && expected != segment {
+                        return false;
+                    }
+                }
+                _ => return false,
+            }
+        }
+    }
+
+    /// The ZeroMQ subscription prefix for this topic or pattern
+    ///
+    /// This is the name up to the first wildcard segment, or the whole name
+    /// when there is none.
+    pub fn zmq_prefix(&self) -> &str {
+        let mut end = 0;
+        for segment in self.segments() {
+            if segment == WILDCARD {
+                return &self.name[..end];
+            }
+            end += segment.len() + SEPARATOR.len_utf8();
+        }
+        &self.name
+    }
+}
+
+impl fmt::Display for Topic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+impl From<&str> for Topic {
+    fn from(name: &str) -> Self {
+        Topic::new(name)
+    }
+}
+
+impl From<String> for Topic {
+    fn from(name: String) -> Self {
+        Topic::new(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcard_matching() {
+        assert!(Topic::new("sensors.temp").matches("sensors.*"));
+        assert!(Topic::new("sensors.room1.temp").matches("sensors.*.temp"));
+        assert!(Topic::new("sensors.room1.temp").matches("sensors.room1.temp"));
+        assert!(!Topic::new("sensors.room1.temp").matches("sensors.*"));
+        assert!(!Topic::new("sensors").matches("sensors.*"));
+        assert!(!Topic::new("alerts.temp").matches("sensors.*"));
+    }
+
+    #[test]
+    fn test_parent() {
+        let topic = Topic::new("sensors.room1.temp");
+        assert_eq!(topic.parent(), Some(Topic::new("sensors.room1")));
+        assert_eq!(Topic::new("sensors").parent(), None);
+    }
+
+    #[test]
+    fn test_zmq_prefix() {
+        assert_eq!(Topic::new("sensors.*").zmq_prefix(), "sensors.");
+        assert_eq!(Topic::new("sensors.*.temp").zmq_prefix(), "sensors.");
+        assert_eq!(Topic::new("*.temp").zmq_prefix(), "");
+        assert_eq!(Topic::new("sensors.room1").zmq_prefix(), "sensors.room1");
+    }
+}
@@ -0,0 +1,318 @@
+//! Self-describing wire formats for [`Message`]
+//!
+//! [`Message::to_bytes`] writes plain JSON, as it always has.
+//! [`Message::to_bytes_with`] can instead write a four-byte header, the
+//! magic bytes `\0OX` and a format tag, followed by the encoded message.
+//! [`Message::from_bytes`] checks for that header and decodes accordingly,
+//! falling back to plain JSON without it, so senders can switch formats
+//! one at a time while receivers read both. A JSON message always starts
+//! with `{` (or whitespace), never with a NUL byte, so the two cannot be
+//! confused.
+//!
+//! | Tag | Format | Feature |
+//! |-----|--------|---------|
+//! | `1` | gzip-compressed JSON | `gzip` |
+//! | `2` | MessagePack | `msgpack` |
+//! | `3` | CBOR | `cbor` |
+//!
+//! Decoding a tagged message whose feature is disabled fails with a
+//! serialization error naming the feature. A gzip body that decompresses
+//! to more than [`MAX_DECOMPRESSED_LEN`] bytes is rejected the same way,
+//! so a small message from the network cannot claim unbounded memory.
+//!
+//! ## JSON format
+//!
+//! A message is one JSON object. `topic` (a string) and `payload` (any
+//! JSON value) are always present; the other fields are left out when
+//! they hold their default:
+//!
+//! | Field | Type | Omitted when | Since |
+//! |-------|------|--------------|-------|
+//! | `topic` | string | never | 1 |
+//! | `payload` | any | never | 1 |
+//! | `version` | unsigned integer | 0 | 2 |
+//! | `id` | string | untracked | 2 |
+//! | `timestamp` | milliseconds since the Unix epoch | untracked | 2 |
+//! | `priority` | integer 0-255 | 0 | 3 |
+//! | `sequence` | unsigned integer | unsequenced | 3 |
+//! | `payload_type` | string | untyped | 3 |
+//! | `checksum` | CRC-32 of the payload, unsigned integer | unchecked | 4 |
+//! | `expires_at` | milliseconds since the Unix epoch | no TTL | 5 |
+//! | `headers` | object of string values | empty | 6 |
+//!
+//! Fields are written in the order above. The "Since" column is the
+//! [`WIRE_FORMAT_VERSION`] that introduced the field. Each version only
+//! adds optional fields, so a reader of the current version decodes
+//! messages from every earlier one (see [`Message::decode_any_version`]),
+//! and unknown fields are ignored, so an older reader decodes newer
+//! messages minus the fields it does not know. The fixtures under
+//! `tests/fixtures/wire` hold a message in each version; the tests below
+//! fail if the encoding drifts from them.
+
+use crate::error::{OxideError, Result};
+use crate::message::Message;
+
+/// Version of the JSON layout written by [`Message::to_bytes`]
+///
+/// Bumped whenever a field is added; existing fields never change meaning.
+pub const WIRE_FORMAT_VERSION: u32 = 6;
+
+/// Largest decompressed size accepted for a gzip-compressed message
+pub const MAX_DECOMPRESSED_LEN: usize = 16 * 1024 * 1024;
+
+/// Header marking a message encoded with [`Message::to_bytes_with`]
+const MAGIC: [u8; 3] = *b"\0OX";
+
+const TAG_GZIP: u8 = 1;
+const TAG_MSGPACK: u8 = 2;
+const TAG_CBOR: u8 = 3;
+
+/// Encoding written by [`Message::to_bytes_with`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireFormat {
+    /// Plain JSON without a header, identical to [`Message::to_bytes`]
+    Json,
+    /// gzip-compressed JSON (requires the `gzip` feature)
+    #[cfg(feature = "gzip")]
+    GzipJson,
+    /// MessagePack with named fields (requires the `msgpack` feature)
+    #[cfg(feature = "msgpack")]
+    MsgPack,
+    /// CBOR, as written by `Message::to_cbor` (requires the `cbor` feature)
+    #[cfg(feature = "cbor")]
+    Cbor,
+}
+
+fn serialization_error(e: impl std::fmt::Display) -> OxideError {
+    OxideError::Serialization(e.to_string())
+}
+
+#[cfg(any(feature = "gzip", feature = "msgpack", feature = "cbor"))]
+fn with_header(tag: u8, body: Vec<u8>) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(MAGIC.len() + 1 + body.len());
+    bytes.extend_from_slice(&MAGIC);
+    bytes.push(tag);
+    bytes.extend_from_slice(&body);
+    bytes
+}
+
+pub(crate) fn encode(message: &Message, format: WireFormat) -> Result<Vec<u8>> {
+    match format {
+        WireFormat::Json => serde_json::to_vec(message).map_err(serialization_error),
+        #[cfg(feature = "gzip")]
+        WireFormat::GzipJson => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            serde_json::to_writer(&mut encoder, message).map_err(serialization_error)?;
+            let body = encoder.finish().map_err(serialization_error)?;
+            Ok(with_header(TAG_GZIP, body))
+        }
+        #[cfg(feature = "msgpack")]
+        WireFormat::MsgPack => {
+            let body = rmp_serde::to_vec_named(message).map_err(serialization_error)?;
+            Ok(with_header(TAG_MSGPACK, body))
+        }
+        #[cfg(feature = "cbor")]
+        WireFormat::Cbor => Ok(with_header(TAG_CBOR, message.to_cbor()?)),
+    }
+}
+
+#[cfg_attr(
+    not(any(feature = "gzip", feature = "msgpack", feature = "cbor")),
+    allow(unused_variables)
+)]
+pub(crate) fn decode(bytes: &[u8]) -> Result<Message> {
+    let Some(rest) = bytes.strip_prefix(&MAGIC) else {
+        return serde_json::from_slice(bytes).map_err(serialization_error);
+    };
+    let Some((&tag, body)) = rest.split_first() else {
+        return Err(OxideError::Serialization(
+            "wire header without a format tag".to_string(),
+        ));
+    };
+    match tag {
+        #[cfg(feature = "gzip")]
+        TAG_GZIP => decode_gzip(body, MAX_DECOMPRESSED_LEN),
+        #[cfg(feature = "msgpack")]
+        TAG_MSGPACK => rmp_serde::from_slice(body).map_err(serialization_error),
+        #[cfg(feature = "cbor")]
+        TAG_CBOR => Message::from_cbor(body),
+        #[cfg(not(feature = "gzip"))]
+        TAG_GZIP => Err(missing_feature("gzip")),
+        #[cfg(not(feature = "msgpack"))]
+        TAG_MSGPACK => Err(missing_feature("msgpack")),
+        #[cfg(not(feature = "cbor"))]
+        TAG_CBOR => Err(missing_feature("cbor")),
+        other => Err(OxideError::Serialization(format!(
+            "unknown wire format tag {}",
+            other
+        ))),
+    }
+}
+
+/// Decompress and decode a gzip body, refusing to expand it past `limit`
+#[cfg(feature = "gzip")]
+fn decode_gzip(body: &[u8], limit: usize) -> Result<Message> {
+    use std::io::Read;
+
+    let mut json = Vec::new();
+    flate2::read::GzDecoder::new(body)
+        .take(limit as u64 + 1)
+        .read_to_end(&mut json)
+        .map_err(serialization_error)?;
+    if json.len() > limit {
+        return Err(OxideError::Serialization(format!(
+            "gzip message decompresses to more than {} bytes",
+            limit
+        )));
+    }
+    serde_json::from_slice(&json).map_err(serialization_error)
+}
+
+#[cfg(not(all(feature = "gzip", feature = "msgpack", feature = "cbor")))]
+fn missing_feature(feature: &str) -> OxideError {
+    OxideError::Serialization(format!("message needs the `{}` feature to decode", feature))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::time::Duration;
+
+    fn sample() -> Message {
+        Message::new_versioned("sensors.temp", json!({"celsius": 21.5, "tags": ["a"]}), 2).track()
+    }
+
+    fn assert_same(decoded: &Message, original: &Message) {
+        assert_eq!(decoded.topic, original.topic);
+        assert_eq!(decoded.payload, original.payload);
+        assert_eq!(decoded.version, original.version);
+        assert_eq!(decoded.id, original.id);
+    }
+
+    #[test]
+    fn test_legacy_json_without_header() {
+        let msg = sample();
+        let legacy = serde_json::to_vec(&msg).unwrap();
+        assert_same(&Message::from_bytes(&legacy).unwrap(), &msg);
+        assert_eq!(msg.to_bytes_with(WireFormat::Json).unwrap(), legacy);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip_prefixed() {
+        let msg = sample();
+        let bytes = msg.to_bytes_with(WireFormat::GzipJson).unwrap();
+        assert_eq!(&bytes[..4], b"\0OX\x01");
+        assert_same(&Message::from_bytes(&bytes).unwrap(), &msg);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip_expanding_past_limit_is_rejected() {
+        let msg = Message::new("bomb", json!("0".repeat(64 * 1024)));
+        let bytes = msg.to_bytes_with(WireFormat::GzipJson).unwrap();
+        let body = &bytes[4..];
+        assert!(body.len() < 1024);
+
+        let json_len = msg.to_bytes().unwrap().len();
+        assert_same(&decode_gzip(body, json_len).unwrap(), &msg);
+        assert!(matches!(
+            decode_gzip(body, json_len - 1),
+            Err(OxideError::Serialization(_))
+        ));
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_msgpack_prefixed() {
+        let msg = sample();
+        let bytes = msg.to_bytes_with(WireFormat::MsgPack).unwrap();
+        assert_eq!(&bytes[..4], b"\0OX\x02");
+        assert_same(&Message::from_bytes(&bytes).unwrap(), &msg);
+    }
+
+    /// The message stored in `tests/fixtures/wire/v6.json`
+    fn canonical() -> Message {
+        let mut msg =
+            Message::new_versioned("sensors.temp", json!({"celsius": 21.5, "tags": ["a"]}), 2);
+        msg.id = Some("6f1c2a8e-3b4d-4e5f-8a9b-0c1d2e3f4a5b".to_string());
+        msg.timestamp = Some(1_735_689_600_000);
+        msg.priority = 5;
+        msg.sequence = Some(42);
+        msg.payload_type = Some("sensors::Reading".to_string());
+        msg.with_checksum()
+            .with_ttl(Duration::from_secs(60))
+            .with_header("trace", "4bf92f35")
+    }
+
+    #[test]
+    fn test_encoding_matches_golden_fixture() {
+        let golden = include_bytes!("../tests/fixtures/wire/v6.json");
+        assert_eq!(WIRE_FORMAT_VERSION, 6, "add a fixture for the new version");
+        assert_eq!(
+            String::from_utf8(canonical().to_bytes().unwrap()).unwrap(),
+            std::str::from_utf8(golden).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_every_version_fixture_decodes() {
+        let expected = canonical();
+        let v1 =
+            Message::decode_any_version(include_bytes!("../tests/fixtures/wire/v1.json")).unwrap();
+        assert_eq!(v1.topic, expected.topic);
+        assert_eq!(v1.payload, expected.payload);
+        assert_eq!((v1.version, v1.id, v1.timestamp), (0, None, None));
+
+        let v2 =
+            Message::decode_any_version(include_bytes!("../tests/fixtures/wire/v2.json")).unwrap();
+        assert_same(&v2, &expected);
+        assert_eq!(v2.timestamp, expected.timestamp);
+        assert_eq!((v2.priority, v2.sequence, v2.payload_type), (0, None, None));
+
+        let v3 =
+            Message::decode_any_version(include_bytes!("../tests/fixtures/wire/v3.json")).unwrap();
+        assert_same(&v3, &expected);
+        assert_eq!(v3.priority, expected.priority);
+        assert_eq!(v3.sequence, expected.sequence);
+        assert_eq!(v3.payload_type, expected.payload_type);
+        assert_eq!(v3.checksum, None);
+
+        let v4 =
+            Message::decode_any_version(include_bytes!("../tests/fixtures/wire/v4.json")).unwrap();
+        assert_same(&v4, &expected);
+        assert_eq!(v4.checksum, expected.checksum);
+        v4.verify_checksum().unwrap();
+        assert_eq!(v4.expires_at, None);
+
+        let v5 =
+            Message::decode_any_version(include_bytes!("../tests/fixtures/wire/v5.json")).unwrap();
+        assert_same(&v5, &expected);
+        assert_eq!(v5.expires_at, expected.expires_at);
+        assert!(v5.headers.is_empty());
+
+        let v6 =
+            Message::decode_any_version(include_bytes!("../tests/fixtures/wire/v6.json")).unwrap();
+        assert_same(&v6, &expected);
+        assert_eq!(v6.headers, expected.headers);
+    }
+
+    #[test]
+    fn test_unknown_fields_are_ignored() {
+        let newer = br#"{"topic":"t","payload":1,"from_the_future":true}"#;
+        assert_eq!(
+            Message::decode_any_version(newer).unwrap().payload,
+            json!(1)
+        );
+    }
+
+    #[test]
+    fn test_unknown_tag_is_rejected() {
+        assert!(matches!(
+            Message::from_bytes(b"\0OX\x7f{}"),
+            Err(OxideError::Serialization(_))
+        ));
+    }
+}